@@ -6,48 +6,149 @@ use std::{
 
 use crate::core::{RbxReadExt, RbxWriteExt};
 
+/// The magic number that every zstd frame starts with. Compressed chunks whose
+/// contents start with this are zstd compressed instead of LZ4 compressed.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
 /// Represents one chunk from a binary model file.
 #[derive(Debug)]
 pub struct Chunk {
     pub name: [u8; 4],
     pub data: Vec<u8>,
+    pub compressed: bool,
 }
 
 impl Chunk {
     /// Reads and decodes a `Chunk` from the given reader.
-    pub fn decode<R: Read>(mut reader: R) -> io::Result<Chunk> {
+    pub fn decode<R: Read>(reader: R) -> io::Result<Chunk> {
+        RawChunk::read(reader)?.decompress()
+    }
+}
+
+/// A chunk that has been read from a binary model file, but whose contents
+/// have not been decompressed yet. Decompression is split out so that it can
+/// be done away from the reader, like on another thread.
+#[derive(Debug)]
+pub struct RawChunk {
+    header: ChunkHeader,
+    contents: Vec<u8>,
+}
+
+impl RawChunk {
+    /// Reads a `RawChunk` from the given reader.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<RawChunk> {
         let header = decode_chunk_header(&mut reader)?;
+        RawChunk::read_contents(header, reader)
+    }
 
+    /// Reads the contents of a chunk whose header has already been read.
+    pub(crate) fn read_contents<R: Read>(header: ChunkHeader, reader: R) -> io::Result<RawChunk> {
         log::trace!("{}", header);
 
-        let data = if header.compressed_len == 0 {
-            let mut data = Vec::with_capacity(header.len as usize);
-            reader.take(header.len as u64).read_to_end(&mut data)?;
-            data
+        let stored_len = if header.compressed_len == 0 {
+            header.len
         } else {
-            let mut compressed_data = Vec::with_capacity(header.compressed_len as usize);
-            reader
-                .take(header.compressed_len as u64)
-                .read_to_end(&mut compressed_data)?;
+            header.compressed_len
+        };
+
+        let mut contents = Vec::with_capacity(stored_len as usize);
+        reader.take(stored_len as u64).read_to_end(&mut contents)?;
 
-            lz4::block::decompress(&compressed_data, Some(header.len as i32))?
+        if contents.len() != stored_len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "chunk was {} bytes long, but the file ended after {} bytes",
+                    stored_len,
+                    contents.len()
+                ),
+            ));
+        }
+
+        Ok(RawChunk { header, contents })
+    }
+
+    /// The 4-byte name of the chunk, like `PROP`.
+    pub fn name(&self) -> &[u8; 4] {
+        &self.header.name
+    }
+
+    /// Decompresses the contents of the chunk, if needed.
+    pub fn decompress(self) -> io::Result<Chunk> {
+        let header = self.header;
+
+        let data = if header.compressed_len == 0 {
+            self.contents
+        } else if self.contents.starts_with(&ZSTD_MAGIC) {
+            zstd::bulk::decompress(&self.contents, header.len as usize)?
+        } else {
+            lz4::block::decompress(&self.contents, Some(header.len as i32))?
         };
 
-        assert_eq!(data.len(), header.len as usize);
+        if data.len() != header.len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "chunk decompressed to {} bytes, but its header said it would be {} bytes",
+                    data.len(),
+                    header.len
+                ),
+            ));
+        }
 
         Ok(Chunk {
             name: header.name,
             data,
+            compressed: header.compressed_len != 0,
         })
     }
 }
 
+/// A chunk from a binary model or place that rbx_binary does not understand,
+/// like a `SIGN` chunk or a chunk type introduced after this version of
+/// rbx_binary.
+///
+/// Unknown chunks can be read with
+/// [`Deserializer::deserialize_with_unknown_chunks`][crate::Deserializer::deserialize_with_unknown_chunks]
+/// and written back out with
+/// [`Serializer::serialize_with_unknown_chunks`][crate::Serializer::serialize_with_unknown_chunks]
+/// so that they aren't lost when a file is rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChunk {
+    /// The 4-byte name of the chunk, like `SIGN`.
+    pub name: [u8; 4],
+
+    /// The decompressed contents of the chunk.
+    pub data: Vec<u8>,
+
+    /// Whether the chunk was compressed in the file it came from. The chunk
+    /// will be compressed again when written if this is set.
+    pub compressed: bool,
+}
+
+impl From<Chunk> for UnknownChunk {
+    fn from(chunk: Chunk) -> Self {
+        Self {
+            name: chunk.name,
+            data: chunk.data,
+            compressed: chunk.compressed,
+        }
+    }
+}
+
 /// The compression format of a chunk in the binary model format.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChunkCompression {
     /// The contents of the chunk should be LZ4 compressed.
     Compressed,
 
+    /// The contents of the chunk should be zstd compressed with the given
+    /// compression level. Level 0 uses zstd's default level.
+    Zstd {
+        /// The zstd compression level, from 1 to 22.
+        level: i32,
+    },
+
     /// The contents of the chunk should be uncompressed.
     Uncompressed,
 }
@@ -59,27 +160,41 @@ pub enum ChunkCompression {
 /// automatically.
 #[must_use]
 pub struct ChunkBuilder {
-    chunk_name: &'static [u8],
+    chunk_name: [u8; 4],
     compression: ChunkCompression,
+    threshold: usize,
     buffer: Vec<u8>,
 }
 
 impl ChunkBuilder {
     /// Creates a new `ChunkBuilder` with the given name and compression
     /// setting.
-    pub fn new(chunk_name: &'static [u8], compression: ChunkCompression) -> Self {
+    pub fn new(chunk_name: &[u8; 4], compression: ChunkCompression) -> Self {
         ChunkBuilder {
-            chunk_name,
+            chunk_name: *chunk_name,
             compression,
+            threshold: 0,
             buffer: Vec::new(),
         }
     }
 
+    /// Sets the size, in bytes, that the chunk's contents must reach before
+    /// they're compressed. Smaller chunks are written uncompressed.
+    pub fn with_threshold(self, threshold: usize) -> Self {
+        Self { threshold, ..self }
+    }
+
     /// Consume the chunk and write it to the given writer.
     pub fn dump<W: Write>(self, mut writer: W) -> io::Result<()> {
-        writer.write_all(self.chunk_name)?;
+        writer.write_all(&self.chunk_name)?;
+
+        let compression = if self.buffer.len() < self.threshold {
+            ChunkCompression::Uncompressed
+        } else {
+            self.compression
+        };
 
-        match self.compression {
+        match compression {
             ChunkCompression::Compressed => {
                 let compressed = lz4::block::compress(&self.buffer, None, false)?;
 
@@ -89,6 +204,15 @@ impl ChunkBuilder {
 
                 writer.write_all(&compressed)?;
             }
+            ChunkCompression::Zstd { level } => {
+                let compressed = zstd::bulk::compress(&self.buffer, level)?;
+
+                writer.write_le_u32(compressed.len() as u32)?;
+                writer.write_le_u32(self.buffer.len() as u32)?;
+                writer.write_le_u32(0)?;
+
+                writer.write_all(&compressed)?;
+            }
             ChunkCompression::Uncompressed => {
                 writer.write_le_u32(0)?;
                 writer.write_le_u32(self.buffer.len() as u32)?;
@@ -113,9 +237,9 @@ impl Write for ChunkBuilder {
 }
 
 #[derive(Debug)]
-struct ChunkHeader {
+pub(crate) struct ChunkHeader {
     /// 4-byte short name for the chunk, like "INST" or "PRNT"
-    name: [u8; 4],
+    pub(crate) name: [u8; 4],
 
     /// The length of the chunk's compressed data. For uncompressed chunks, this
     /// is always zero.
@@ -145,7 +269,7 @@ impl fmt::Display for ChunkHeader {
     }
 }
 
-fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
+pub(crate) fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
     let mut name = [0; 4];
     source.read_exact(&mut name)?;
 
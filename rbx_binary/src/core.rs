@@ -10,6 +10,7 @@ use rbx_reflection::{
 pub static FILE_MAGIC_HEADER: &[u8] = b"<roblox!";
 pub static FILE_SIGNATURE: &[u8] = b"\x89\xff\x0d\x0a\x1a\x0a";
 pub const FILE_VERSION: u16 = 0;
+pub static FILE_FOOTER: &[u8] = b"</roblox>";
 
 pub trait RbxReadExt: Read {
     fn read_le_u32(&mut self) -> io::Result<u32> {
@@ -1,24 +1,135 @@
-use std::io;
+use std::{fmt, io, str};
 
 use thiserror::Error;
 
 use crate::types::InvalidTypeError;
 
 /// Represents an error that occurred during deserialization.
-#[derive(Debug, Error)]
-#[error(transparent)]
+///
+/// Along with what went wrong, errors record where in the file it happened, as
+/// far as is known. This information is included when the error is displayed,
+/// and is available separately through methods like [`Error::chunk_name`] and
+/// [`Error::offset`].
+#[derive(Debug)]
 pub struct Error {
     source: Box<InnerError>,
+    location: Box<Location>,
+}
+
+/// Where in a file an error occurred. Every part is optional because errors
+/// can happen before any of them are known, like while reading the file header.
+#[derive(Debug, Default)]
+struct Location {
+    chunk_name: Option<[u8; 4]>,
+    offset: Option<u64>,
+    class_name: Option<String>,
+    property_name: Option<String>,
+    referent: Option<i32>,
+}
+
+impl Error {
+    /// The name of the chunk that was being decoded, like `PROP`.
+    pub fn chunk_name(&self) -> Option<[u8; 4]> {
+        self.location.chunk_name
+    }
+
+    /// The position in the file, in bytes, of the start of the chunk that was
+    /// being decoded.
+    pub fn offset(&self) -> Option<u64> {
+        self.location.offset
+    }
+
+    /// The class of the instances whose property was being decoded.
+    pub fn class_name(&self) -> Option<&str> {
+        self.location.class_name.as_deref()
+    }
+
+    /// The name of the property that was being decoded, as it appears in the
+    /// file.
+    pub fn property_name(&self) -> Option<&str> {
+        self.location.property_name.as_deref()
+    }
+
+    /// The referent that the file gives to the instance that was being decoded.
+    /// This is the ID used in the file's `INST` and `PRNT` chunks, not a `Ref`.
+    pub fn referent(&self) -> Option<i32> {
+        self.location.referent
+    }
+
+    pub(crate) fn with_chunk(mut self, name: Option<[u8; 4]>, offset: u64) -> Self {
+        self.location.chunk_name = self.location.chunk_name.or(name);
+        self.location.offset = self.location.offset.or(Some(offset));
+        self
+    }
+
+    pub(crate) fn with_property(mut self, class_name: &str, property_name: &str) -> Self {
+        if self.location.class_name.is_none() {
+            self.location.class_name = Some(class_name.to_owned());
+            self.location.property_name = Some(property_name.to_owned());
+        }
+        self
+    }
 }
 
 impl From<InnerError> for Error {
     fn from(inner: InnerError) -> Self {
+        let mut location = Location::default();
+
+        let source = match inner {
+            InnerError::Instance { referent, source } => {
+                location.referent = Some(referent);
+                source
+            }
+            inner => Box::new(inner),
+        };
+
         Self {
-            source: Box::new(inner),
+            source,
+            location: Box::new(location),
         }
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+
+        let location = &self.location;
+        let mut parts = Vec::new();
+
+        if let Some(offset) = location.offset {
+            let name = location
+                .chunk_name
+                .as_ref()
+                .and_then(|name| str::from_utf8(name).ok())
+                .map(|name| name.trim_end_matches('\0'))
+                .unwrap_or("chunk");
+
+            parts.push(format!("{} chunk at byte {}", name, offset));
+        }
+        if let (Some(class_name), Some(property_name)) =
+            (&location.class_name, &location.property_name)
+        {
+            parts.push(format!("property {}.{}", class_name, property_name));
+        }
+        if let Some(referent) = location.referent {
+            parts.push(format!("instance {}", referent));
+        }
+
+        if !parts.is_empty() {
+            write!(f, " (in {})", parts.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum InnerError {
     #[error(transparent)]
@@ -86,4 +197,32 @@ pub(crate) enum InnerError {
         prop_name: String,
         class_name: String,
     },
+
+    #[error("Instance was given a parent, but it was not declared")]
+    UnknownInstance,
+
+    #[error("Instance {parent} was given as a parent, but it was not declared")]
+    UnknownParent { parent: i32 },
+
+    #[error("Instance was given more than one parent")]
+    DuplicateParent,
+
+    /// Wraps an error that happened while decoding a value for a specific
+    /// instance. Unwrapped into the location of an `Error`.
+    #[error("{source}")]
+    Instance {
+        referent: i32,
+        source: Box<InnerError>,
+    },
+}
+
+impl InnerError {
+    /// Attaches the referent of the instance that was being decoded when this
+    /// error occurred.
+    pub(crate) fn for_instance(self, referent: i32) -> Self {
+        InnerError::Instance {
+            referent,
+            source: Box::new(self),
+        }
+    }
 }
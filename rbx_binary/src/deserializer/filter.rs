@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+
+use rbx_reflection::ReflectionDatabase;
+
+/// Describes which classes and properties a [`Deserializer`][super::Deserializer]
+/// should decode.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecodeFilter {
+    /// If set, only instances that are one of these classes (or inherit from
+    /// one of them) will have their properties decoded.
+    pub(crate) classes: Option<HashSet<String>>,
+
+    /// If set, only properties with these names will be decoded.
+    pub(crate) properties: Option<HashSet<String>>,
+
+    /// Whether instances that don't match `classes` and have no descendants
+    /// that do should be left out of the tree entirely.
+    pub(crate) prune: bool,
+}
+
+impl DecodeFilter {
+    /// Returns whether instances of the given class should have their
+    /// properties decoded.
+    pub(crate) fn allows_class(&self, database: &ReflectionDatabase, class_name: &str) -> bool {
+        match &self.classes {
+            Some(classes) => classes
+                .iter()
+                .any(|allowed| database.is_a(class_name, allowed)),
+            None => true,
+        }
+    }
+
+    /// Returns whether the property with the given canonical name should be
+    /// decoded. Names are always decoded.
+    pub(crate) fn allows_property(&self, property_name: &str) -> bool {
+        match &self.properties {
+            Some(properties) => property_name == "Name" || properties.contains(property_name),
+            None => true,
+        }
+    }
+
+    /// Returns whether instances that don't match the filter should be left
+    /// out of the tree.
+    pub(crate) fn prunes(&self) -> bool {
+        self.prune && self.classes.is_some()
+    }
+}
@@ -1,17 +1,24 @@
 mod error;
+mod filter;
 mod header;
 mod state;
+mod stream;
 
 use std::{io::Read, str};
 
 use rbx_dom_weak::WeakDom;
 use rbx_reflection::ReflectionDatabase;
 
-use self::state::DeserializerState;
+use crate::chunk::UnknownChunk;
+
+use self::{error::InnerError, filter::DecodeFilter, state::DeserializerState};
 
 pub(crate) use self::header::FileHeader;
 
-pub use self::error::Error;
+pub use self::{
+    error::Error,
+    stream::{DecodeEvent, DecodeStream},
+};
 
 /// A configurable deserializer for Roblox binary models and places.
 ///
@@ -44,10 +51,22 @@ pub use self::error::Error;
 /// A custom [`ReflectionDatabase`][ReflectionDatabase] can be specified via
 /// [`reflection_database`][reflection_database].
 ///
+/// Decoding can be limited to specific classes and properties with
+/// [`only_classes`][only_classes] and [`only_properties`][only_properties],
+/// which can greatly reduce the time and memory needed to read large places.
+///
 /// [ReflectionDatabase]: rbx_reflection::ReflectionDatabase
 /// [reflection_database]: Deserializer#method.reflection_database
+/// [only_classes]: Deserializer#method.only_classes
+/// [only_properties]: Deserializer#method.only_properties
 pub struct Deserializer<'db> {
     database: &'db ReflectionDatabase<'db>,
+    filter: DecodeFilter,
+
+    /// Whether runs of PROP chunks are read in full and then decoded, instead
+    /// of being decoded one at a time. This only helps with the `rayon`
+    /// feature enabled, where they're decoded in parallel.
+    parallel: bool,
 }
 
 impl<'db> Deserializer<'db> {
@@ -55,43 +74,157 @@ impl<'db> Deserializer<'db> {
     pub fn new() -> Self {
         Self {
             database: rbx_reflection_database::get(),
+            filter: DecodeFilter::default(),
+            parallel: cfg!(feature = "rayon"),
         }
     }
 
     /// Sets what reflection database for the deserializer to use.
     #[inline]
     pub fn reflection_database(self, database: &'db ReflectionDatabase<'db>) -> Self {
-        Self { database }
+        Self { database, ..self }
+    }
+
+    /// Only decode properties of instances that are one of the given classes
+    /// or inherit from one of them. Instances of other classes are still
+    /// created with their names unless [`prune_filtered`][prune_filtered] is
+    /// set.
+    ///
+    /// [prune_filtered]: Deserializer#method.prune_filtered
+    pub fn only_classes<I, S>(mut self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.filter.classes = Some(classes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only decode properties with the given canonical names. Instance names
+    /// are always decoded.
+    pub fn only_properties<I, S>(mut self, properties: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.filter.properties = Some(properties.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets whether instances that aren't one of the classes given to
+    /// [`only_classes`][only_classes] should be left out of the tree, unless
+    /// one of their descendants is. Has no effect if `only_classes` isn't
+    /// used.
+    ///
+    /// `Ref` properties that point to pruned instances are left as-is.
+    ///
+    /// [only_classes]: Deserializer#method.only_classes
+    #[inline]
+    pub fn prune_filtered(mut self, prune: bool) -> Self {
+        self.filter.prune = prune;
+        self
+    }
+
+    /// Overrides whether runs of PROP chunks are decoded all at once, so that
+    /// tests can check both ways of reading them.
+    #[cfg(test)]
+    pub(crate) fn parallel(self, parallel: bool) -> Self {
+        Self { parallel, ..self }
+    }
+
+    /// Decodes a file like [`deserialize`][Deserializer::deserialize],
+    /// returning the most PROP chunks that were held in memory at once.
+    #[cfg(test)]
+    pub(crate) fn largest_prop_run<R: Read>(&self, reader: R) -> Result<usize, Error> {
+        let mut deserializer = DeserializerState::new(self, reader)?;
+        read_chunks(&mut deserializer)?;
+
+        Ok(deserializer.largest_prop_run)
     }
 
     /// Deserialize a Roblox binary model or place from the given stream using
     /// this deserializer.
     pub fn deserialize<R: Read>(&self, reader: R) -> Result<WeakDom, Error> {
+        let (dom, _) = self.deserialize_with_unknown_chunks(reader)?;
+
+        Ok(dom)
+    }
+
+    /// Deserialize a Roblox binary model or place from the given stream using
+    /// this deserializer, also returning any chunks that rbx_binary does not
+    /// understand in the order they appeared.
+    ///
+    /// The returned chunks can be passed to
+    /// [`Serializer::serialize_with_unknown_chunks`][crate::Serializer::serialize_with_unknown_chunks]
+    /// to preserve them when writing the file back out.
+    pub fn deserialize_with_unknown_chunks<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<(WeakDom, Vec<UnknownChunk>), Error> {
         profiling::scope!("rbx_binary::deserialize");
 
         let mut deserializer = DeserializerState::new(self, reader)?;
+        let unknown_chunks = read_chunks(&mut deserializer)?;
 
-        loop {
-            let chunk = deserializer.next_chunk()?;
-
-            match &chunk.name {
-                b"META" => deserializer.decode_meta_chunk(&chunk.data)?,
-                b"SSTR" => deserializer.decode_sstr_chunk(&chunk.data)?,
-                b"INST" => deserializer.decode_inst_chunk(&chunk.data)?,
-                b"PROP" => deserializer.decode_prop_chunk(&chunk.data)?,
-                b"PRNT" => deserializer.decode_prnt_chunk(&chunk.data)?,
-                b"END\0" => {
-                    deserializer.decode_end_chunk(&chunk.data)?;
-                    break;
-                }
-                _ => match str::from_utf8(&chunk.name) {
-                    Ok(name) => log::info!("Unknown binary chunk name {}", name),
-                    Err(_) => log::info!("Unknown binary chunk name {:?}", chunk.name),
-                },
-            }
-        }
+        Ok((deserializer.finish(), unknown_chunks))
+    }
 
-        Ok(deserializer.finish())
+    /// Deserialize a Roblox binary model or place from the given stream using
+    /// this deserializer, skipping over any parts of the file that can't be
+    /// decoded instead of returning an error.
+    ///
+    /// This is useful for salvaging damaged files. Chunks that can't be
+    /// decoded are skipped, along with instances that are given a parent that
+    /// doesn't exist. If the file ends early, everything read up to that
+    /// point is kept. The errors that were skipped over are returned in the
+    /// order they were found.
+    ///
+    /// An error is only returned if the file's header can't be read.
+    pub fn deserialize_lenient<R: Read>(&self, reader: R) -> Result<(WeakDom, Vec<Error>), Error> {
+        profiling::scope!("rbx_binary::deserialize_lenient");
+
+        let mut deserializer = DeserializerState::new(self, reader)?;
+        deserializer.recover_errors();
+        read_chunks(&mut deserializer)?;
+
+        let warnings = deserializer.take_warnings();
+
+        Ok((deserializer.finish(), warnings))
+    }
+
+    /// Decode a Roblox binary model or place from the given stream one chunk
+    /// at a time, without building a `WeakDom`.
+    ///
+    /// This is useful for scanning very large files for specific information,
+    /// like every `Content` value in a place. See [`DecodeEvent`] for what
+    /// the returned iterator produces. Chunks for classes and properties that
+    /// are filtered out are skipped, but pruning is not performed.
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    ///
+    /// use rbx_binary::{DecodeEvent, Deserializer};
+    /// use rbx_dom_weak::types::Variant;
+    ///
+    /// let input = BufReader::new(File::open("Place.rbxl")?);
+    ///
+    /// for event in Deserializer::new().deserialize_streaming(input)? {
+    ///     if let DecodeEvent::Property { values, .. } = event? {
+    ///         for (_, value) in values {
+    ///             if let Variant::Content(content) = value {
+    ///                 println!("{}", content.into_string());
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deserialize_streaming<R: Read>(&self, reader: R) -> Result<DecodeStream<'_, R>, Error> {
+        let state = DeserializerState::new(self, reader)?;
+
+        Ok(DecodeStream::new(state))
     }
 }
 
@@ -100,3 +233,86 @@ impl<'db> Default for Deserializer<'db> {
         Self::new()
     }
 }
+
+/// Reads and decodes every chunk in a file into the given state, returning the
+/// chunks that rbx_binary does not understand.
+fn read_chunks<R: Read>(
+    deserializer: &mut DeserializerState<'_, R>,
+) -> Result<Vec<UnknownChunk>, Error> {
+    let mut unknown_chunks = Vec::new();
+
+    // When decoding in parallel, PROP chunks are collected into runs so that
+    // they can be decoded together once a chunk of any other kind is found.
+    // Otherwise, each one is decoded as soon as it's read.
+    let mut prop_chunks = Vec::new();
+
+    loop {
+        // If a chunk can't be read, there's no way to tell where the next one
+        // starts, so decoding stops here.
+        let chunk = match deserializer.next_raw_chunk() {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                deserializer.decode_prop_chunks(prop_chunks)?;
+                deserializer.recover(err)?;
+                break;
+            }
+        };
+
+        if chunk.name() == b"PROP" {
+            prop_chunks.push((deserializer.chunk_offset(), chunk));
+            if !deserializer.parallel() {
+                deserializer.decode_prop_chunks(std::mem::take(&mut prop_chunks))?;
+            }
+            continue;
+        }
+
+        if !prop_chunks.is_empty() {
+            deserializer.decode_prop_chunks(std::mem::take(&mut prop_chunks))?;
+        }
+
+        let chunk = match chunk.decompress() {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let err = deserializer.locate(InnerError::from(err));
+                deserializer.recover(err)?;
+                continue;
+            }
+        };
+
+        let result = match &chunk.name {
+            b"META" => deserializer
+                .decode_meta_chunk(&chunk.data)
+                .map_err(Error::from),
+            b"SSTR" => deserializer
+                .decode_sstr_chunk(&chunk.data)
+                .map_err(Error::from),
+            b"INST" => deserializer
+                .decode_inst_chunk(&chunk.data)
+                .map(|_| ())
+                .map_err(Error::from),
+            b"PRNT" => deserializer.decode_prnt_chunk(&chunk.data),
+            b"END\0" => {
+                deserializer
+                    .decode_end_chunk(&chunk.data)
+                    .map_err(|err| deserializer.locate(err))?;
+                break;
+            }
+            _ => {
+                match str::from_utf8(&chunk.name) {
+                    Ok(name) => log::info!("Unknown binary chunk name {}", name),
+                    Err(_) => log::info!("Unknown binary chunk name {:?}", chunk.name),
+                }
+
+                unknown_chunks.push(chunk.into());
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            let err = deserializer.locate(err);
+            deserializer.recover(err)?;
+        }
+    }
+
+    Ok(unknown_chunks)
+}
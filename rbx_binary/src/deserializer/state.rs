@@ -1,7 +1,9 @@
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet, VecDeque},
     convert::TryInto,
-    io::Read,
+    io::{self, Read},
+    sync::{Arc, Mutex},
 };
 
 use rbx_dom_weak::{
@@ -9,36 +11,42 @@ use rbx_dom_weak::{
         Attributes, Axes, BinaryString, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
         ColorSequenceKeypoint, Content, CustomPhysicalProperties, Enum, Faces, Font, FontStyle,
         FontWeight, MaterialColors, Matrix3, NumberRange, NumberSequence, NumberSequenceKeypoint,
-        PhysicalProperties, Ray, Rect, Ref, SecurityCapabilities, SharedString, Tags, UDim, UDim2,
-        UniqueId, Variant, VariantType, Vector2, Vector3, Vector3int16,
+        Opaque, PhysicalProperties, Ray, Rect, Ref, SecurityCapabilities, SharedString, Tags, UDim,
+        UDim2, UniqueId, Variant, VariantType, Vector2, Vector3, Vector3int16,
     },
     InstanceBuilder, WeakDom,
 };
 use rbx_reflection::{DataType, PropertyKind, PropertySerialization, ReflectionDatabase};
 
 use crate::{
-    chunk::Chunk,
+    chunk::{decode_chunk_header, Chunk, RawChunk},
     core::{find_property_descriptors, RbxReadExt},
+    parallel,
     types::Type,
 };
 
-use super::{error::InnerError, header::FileHeader, Deserializer};
+use super::{
+    error::{Error, InnerError},
+    header::FileHeader,
+    Deserializer,
+};
 
 pub(super) struct DeserializerState<'db, R> {
     /// The user-provided configuration that we should use.
     deserializer: &'db Deserializer<'db>,
 
     /// The input data encoded as a binary model.
-    input: R,
+    input: CountingReader<R>,
+
+    /// The name of the chunk that was read most recently and the offset in the
+    /// file that it started at. Used to say where errors happened.
+    chunk_name: Option<[u8; 4]>,
+    chunk_offset: u64,
 
     /// The tree that instances should be written into. Eventually returned to
     /// the user.
     tree: WeakDom,
 
-    /// The metadata contained in the file, which affects how some constructs
-    /// are interpreted by Roblox.
-    metadata: HashMap<String, String>,
-
     /// The SharedStrings contained in the file, if any, in the order that they
     /// appear in the file.
     shared_strings: Vec<SharedString>,
@@ -56,7 +64,42 @@ pub(super) struct DeserializerState<'db, R> {
     /// Contains a set of unknown type IDs that we've encountered so far while
     /// deserializing this file. We use this map in order to ensure we only
     /// print one warning per unknown type ID when deserializing a file.
-    unknown_type_ids: HashSet<u8>,
+    unknown_type_ids: Mutex<HashSet<u8>>,
+
+    /// The errors that have been skipped over when decoding leniently, or
+    /// `None` if any error should stop decoding.
+    warnings: Option<Vec<Error>>,
+
+    /// The most PROP chunks that have been decoded at once.
+    #[cfg(test)]
+    pub(super) largest_prop_run: usize,
+}
+
+/// Wraps the input of a `DeserializerState` to keep track of how far into the
+/// file we've read.
+struct CountingReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+/// The parts of `DeserializerState` that are needed to decode PROP chunks.
+/// This is split out from `DeserializerState` so that it can be shared between
+/// threads without the input needing to be shared too.
+pub(super) struct PropReader<'a, 'db> {
+    deserializer: &'db Deserializer<'db>,
+    shared_strings: &'a [SharedString],
+    type_infos: &'a HashMap<u32, TypeInfo>,
+    instances_by_ref: &'a HashMap<i32, Instance>,
+    unknown_type_ids: &'a Mutex<HashSet<u8>>,
 }
 
 /// Represents a unique instance class. Binary models define all their instance
@@ -82,6 +125,9 @@ struct Instance {
 
     /// Document-defined IDs for the children of this instance.
     children: Vec<i32>,
+
+    /// Whether a PRNT chunk has given this instance a parent yet.
+    has_parent: bool,
 }
 
 /// Properties may be serialized under different names or types than
@@ -90,18 +136,34 @@ struct Instance {
 /// contains a migration for some properties Roblox has replaced with
 /// others (like Font, which has been superceded by FontFace).
 #[derive(Debug)]
-struct CanonicalProperty<'db> {
-    name: &'db str,
-    ty: VariantType,
+pub(super) struct CanonicalProperty<'db> {
+    pub(super) name: Cow<'db, str>,
+    pub(super) ty: VariantType,
     migration: Option<&'db PropertySerialization<'db>>,
 }
 
-fn find_canonical_property<'de>(
-    database: &'de ReflectionDatabase,
+/// The values decoded from a PROP chunk, in the same order as the referents of
+/// the chunk's type.
+pub(super) struct DecodedProp<'db> {
+    /// The ID of the type that the chunk belongs to.
+    pub(super) type_id: u32,
+
+    /// The canonical name of the property.
+    pub(super) name: String,
+
+    /// Information about the property, or `None` if this chunk contains the
+    /// names of instances.
+    pub(super) property: Option<CanonicalProperty<'db>>,
+
+    pub(super) values: Vec<Variant>,
+}
+
+fn find_canonical_property<'db>(
+    database: &'db ReflectionDatabase<'db>,
     binary_type: Type,
     class_name: &str,
-    prop_name: &'de str,
-) -> Option<CanonicalProperty<'de>> {
+    prop_name: &str,
+) -> Option<CanonicalProperty<'db>> {
     match find_property_descriptors(database, class_name, prop_name) {
         Some(descriptors) => {
             // If this descriptor is known but wasn't supposed to be
@@ -154,7 +216,7 @@ fn find_canonical_property<'de>(
             );
 
             Some(CanonicalProperty {
-                name: canonical_name,
+                name: Cow::Borrowed(canonical_name),
                 ty: canonical_type,
                 migration,
             })
@@ -171,7 +233,7 @@ fn find_canonical_property<'de>(
             log::trace!("Unknown prop, using type {:?}", canonical_type);
 
             Some(CanonicalProperty {
-                name: prop_name,
+                name: Cow::Owned(prop_name.to_owned()),
                 ty: canonical_type,
                 migration: None,
             })
@@ -182,7 +244,7 @@ fn find_canonical_property<'de>(
 fn add_property(instance: &mut Instance, canonical_property: &CanonicalProperty, value: Variant) {
     if let Some(PropertySerialization::Migrate(migration)) = canonical_property.migration {
         let new_property_name = &migration.new_property_name;
-        let old_property_name = canonical_property.name;
+        let old_property_name = &canonical_property.name;
 
         if !instance.builder.has_property(new_property_name) {
             log::trace!(
@@ -206,15 +268,16 @@ fn add_property(instance: &mut Instance, canonical_property: &CanonicalProperty,
     } else {
         instance
             .builder
-            .add_property(canonical_property.name, value)
+            .add_property(canonical_property.name.as_ref(), value)
     }
 }
 
 impl<'db, R: Read> DeserializerState<'db, R> {
-    pub(super) fn new(
-        deserializer: &'db Deserializer<'db>,
-        mut input: R,
-    ) -> Result<Self, InnerError> {
+    pub(super) fn new(deserializer: &'db Deserializer<'db>, input: R) -> Result<Self, InnerError> {
+        let mut input = CountingReader {
+            inner: input,
+            position: 0,
+        };
         let tree = WeakDom::new(InstanceBuilder::new("DataModel"));
 
         let header = FileHeader::decode(&mut input)?;
@@ -225,33 +288,110 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         Ok(DeserializerState {
             deserializer,
             input,
+            chunk_name: None,
+            chunk_offset: 0,
             tree,
-            metadata: HashMap::new(),
             shared_strings: Vec::new(),
             type_infos,
             instances_by_ref,
             root_instance_refs: Vec::new(),
-            unknown_type_ids: HashSet::new(),
+            unknown_type_ids: Mutex::new(HashSet::new()),
+            warnings: None,
+            #[cfg(test)]
+            largest_prop_run: 0,
         })
     }
 
-    pub(super) fn next_chunk(&mut self) -> Result<Chunk, InnerError> {
-        Ok(Chunk::decode(&mut self.input)?)
+    pub(super) fn next_chunk(&mut self) -> Result<Chunk, Error> {
+        let chunk = self.next_raw_chunk()?;
+        chunk
+            .decompress()
+            .map_err(|err| self.locate(InnerError::from(err)))
+    }
+
+    /// Reads the next chunk without decompressing it.
+    pub(super) fn next_raw_chunk(&mut self) -> Result<RawChunk, Error> {
+        self.chunk_name = None;
+        self.chunk_offset = self.input.position;
+
+        // The name is recorded as soon as the header is read so that errors in
+        // the rest of the chunk, like the file ending early, can be located.
+        let header = decode_chunk_header(&mut self.input)
+            .map_err(|err| self.locate(InnerError::from(err)))?;
+        self.chunk_name = Some(header.name);
+
+        RawChunk::read_contents(header, &mut self.input)
+            .map_err(|err| self.locate(InnerError::from(err)))
+    }
+
+    /// The offset in the file of the chunk that was read most recently.
+    pub(super) fn chunk_offset(&self) -> u64 {
+        self.chunk_offset
+    }
+
+    /// Whether runs of PROP chunks should be decoded all at once.
+    pub(super) fn parallel(&self) -> bool {
+        self.deserializer.parallel
+    }
+
+    /// Makes errors in chunks and instances be recorded as warnings instead of
+    /// stopping decoding. See [`Deserializer::deserialize_lenient`].
+    pub(super) fn recover_errors(&mut self) {
+        self.warnings = Some(Vec::new());
+    }
+
+    /// Called when part of the file can't be decoded. When decoding leniently,
+    /// the error is recorded so that decoding can continue past it. Otherwise,
+    /// it's returned.
+    pub(super) fn recover(&mut self, error: Error) -> Result<(), Error> {
+        match &mut self.warnings {
+            Some(warnings) => {
+                log::warn!("Skipping part of binary file: {}", error);
+                warnings.push(error);
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Takes the errors that have been recovered from so far.
+    pub(super) fn take_warnings(&mut self) -> Vec<Error> {
+        self.warnings
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Attaches the location of the chunk that was read most recently to an
+    /// error.
+    pub(super) fn locate(&self, error: impl Into<Error>) -> Error {
+        error.into().with_chunk(self.chunk_name, self.chunk_offset)
     }
 
     #[profiling::function]
-    pub(super) fn decode_meta_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
+    pub(super) fn decode_meta_chunk(&mut self, chunk: &[u8]) -> Result<(), InnerError> {
+        let entries = self.read_meta_chunk(chunk)?;
+        self.tree.metadata_mut().extend(entries);
+
+        Ok(())
+    }
+
+    /// Decodes the key-value pairs in a META chunk, in the order they appear.
+    pub(super) fn read_meta_chunk(
+        &mut self,
+        mut chunk: &[u8],
+    ) -> Result<Vec<(String, String)>, InnerError> {
         let len = chunk.read_le_u32()?;
-        self.metadata.reserve(len as usize);
+        let mut entries = Vec::with_capacity(len as usize);
 
         for _ in 0..len {
             let key = chunk.read_string()?;
             let value = chunk.read_string()?;
 
-            self.metadata.insert(key, value);
+            entries.push((key, value));
         }
 
-        Ok(())
+        Ok(entries)
     }
 
     #[profiling::function]
@@ -276,8 +416,9 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         Ok(())
     }
 
+    /// Decodes an INST chunk, returning the type ID it declares.
     #[profiling::function]
-    pub(super) fn decode_inst_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
+    pub(super) fn decode_inst_chunk(&mut self, mut chunk: &[u8]) -> Result<u32, InnerError> {
         let type_id = chunk.read_le_u32()?;
         let type_name = chunk.read_string()?;
         let object_format = chunk.read_u8()?;
@@ -302,6 +443,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                 Instance {
                     builder: InstanceBuilder::new(&type_name),
                     children: Vec::new(),
+                    has_parent: false,
                 },
             );
         }
@@ -315,19 +457,120 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
         );
 
-        Ok(())
+        Ok(type_id)
     }
 
+    /// Decompresses and decodes a run of PROP chunks, each paired with its
+    /// offset in the file. PROP chunks only depend on the chunks that come
+    /// before them, so with the `rayon` feature enabled, they're decoded in
+    /// parallel.
     #[profiling::function]
-    pub(super) fn decode_prop_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
-        let type_id = chunk.read_le_u32()?;
-        let prop_name = chunk.read_string()?;
+    pub(super) fn decode_prop_chunks(&mut self, chunks: Vec<(u64, RawChunk)>) -> Result<(), Error> {
+        #[cfg(test)]
+        {
+            self.largest_prop_run = self.largest_prop_run.max(chunks.len());
+        }
+
+        let reader = self.prop_reader();
+        let props = parallel::map(chunks, |(offset, chunk)| {
+            let name = *chunk.name();
+            let decoded = match chunk.decompress() {
+                Ok(chunk) => reader.read_prop_chunk(&chunk.data),
+                Err(err) => Err(InnerError::from(err).into()),
+            };
+
+            decoded.map_err(|err| err.with_chunk(Some(name), offset))
+        });
+
+        for prop in props {
+            match prop {
+                Ok(Some(prop)) => self.add_prop(prop),
+                Ok(None) => {}
+                Err(err) => self.recover(err)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the values decoded from a PROP chunk to their instances.
+    fn add_prop(&mut self, prop: DecodedProp) {
+        let type_info = &self.type_infos[&prop.type_id];
+
+        for (referent, value) in type_info.referents.iter().zip(prop.values) {
+            let instance = self.instances_by_ref.get_mut(referent).unwrap();
+
+            match &prop.property {
+                Some(property) => add_property(instance, property, value),
+                None => {
+                    if let Variant::String(name) = value {
+                        instance.builder.set_name(name);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes the values in a PROP chunk without adding them to any
+    /// instances. See [`PropReader::read_prop_chunk`].
+    pub(super) fn read_prop_chunk(&self, chunk: &[u8]) -> Result<Option<DecodedProp<'db>>, Error> {
+        self.prop_reader().read_prop_chunk(chunk)
+    }
+
+    fn prop_reader(&self) -> PropReader<'_, 'db> {
+        PropReader {
+            deserializer: self.deserializer,
+            shared_strings: &self.shared_strings,
+            type_infos: &self.type_infos,
+            instances_by_ref: &self.instances_by_ref,
+            unknown_type_ids: &self.unknown_type_ids,
+        }
+    }
+}
+
+impl<'a, 'db> PropReader<'a, 'db> {
+    /// Decodes the values in a PROP chunk, one for each instance of the chunk's
+    /// type, without adding them to any instances.
+    ///
+    /// Returns `None` if the chunk should be skipped.
+    pub(super) fn read_prop_chunk(
+        &self,
+        mut chunk: &[u8],
+    ) -> Result<Option<DecodedProp<'db>>, Error> {
+        let type_id = chunk.read_le_u32().map_err(InnerError::from)?;
+        let prop_name = chunk.read_string().map_err(InnerError::from)?;
 
         let type_info = self
             .type_infos
             .get(&type_id)
             .ok_or(InnerError::InvalidTypeId { type_id })?;
 
+        self.read_prop_values(type_id, type_info, prop_name.clone(), chunk)
+            .map_err(|err| Error::from(err).with_property(&type_info.type_name, &prop_name))
+    }
+
+    /// Decodes the rest of a PROP chunk after its header.
+    fn read_prop_values(
+        &self,
+        type_id: u32,
+        type_info: &TypeInfo,
+        prop_name: String,
+        mut chunk: &[u8],
+    ) -> Result<Option<DecodedProp<'db>>, InnerError> {
+        if prop_name != "Name"
+            && !self
+                .deserializer
+                .filter
+                .allows_class(self.deserializer.database, &type_info.type_name)
+        {
+            log::trace!(
+                "Skipping PROP chunk {}.{} because its class was filtered out",
+                type_info.type_name,
+                prop_name
+            );
+            return Ok(None);
+        }
+
         // PROP chunks that contain no type byte are ignored by Roblox. This can
         // happen when a new type is introduced.
         //
@@ -337,13 +580,18 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         // that end immediately after the prop name, so we do the same.
         let binary_type_byte = match chunk.read_u8() {
             Ok(byte) => byte,
-            Err(_) => return Ok(()),
+            Err(_) => return Ok(None),
         };
 
         let binary_type: Type = match binary_type_byte.try_into() {
             Ok(ty) => ty,
             Err(_) => {
-                if self.unknown_type_ids.insert(binary_type_byte) {
+                if self
+                    .unknown_type_ids
+                    .lock()
+                    .unwrap()
+                    .insert(binary_type_byte)
+                {
                     log::warn!(
                         "Unknown value type ID {byte:#04x} ({byte}) in Roblox \
                          binary model file. Found in property {class}.{prop}.",
@@ -353,7 +601,29 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     );
                 }
 
-                return Ok(());
+                if !self.deserializer.filter.allows_property(&prop_name) {
+                    return Ok(None);
+                }
+
+                // We can't tell where one value ends and the next begins, so
+                // every instance gets a handle to the entire chunk's data.
+                let data: Arc<[u8]> = Arc::from(chunk);
+                let values = (0..type_info.referents.len())
+                    .map(|index| {
+                        Opaque::binary(binary_type_byte, index as u32, data.clone()).into()
+                    })
+                    .collect();
+
+                return Ok(Some(DecodedProp {
+                    type_id,
+                    name: prop_name.clone(),
+                    property: Some(CanonicalProperty {
+                        name: Cow::Owned(prop_name),
+                        ty: VariantType::Opaque,
+                        migration: None,
+                    }),
+                    values,
+                }));
             }
         };
 
@@ -372,13 +642,17 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             // path, we should use the reflection database to figure out its
             // default name. This should be rare: effectively never!
 
-            for referent in &type_info.referents {
-                let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                let value = chunk.read_string()?;
-                instance.builder.set_name(value);
+            let mut names = Vec::with_capacity(type_info.referents.len());
+            for _ in 0..type_info.referents.len() {
+                names.push(Variant::String(chunk.read_string()?));
             }
 
-            return Ok(());
+            return Ok(Some(DecodedProp {
+                type_id,
+                name: prop_name,
+                property: None,
+                values: names,
+            }));
         }
 
         let property = if let Some(property) = find_canonical_property(
@@ -389,37 +663,53 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         ) {
             property
         } else {
-            return Ok(());
+            return Ok(None);
         };
 
+        if !self.deserializer.filter.allows_property(&property.name) {
+            let migrated_allowed = match property.migration {
+                Some(PropertySerialization::Migrate(migration)) => self
+                    .deserializer
+                    .filter
+                    .allows_property(&migration.new_property_name),
+                _ => false,
+            };
+
+            if !migrated_allowed {
+                log::trace!(
+                    "Skipping PROP chunk {}.{} because it was filtered out",
+                    type_info.type_name,
+                    prop_name
+                );
+                return Ok(None);
+            }
+        }
+
         let canonical_type = property.ty;
+        let mut decoded = Vec::with_capacity(type_info.referents.len());
 
         match binary_type {
             Type::String => match canonical_type {
                 VariantType::String => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value = chunk.read_string()?;
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 VariantType::Content => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value: Content = chunk.read_string()?.into();
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 VariantType::BinaryString => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value: BinaryString = chunk.read_binary_string()?.into();
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 VariantType::Tags => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for &referent in &type_info.referents {
                         let buffer = chunk.read_binary_string()?;
 
                         let value = Tags::decode(buffer.as_ref()).map_err(|_| {
@@ -429,42 +719,43 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                                 valid_value: "a list of valid null-delimited UTF-8 strings",
                                 actual_value: "invalid UTF-8".to_string(),
                             }
+                            .for_instance(referent)
                         })?;
 
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 VariantType::Attributes => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for &referent in &type_info.referents {
                         let buffer = chunk.read_binary_string()?;
 
                         match Attributes::from_reader(buffer.as_slice()) {
                             Ok(value) => {
-                                add_property(instance, &property, value.into());
+                                decoded.push(value.into());
                             }
                             Err(err) => {
                                 return Err(InnerError::BadPropertyValue {
                                     source: err,
                                     class_name: type_info.type_name.to_string(),
                                     prop_name,
-                                })
+                                }
+                                .for_instance(referent))
                             }
                         }
                     }
                 }
                 VariantType::MaterialColors => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for &referent in &type_info.referents {
                         let buffer = chunk.read_binary_string()?;
                         match MaterialColors::decode(&buffer) {
-                            Ok(value) => add_property(instance, &property, value.into()),
+                            Ok(value) => decoded.push(value.into()),
                             Err(err) => {
                                 return Err(InnerError::BadPropertyValue {
                                     source: err,
                                     class_name: type_info.type_name.to_string(),
                                     prop_name,
-                                })
+                                }
+                                .for_instance(referent));
                             }
                         }
                     }
@@ -480,10 +771,9 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Bool => match canonical_type {
                 VariantType::Bool => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value = chunk.read_bool()?;
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -500,9 +790,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_i32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 // This branch allows values serialized as Int32 to be converted to Int64 when we expect a Int64
@@ -512,10 +801,9 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_i32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for value in values {
                         let value_converted = i64::from(value);
-                        add_property(instance, &property, value_converted.into());
+                        decoded.push(value_converted.into());
                     }
                 }
                 invalid_type => {
@@ -532,9 +820,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0.0; type_info.referents.len()];
                     chunk.read_interleaved_f32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -548,10 +835,9 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Float64 => match canonical_type {
                 VariantType::Float64 => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value = chunk.read_le_f64()?;
-                        add_property(instance, &property, value.into());
+                        decoded.push(value.into());
                     }
                 }
                 // This branch allows values serialized as Float32 to be converted to Float64 when we expect a Float64
@@ -561,10 +847,9 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0.0; type_info.referents.len()];
                     chunk.read_interleaved_f32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for value in values {
                         let converted_value = f64::from(value);
-                        add_property(instance, &property, converted_value.into());
+                        decoded.push(converted_value.into());
                     }
                 }
                 invalid_type => {
@@ -589,9 +874,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .zip(offsets)
                         .map(|(scale, offset)| UDim::new(scale, offset));
 
-                    for (value, referent) in values.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -628,9 +912,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
 
                     let values = x.zip(y).map(|(x, y)| UDim2::new(x, y));
 
-                    for (value, referent) in values.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -644,7 +927,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Ray => match canonical_type {
                 VariantType::Ray => {
-                    for referent in &type_info.referents {
+                    for _ in 0..type_info.referents.len() {
                         let origin_x = chunk.read_le_f32()?;
                         let origin_y = chunk.read_le_f32()?;
                         let origin_z = chunk.read_le_f32()?;
@@ -652,11 +935,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         let direction_y = chunk.read_le_f32()?;
                         let direction_z = chunk.read_le_f32()?;
 
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-
-                        add_property(
-                            instance,
-                            &property,
+                        decoded.push(
                             Ray::new(
                                 Vector3::new(origin_x, origin_y, origin_z),
                                 Vector3::new(direction_x, direction_y, direction_z),
@@ -676,18 +955,19 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Faces => match canonical_type {
                 VariantType::Faces => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for &referent in &type_info.referents {
                         let value = chunk.read_u8()?;
-                        let faces =
-                            Faces::from_bits(value).ok_or_else(|| InnerError::InvalidPropData {
+                        let faces = Faces::from_bits(value).ok_or_else(|| {
+                            InnerError::InvalidPropData {
                                 type_name: type_info.type_name.clone(),
                                 prop_name: prop_name.clone(),
                                 valid_value: "less than 63",
                                 actual_value: value.to_string(),
-                            })?;
+                            }
+                            .for_instance(referent)
+                        })?;
 
-                        add_property(instance, &property, faces.into());
+                        decoded.push(faces.into());
                     }
                 }
                 invalid_type => {
@@ -701,19 +981,20 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Axes => match canonical_type {
                 VariantType::Axes => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for &referent in &type_info.referents {
                         let value = chunk.read_u8()?;
 
-                        let axes =
-                            Axes::from_bits(value).ok_or_else(|| InnerError::InvalidPropData {
+                        let axes = Axes::from_bits(value).ok_or_else(|| {
+                            InnerError::InvalidPropData {
                                 type_name: type_info.type_name.clone(),
                                 prop_name: prop_name.clone(),
                                 valid_value: "less than 7",
                                 actual_value: value.to_string(),
-                            })?;
+                            }
+                            .for_instance(referent)
+                        })?;
 
-                        add_property(instance, &property, axes.into());
+                        decoded.push(axes.into());
                     }
                 }
                 invalid_type => {
@@ -730,20 +1011,22 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_u32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for (value, &referent) in values.into_iter().zip(&type_info.referents) {
                         let color = value
                             .try_into()
                             .ok()
                             .and_then(BrickColor::from_number)
-                            .ok_or_else(|| InnerError::InvalidPropData {
-                                type_name: type_info.type_name.clone(),
-                                prop_name: prop_name.clone(),
-                                valid_value: "a valid BrickColor",
-                                actual_value: value.to_string(),
+                            .ok_or_else(|| {
+                                InnerError::InvalidPropData {
+                                    type_name: type_info.type_name.clone(),
+                                    prop_name: prop_name.clone(),
+                                    valid_value: "a valid BrickColor",
+                                    actual_value: value.to_string(),
+                                }
+                                .for_instance(referent)
                             })?;
 
-                        add_property(instance, &property, color.into());
+                        decoded.push(color.into());
                     }
                 }
                 invalid_type => {
@@ -771,9 +1054,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .zip(b)
                         .map(|((r, g), b)| Color3::new(r, g, b));
 
-                    for (color, referent) in colors.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, color.into());
+                    for color in colors {
+                        decoded.push(color.into());
                     }
                 }
                 invalid_type => {
@@ -795,9 +1077,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
 
                     let values = x.into_iter().zip(y).map(|(x, y)| Vector2::new(x, y));
 
-                    for (value, referent) in values.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -825,9 +1106,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .zip(z)
                         .map(|((x, y), z)| Vector3::new(x, y, z));
 
-                    for (value, referent) in values.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -844,7 +1124,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let referents = &type_info.referents;
                     let mut rotations = Vec::with_capacity(referents.len());
 
-                    for _ in 0..referents.len() {
+                    for &referent in referents {
                         let id = chunk.read_u8()?;
                         if id == 0 {
                             rotations.push(Matrix3::new(
@@ -871,7 +1151,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                                 type_name: type_info.type_name.clone(),
                                 prop_name,
                                 id,
-                            });
+                            }
+                            .for_instance(referent));
                         }
                     }
 
@@ -891,9 +1172,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .zip(rotations)
                         .map(|(position, rotation)| CFrame::new(position, rotation));
 
-                    for (cframe, referent) in values.zip(referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, cframe.into());
+                    for cframe in values {
+                        decoded.push(cframe.into());
                     }
                 }
                 invalid_type => {
@@ -910,9 +1190,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_u32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, Enum::from_u32(value).into());
+                    for value in values {
+                        decoded.push(Enum::from_u32(value).into());
                     }
                 }
                 invalid_type => {
@@ -929,15 +1208,13 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut refs = vec![0; type_info.referents.len()];
                     chunk.read_referent_array(&mut refs)?;
 
-                    for (value, referent) in refs.into_iter().zip(&type_info.referents) {
+                    for value in refs {
                         let rbx_value = if let Some(instance) = self.instances_by_ref.get(&value) {
                             instance.builder.referent()
                         } else {
                             Ref::none()
                         };
-
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, rbx_value.into());
+                        decoded.push(rbx_value.into());
                     }
                 }
                 invalid_type => {
@@ -951,18 +1228,15 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Vector3int16 => match canonical_type {
                 VariantType::Vector3int16 => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(
-                            instance,
-                            &property,
+                    for _ in 0..type_info.referents.len() {
+                        decoded.push(
                             Vector3int16::new(
                                 chunk.read_le_i16()?,
                                 chunk.read_le_i16()?,
                                 chunk.read_le_i16()?,
                             )
                             .into(),
-                        )
+                        );
                     }
                 }
                 invalid_type => {
@@ -976,9 +1250,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::Font => match canonical_type {
                 VariantType::Font => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-
+                    for _ in 0..type_info.referents.len() {
                         let family = chunk.read_string()?;
                         let weight = FontWeight::from_u16(chunk.read_le_u16()?).unwrap_or_default();
                         let style = FontStyle::from_u8(chunk.read_u8()?).unwrap_or_default();
@@ -990,9 +1262,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                             Some(cached_face_id)
                         };
 
-                        add_property(
-                            instance,
-                            &property,
+                        decoded.push(
                             Font {
                                 family,
                                 weight,
@@ -1014,8 +1284,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::NumberSequence => match canonical_type {
                 VariantType::NumberSequence => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let keypoint_count = chunk.read_le_u32()?;
                         let mut keypoints = Vec::with_capacity(keypoint_count as usize);
 
@@ -1027,7 +1296,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                             ))
                         }
 
-                        add_property(instance, &property, NumberSequence { keypoints }.into())
+                        decoded.push(NumberSequence { keypoints }.into());
                     }
                 }
                 invalid_type => {
@@ -1041,8 +1310,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::ColorSequence => match canonical_type {
                 VariantType::ColorSequence => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let keypoint_count = chunk.read_le_u32()? as usize;
                         let mut keypoints = Vec::with_capacity(keypoint_count);
 
@@ -1060,7 +1328,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                             chunk.read_le_f32()?;
                         }
 
-                        add_property(instance, &property, ColorSequence { keypoints }.into())
+                        decoded.push(ColorSequence { keypoints }.into());
                     }
                 }
                 invalid_type => {
@@ -1074,13 +1342,10 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::NumberRange => match canonical_type {
                 VariantType::NumberRange => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(
-                            instance,
-                            &property,
+                    for _ in 0..type_info.referents.len() {
+                        decoded.push(
                             NumberRange::new(chunk.read_le_f32()?, chunk.read_le_f32()?).into(),
-                        )
+                        );
                     }
                 }
                 invalid_type => {
@@ -1111,9 +1376,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         },
                     );
 
-                    for (value, referent) in values.zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into())
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -1127,8 +1391,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
             Type::PhysicalProperties => match canonical_type {
                 VariantType::PhysicalProperties => {
-                    for referent in &type_info.referents {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                    for _ in 0..type_info.referents.len() {
                         let value = if chunk.read_u8()? == 1 {
                             Variant::PhysicalProperties(PhysicalProperties::Custom(
                                 CustomPhysicalProperties {
@@ -1143,7 +1406,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                             Variant::PhysicalProperties(PhysicalProperties::Default)
                         };
 
-                        add_property(instance, &property, value);
+                        decoded.push(value);
                     }
                 }
                 invalid_type => {
@@ -1172,9 +1435,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .zip(b)
                         .map(|((r, g), b)| Color3uint8::new(r, g, b));
 
-                    for (color, referent) in colors.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, color.into());
+                    for color in colors {
+                        decoded.push(color.into());
                     }
                 }
                 invalid_type => {
@@ -1191,9 +1453,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_i64_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into());
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -1210,7 +1471,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_u32_array(&mut values)?;
 
-                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                    for (value, &referent) in values.into_iter().zip(&type_info.referents) {
                         let shared_string =
                             self.shared_strings.get(value as usize).ok_or_else(|| {
                                 InnerError::InvalidPropData {
@@ -1219,11 +1480,10 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                                     valid_value: "a valid SharedString",
                                     actual_value: format!("{:?}", value),
                                 }
+                                .for_instance(referent)
                             })?;
 
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-
-                        add_property(instance, &property, shared_string.clone().into());
+                        decoded.push(shared_string.clone().into());
                     }
                 }
                 invalid_type => {
@@ -1252,7 +1512,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         });
                     }
 
-                    for _ in 0..referents.len() {
+                    for &referent in referents {
                         let id = chunk.read_u8()?;
                         if id == 0 {
                             rotations.push(Matrix3::new(
@@ -1279,7 +1539,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                                 type_name: type_info.type_name.clone(),
                                 prop_name,
                                 id,
-                            });
+                            }
+                            .for_instance(referent));
                         }
                     }
 
@@ -1317,9 +1578,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                             }
                         });
 
-                    for (cframe, referent) in values.zip(referents) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, cframe.into());
+                    for cframe in values {
+                        decoded.push(cframe.into());
                     }
                 }
                 invalid_type => {
@@ -1337,19 +1597,16 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                     let mut values = vec![[0; 16]; n];
                     chunk.read_interleaved_bytes::<16>(&mut values)?;
 
-                    for (i, referent) in type_info.referents.iter().enumerate() {
-                        let mut value = values[i].as_slice();
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(
-                            instance,
-                            &property,
+                    for value in &values {
+                        let mut value = value.as_slice();
+                        decoded.push(
                             UniqueId::new(
                                 value.read_be_u32()?,
                                 value.read_be_u32()?,
                                 value.read_be_i64()?.rotate_right(1),
                             )
                             .into(),
-                        )
+                        );
                     }
                 }
                 invalid_type => {
@@ -1372,9 +1629,8 @@ impl<'db, R: Read> DeserializerState<'db, R> {
                         .map(|value| SecurityCapabilities::from_bits(value as u64))
                         .collect();
 
-                    for (referent, value) in type_info.referents.iter().zip(values) {
-                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        add_property(instance, &property, value.into())
+                    for value in values {
+                        decoded.push(value.into());
                     }
                 }
                 invalid_type => {
@@ -1388,11 +1644,59 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             },
         }
 
-        Ok(())
+        Ok(Some(DecodedProp {
+            type_id,
+            name: property.name.to_string(),
+            property: Some(property),
+            values: decoded,
+        }))
     }
+}
 
+impl<'db, R: Read> DeserializerState<'db, R> {
     #[profiling::function]
-    pub(super) fn decode_prnt_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
+    pub(super) fn decode_prnt_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        for (id, parent_ref) in self.read_prnt_chunk(chunk)? {
+            let has_parent = match self.instances_by_ref.get(&id) {
+                Some(instance) => instance.has_parent,
+                None => {
+                    let error = self.locate(InnerError::UnknownInstance.for_instance(id));
+                    self.recover(error)?;
+                    continue;
+                }
+            };
+
+            // Only the first parent an instance is given is used.
+            if has_parent {
+                let error = self.locate(InnerError::DuplicateParent.for_instance(id));
+                self.recover(error)?;
+                continue;
+            }
+
+            if parent_ref == -1 {
+                self.root_instance_refs.push(id);
+            } else if let Some(instance) = self.instances_by_ref.get_mut(&parent_ref) {
+                instance.children.push(id);
+            } else {
+                let error = InnerError::UnknownParent { parent: parent_ref }.for_instance(id);
+                let error = self.locate(error);
+                self.recover(error)?;
+                continue;
+            }
+
+            self.instances_by_ref.get_mut(&id).unwrap().has_parent = true;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the pairs of document-defined IDs for instances and their
+    /// parents contained in a PRNT chunk. Instances with no parent have a
+    /// parent ID of -1.
+    pub(super) fn read_prnt_chunk(
+        &mut self,
+        mut chunk: &[u8],
+    ) -> Result<Vec<(i32, i32)>, InnerError> {
         let version = chunk.read_u8()?;
 
         if version != 0 {
@@ -1412,16 +1716,7 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         chunk.read_referent_array(&mut subjects)?;
         chunk.read_referent_array(&mut parents)?;
 
-        for (id, parent_ref) in subjects.iter().copied().zip(parents.iter().copied()) {
-            if parent_ref == -1 {
-                self.root_instance_refs.push(id);
-            } else {
-                let instance = self.instances_by_ref.get_mut(&parent_ref).unwrap();
-                instance.children.push(id);
-            }
-        }
-
-        Ok(())
+        Ok(subjects.into_iter().zip(parents).collect())
     }
 
     #[profiling::function]
@@ -1435,6 +1730,22 @@ impl<'db, R: Read> DeserializerState<'db, R> {
         Ok(())
     }
 
+    /// Returns the `Ref` given to the instance with the given document-defined
+    /// ID, or `Ref::none()` if there is no such instance.
+    pub(super) fn referent(&self, id: i32) -> Ref {
+        match self.instances_by_ref.get(&id) {
+            Some(instance) => instance.builder.referent(),
+            None => Ref::none(),
+        }
+    }
+
+    /// Returns the class name and document-defined IDs of the instances with
+    /// the given type ID.
+    pub(super) fn type_info(&self, type_id: u32) -> (&str, &[i32]) {
+        let type_info = &self.type_infos[&type_id];
+        (&type_info.type_name, &type_info.referents)
+    }
+
     /// Combines together all the decoded information to build and emplace
     /// instances in our tree.
     #[profiling::function]
@@ -1454,7 +1765,19 @@ impl<'db, R: Read> DeserializerState<'db, R> {
             instances_to_construct.push_back((referent, root_ref));
         }
 
+        let kept = if self.deserializer.filter.prunes() {
+            Some(self.find_kept_instances())
+        } else {
+            None
+        };
+
         while let Some((referent, parent_ref)) = instances_to_construct.pop_front() {
+            if let Some(kept) = &kept {
+                if !kept.contains(&referent) {
+                    continue;
+                }
+            }
+
             let instance = self.instances_by_ref.remove(&referent).unwrap();
             let id = self.tree.insert(parent_ref, instance.builder);
 
@@ -1465,4 +1788,40 @@ impl<'db, R: Read> DeserializerState<'db, R> {
 
         self.tree
     }
+
+    /// Finds every instance that should be constructed when pruning is
+    /// enabled: instances whose class passes the filter, and their ancestors.
+    fn find_kept_instances(&self) -> HashSet<i32> {
+        let filter = &self.deserializer.filter;
+        let database = self.deserializer.database;
+
+        let mut kept = HashSet::new();
+        for type_info in self.type_infos.values() {
+            if filter.allows_class(database, &type_info.type_name) {
+                kept.extend(type_info.referents.iter().copied());
+            }
+        }
+
+        // Visit instances from the top of the tree down, then walk that list
+        // backwards so that children are always visited before their parents.
+        let mut order = self.root_instance_refs.clone();
+        let mut i = 0;
+        while let Some(&referent) = order.get(i) {
+            order.extend(self.instances_by_ref[&referent].children.iter().copied());
+            i += 1;
+        }
+
+        for &referent in order.iter().rev() {
+            let has_kept_child = self.instances_by_ref[&referent]
+                .children
+                .iter()
+                .any(|child| kept.contains(child));
+
+            if has_kept_child {
+                kept.insert(referent);
+            }
+        }
+
+        kept
+    }
 }
@@ -0,0 +1,174 @@
+use std::io::Read;
+
+use rbx_dom_weak::types::{Ref, Variant};
+
+use crate::chunk::UnknownChunk;
+
+use super::{error::Error, state::DeserializerState};
+
+/// A piece of a binary model or place, produced by [`DecodeStream`] as the
+/// chunk containing it is read.
+///
+/// Instances are identified by the `Ref` they were given when their `INST`
+/// chunk was read. These refs are stable for the lifetime of the stream and are
+/// also used for `Ref` property values.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeEvent {
+    /// Metadata about the file, from a `META` chunk.
+    Metadata {
+        /// The key-value pairs contained in the chunk, in file order.
+        entries: Vec<(String, String)>,
+    },
+
+    /// A group of instances of the same class, from an `INST` chunk.
+    Instances {
+        /// The class of every instance in the group.
+        class_name: String,
+
+        /// The instances in the group.
+        referents: Vec<Ref>,
+    },
+
+    /// The values of one property for every instance of a class, from a `PROP`
+    /// chunk.
+    ///
+    /// Values are reported under the property's canonical name, like when
+    /// deserializing into a `WeakDom`, but properties that would be migrated
+    /// to a new property are reported as-is. Instance names are reported as a
+    /// `String` property named `Name`.
+    Property {
+        /// The class of the instances the property belongs to.
+        class_name: String,
+
+        /// The canonical name of the property.
+        property_name: String,
+
+        /// Each instance of the class paired with its value for the property.
+        values: Vec<(Ref, Variant)>,
+    },
+
+    /// The parents of instances, from a `PRNT` chunk.
+    Parents {
+        /// Each instance paired with its parent. Instances at the top level of
+        /// the file have a parent of `Ref::none()`.
+        pairs: Vec<(Ref, Ref)>,
+    },
+
+    /// A chunk that rbx_binary does not understand.
+    Unknown {
+        /// The contents of the chunk.
+        chunk: UnknownChunk,
+    },
+}
+
+/// An iterator over the [`DecodeEvent`]s in a binary model or place, created
+/// with [`Deserializer::deserialize_streaming`][super::Deserializer::deserialize_streaming].
+///
+/// Property values are handed to the caller instead of being stored, so the
+/// memory used by a `DecodeStream` grows only with the number of instances in
+/// the file.
+///
+/// The stream ends after the `END` chunk is read or after an error is returned.
+pub struct DecodeStream<'db, R> {
+    state: DeserializerState<'db, R>,
+    finished: bool,
+}
+
+impl<'db, R: Read> DecodeStream<'db, R> {
+    pub(super) fn new(state: DeserializerState<'db, R>) -> Self {
+        Self {
+            state,
+            finished: false,
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<DecodeEvent>, Error> {
+        loop {
+            let chunk = self.state.next_chunk()?;
+
+            let state = &mut self.state;
+
+            match &chunk.name {
+                b"META" => {
+                    let entries = state
+                        .read_meta_chunk(&chunk.data)
+                        .map_err(|err| state.locate(err))?;
+                    return Ok(Some(DecodeEvent::Metadata { entries }));
+                }
+                b"SSTR" => state
+                    .decode_sstr_chunk(&chunk.data)
+                    .map_err(|err| state.locate(err))?,
+                b"INST" => {
+                    let type_id = state
+                        .decode_inst_chunk(&chunk.data)
+                        .map_err(|err| state.locate(err))?;
+                    let (class_name, ids) = state.type_info(type_id);
+
+                    return Ok(Some(DecodeEvent::Instances {
+                        class_name: class_name.to_owned(),
+                        referents: ids.iter().map(|&id| state.referent(id)).collect(),
+                    }));
+                }
+                b"PROP" => {
+                    let prop = match state
+                        .read_prop_chunk(&chunk.data)
+                        .map_err(|err| state.locate(err))?
+                    {
+                        Some(prop) => prop,
+                        None => continue,
+                    };
+                    let (class_name, ids) = state.type_info(prop.type_id);
+
+                    return Ok(Some(DecodeEvent::Property {
+                        class_name: class_name.to_owned(),
+                        property_name: prop.name,
+                        values: ids
+                            .iter()
+                            .map(|&id| state.referent(id))
+                            .zip(prop.values)
+                            .collect(),
+                    }));
+                }
+                b"PRNT" => {
+                    let pairs = state
+                        .read_prnt_chunk(&chunk.data)
+                        .map_err(|err| state.locate(err))?
+                        .into_iter()
+                        .map(|(id, parent)| (state.referent(id), state.referent(parent)))
+                        .collect();
+
+                    return Ok(Some(DecodeEvent::Parents { pairs }));
+                }
+                b"END\0" => {
+                    state
+                        .decode_end_chunk(&chunk.data)
+                        .map_err(|err| state.locate(err))?;
+                    return Ok(None);
+                }
+                _ => {
+                    return Ok(Some(DecodeEvent::Unknown {
+                        chunk: chunk.into(),
+                    }))
+                }
+            }
+        }
+    }
+}
+
+impl<'db, R: Read> Iterator for DecodeStream<'db, R> {
+    type Item = Result<DecodeEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.next_event();
+        if !matches!(result, Ok(Some(_))) {
+            self.finished = true;
+        }
+
+        result.transpose()
+    }
+}
@@ -56,12 +56,16 @@ rbx_binary::to_writer(output, &dom, &[dom.root_ref()])?;
 mod chunk;
 mod core;
 mod deserializer;
+mod parallel;
 mod serializer;
 mod types;
 
 #[cfg(any(test, feature = "unstable_text_format"))]
 mod text_deserializer;
 
+#[cfg(any(test, feature = "unstable_text_format"))]
+mod text_serializer;
+
 #[cfg(test)]
 mod tests;
 
@@ -76,7 +80,8 @@ pub mod text_format {
 }
 
 pub use crate::{
-    deserializer::{Deserializer, Error as DecodeError},
+    chunk::{ChunkCompression, UnknownChunk},
+    deserializer::{DecodeEvent, DecodeStream, Deserializer, Error as DecodeError},
     serializer::{Error as EncodeError, Serializer},
 };
 
@@ -0,0 +1,30 @@
+//! Helpers for doing the same work on many chunks at once.
+//!
+//! With the `rayon` feature enabled, work is spread across rayon's global
+//! thread pool. Otherwise, it's done in order on the current thread. Either
+//! way, results are returned in the same order as their inputs so that output
+//! doesn't depend on which feature set is used.
+
+/// Applies `f` to every item, returning the results in the same order as the
+/// items.
+#[cfg(feature = "rayon")]
+pub(crate) fn map<T, U, F>(items: Vec<T>, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Send + Sync,
+{
+    use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+    items.into_par_iter().map(f).collect()
+}
+
+/// Applies `f` to every item, returning the results in the same order as the
+/// items.
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map<T, U, F>(items: Vec<T>, f: F) -> Vec<U>
+where
+    F: Fn(T) -> U,
+{
+    items.into_iter().map(f).collect()
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use crate::chunk::{ChunkBuilder, ChunkCompression};
+
+/// Describes how a [`Serializer`][super::Serializer] should compress each
+/// chunk it writes.
+#[derive(Debug, Clone)]
+pub(crate) struct CompressionPolicy {
+    /// The compression used for chunks that Roblox compresses, unless there's
+    /// an entry in `overrides` for the chunk.
+    pub(crate) default: ChunkCompression,
+
+    /// The compression used for chunks with specific names, like `PROP`.
+    pub(crate) overrides: HashMap<[u8; 4], ChunkCompression>,
+
+    /// Chunks whose contents are smaller than this many bytes are written
+    /// uncompressed.
+    pub(crate) threshold: usize,
+}
+
+impl CompressionPolicy {
+    /// Returns the compression that should be used for chunks with the given
+    /// name.
+    pub(crate) fn compression_for(&self, chunk_name: &[u8; 4]) -> ChunkCompression {
+        self.overrides
+            .get(chunk_name)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Creates a `ChunkBuilder` for a chunk that Roblox compresses, following
+    /// this policy.
+    pub(crate) fn chunk_builder(&self, chunk_name: &[u8; 4]) -> ChunkBuilder {
+        ChunkBuilder::new(chunk_name, self.compression_for(chunk_name))
+            .with_threshold(self.threshold)
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self {
+            default: ChunkCompression::Compressed,
+            overrides: HashMap::new(),
+            threshold: 0,
+        }
+    }
+}
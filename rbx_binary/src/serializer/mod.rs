@@ -1,3 +1,4 @@
+mod compression;
 mod error;
 mod state;
 
@@ -6,7 +7,9 @@ use std::io::Write;
 use rbx_dom_weak::{types::Ref, WeakDom};
 use rbx_reflection::ReflectionDatabase;
 
-use self::state::SerializerState;
+use crate::chunk::{ChunkCompression, UnknownChunk};
+
+use self::{compression::CompressionPolicy, state::SerializerState};
 
 pub use self::error::Error;
 
@@ -34,14 +37,33 @@ pub use self::error::Error;
 /// A custom [`ReflectionDatabase`][ReflectionDatabase] can be specified via
 /// [`reflection_database`][reflection_database].
 ///
+/// The codec used to compress chunks can be chosen via
+/// [`compression`][compression], or for specific kinds of chunk via
+/// [`chunk_compression`][chunk_compression]. Small chunks can be left
+/// uncompressed with [`compression_threshold`][compression_threshold].
+///
+/// Output that is byte-for-byte the same for equal DOMs can be requested via
+/// [`deterministic`][deterministic].
+///
 /// [ReflectionDatabase]: rbx_reflection::ReflectionDatabase
 /// [reflection_database]: Serializer#method.reflection_database
+/// [compression]: Serializer#method.compression
+/// [chunk_compression]: Serializer#method.chunk_compression
+/// [compression_threshold]: Serializer#method.compression_threshold
+/// [deterministic]: Serializer#method.deterministic
 //
 // future settings:
 // * recursive: bool = true
 #[non_exhaustive]
 pub struct Serializer<'db> {
     database: &'db ReflectionDatabase<'db>,
+    compression: CompressionPolicy,
+    deterministic: bool,
+
+    /// Whether PROP chunks are all encoded at once and then written, instead
+    /// of being written one at a time. This only helps with the `rayon`
+    /// feature enabled, where they're encoded in parallel.
+    parallel: bool,
 }
 
 impl<'db> Serializer<'db> {
@@ -49,18 +71,101 @@ impl<'db> Serializer<'db> {
     pub fn new() -> Self {
         Serializer {
             database: rbx_reflection_database::get(),
+            compression: CompressionPolicy::default(),
+            deterministic: false,
+            parallel: cfg!(feature = "rayon"),
         }
     }
 
     /// Sets what reflection database for the serializer to use.
     #[inline]
     pub fn reflection_database(self, database: &'db ReflectionDatabase<'db>) -> Self {
-        Self { database }
+        Self { database, ..self }
+    }
+
+    /// Sets how chunks that Roblox compresses should be compressed. Defaults to
+    /// [`ChunkCompression::Compressed`], which uses LZ4.
+    ///
+    /// Chunks that are always left uncompressed, like `META` and `END`, are
+    /// not affected by this setting.
+    #[inline]
+    pub fn compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression.default = compression;
+        self
+    }
+
+    /// Sets how chunks with the given name, like `PROP`, should be compressed,
+    /// overriding [`compression`][Serializer::compression] for those chunks.
+    ///
+    /// Chunks that are always left uncompressed, like `META` and `END`, are
+    /// not affected by this setting.
+    pub fn chunk_compression(
+        mut self,
+        chunk_name: &[u8; 4],
+        compression: ChunkCompression,
+    ) -> Self {
+        self.compression.overrides.insert(*chunk_name, compression);
+        self
+    }
+
+    /// Sets the size, in bytes, that the contents of a chunk must reach before
+    /// it's compressed. Smaller chunks are written uncompressed, which is
+    /// faster and often no bigger. Defaults to 0, which compresses every chunk
+    /// that Roblox compresses.
+    #[inline]
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression.threshold = threshold;
+        self
+    }
+
+    /// Sets whether the serializer should guarantee that equal DOMs are always
+    /// written as the same bytes. Defaults to `false`.
+    ///
+    /// When enabled, the properties of each instance are visited in order of
+    /// their names, and classes are numbered in the same order they're
+    /// written in, sorted by name. This makes the output independent of the
+    /// order properties were added to instances in, which matters when an
+    /// instance has more than one property that serializes as the same
+    /// property, like an alias and the property it aliases.
+    ///
+    /// Instances are always numbered in the order they're visited in, and
+    /// shared strings are always sorted by their hash, so equal DOMs with
+    /// different referents are written the same way either way.
+    #[inline]
+    pub fn deterministic(self, deterministic: bool) -> Self {
+        Self {
+            deterministic,
+            ..self
+        }
+    }
+
+    /// Overrides whether PROP chunks are encoded all at once, so that tests
+    /// can compare the output of both ways of writing them.
+    #[cfg(test)]
+    pub(crate) fn parallel(self, parallel: bool) -> Self {
+        Self { parallel, ..self }
     }
 
     /// Serialize a Roblox binary model or place into the given stream using
     /// this serializer.
     pub fn serialize<W: Write>(&self, writer: W, dom: &WeakDom, refs: &[Ref]) -> Result<(), Error> {
+        self.serialize_with_unknown_chunks(writer, dom, refs, &[])
+    }
+
+    /// Serialize a Roblox binary model or place into the given stream using
+    /// this serializer, also writing out the given chunks that rbx_binary
+    /// does not understand.
+    ///
+    /// Unknown chunks are written in the order given, just before the end of
+    /// the file. They're usually obtained from
+    /// [`Deserializer::deserialize_with_unknown_chunks`][crate::Deserializer::deserialize_with_unknown_chunks].
+    pub fn serialize_with_unknown_chunks<W: Write>(
+        &self,
+        writer: W,
+        dom: &WeakDom,
+        refs: &[Ref],
+        unknown_chunks: &[UnknownChunk],
+    ) -> Result<(), Error> {
         profiling::scope!("rbx_binary::seserialize");
 
         let mut serializer = SerializerState::new(self, dom, writer);
@@ -73,6 +178,7 @@ impl<'db> Serializer<'db> {
         serializer.serialize_instances()?;
         serializer.serialize_properties()?;
         serializer.serialize_parents()?;
+        serializer.serialize_unknown_chunks(unknown_chunks)?;
         serializer.serialize_end()?;
 
         Ok(())
@@ -10,9 +10,9 @@ use rbx_dom_weak::{
     types::{
         Attributes, Axes, BinaryString, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
         ColorSequenceKeypoint, Content, Enum, Faces, Font, MaterialColors, Matrix3, NumberRange,
-        NumberSequence, NumberSequenceKeypoint, PhysicalProperties, Ray, Rect, Ref,
-        SecurityCapabilities, SharedString, Tags, UDim, UDim2, UniqueId, Variant, VariantType,
-        Vector2, Vector3, Vector3int16,
+        NumberSequence, NumberSequenceKeypoint, Opaque, OpaqueFormat, PhysicalProperties, Ray,
+        Rect, Ref, SecurityCapabilities, SharedString, Tags, UDim, UDim2, UniqueId, Variant,
+        VariantType, Vector2, Vector3, Vector3int16,
     },
     Instance, WeakDom,
 };
@@ -23,17 +23,17 @@ use rbx_reflection::{
 };
 
 use crate::{
-    chunk::{ChunkBuilder, ChunkCompression},
+    chunk::{ChunkBuilder, ChunkCompression, UnknownChunk},
     core::{
-        find_property_descriptors, RbxWriteExt, FILE_MAGIC_HEADER, FILE_SIGNATURE, FILE_VERSION,
+        find_property_descriptors, RbxWriteExt, FILE_FOOTER, FILE_MAGIC_HEADER, FILE_SIGNATURE,
+        FILE_VERSION,
     },
+    parallel,
     types::Type,
     Serializer,
 };
 
-use super::error::InnerError;
-
-static FILE_FOOTER: &[u8] = b"</roblox>";
+use super::{compression::CompressionPolicy, error::InnerError};
 
 /// Represents all of the state during a single serialization session. A new
 /// `BinarySerializer` object should be created every time we want to serialize
@@ -99,6 +99,11 @@ struct TypeInfo<'dom, 'db> {
     /// processed. This helps us avoid traversing the reflection database
     /// multiple times if there are many copies of the same kind of instance.
     properties_visited: HashSet<(Cow<'db, str>, VariantType)>,
+
+    /// The names of properties on this type that hold opaque values read from
+    /// a binary file. These are written back out as-is instead of going
+    /// through `properties`.
+    opaque_properties: BTreeSet<String>,
 }
 
 /// A property on a specific class that our serializer knows about.
@@ -220,6 +225,7 @@ impl<'dom, 'db> TypeInfos<'dom, 'db> {
                     properties,
                     class_descriptor,
                     properties_visited: HashSet::new(),
+                    opaque_properties: BTreeSet::new(),
                 },
             );
         }
@@ -263,6 +269,24 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
             to_visit.extend(instance.children());
         }
 
+        // Opaque values can only be written back out if instances are in the
+        // same order as the chunk they were read from.
+        for type_info in self.type_infos.values.values_mut() {
+            if let Some(prop_name) = type_info.opaque_properties.iter().next() {
+                type_info
+                    .instances
+                    .sort_by_key(|instance| opaque_index(instance, prop_name));
+            }
+        }
+
+        // Type IDs are handed out in the order classes are discovered in, but
+        // INST chunks are written in order of class name.
+        if self.serializer.deterministic {
+            for (type_id, type_info) in self.type_infos.values.values_mut().enumerate() {
+                type_info.type_id = type_id as u32;
+            }
+        }
+
         // Sort shared_strings by their hash, to ensure they are deterministically added
         // into the SSTR chunk, then assign them corresponding ids
         self.shared_strings.sort_by_key(SharedString::hash);
@@ -288,7 +312,40 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
         let type_info = self.type_infos.get_or_create(&instance.class);
         type_info.instances.push(instance);
 
-        for (prop_name, prop_value) in &instance.properties {
+        // When more than one property on an instance maps to the same logical
+        // property, how it's serialized can depend on which one we visit
+        // first. Sorting is only worth the allocation when we're asked to be
+        // deterministic.
+        let mut sorted;
+        let mut unsorted;
+        let properties: &mut dyn Iterator<Item = _> = if self.serializer.deterministic {
+            let mut properties: Vec<_> = instance.properties.iter().collect();
+            properties.sort_unstable_by_key(|(prop_name, _)| prop_name.as_str());
+            sorted = properties.into_iter();
+            &mut sorted
+        } else {
+            unsorted = instance.properties.iter();
+            &mut unsorted
+        };
+
+        for (prop_name, prop_value) in properties {
+            if let Variant::Opaque(opaque) = prop_value {
+                match opaque.format() {
+                    OpaqueFormat::Binary { .. } => {
+                        if !type_info.opaque_properties.contains(prop_name) {
+                            type_info.opaque_properties.insert(prop_name.clone());
+                        }
+                    }
+                    _ => log::warn!(
+                        "Property {}.{} holds an opaque value from another format and will not be written",
+                        instance.class,
+                        prop_name
+                    ),
+                }
+
+                continue;
+            }
+
             // Discover and track any shared strings we come across.
             if let Variant::SharedString(shared_string) = prop_value {
                 if !self.shared_string_ids.contains_key(shared_string) {
@@ -496,8 +553,24 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
 
     /// Write out any metadata about this file, stored in a chunk named META.
     pub fn serialize_metadata(&mut self) -> Result<(), InnerError> {
-        log::trace!("Writing metadata (currently no-op)");
-        // TODO: There is no concept of metadata in a dom yet.
+        log::trace!("Writing metadata");
+
+        let metadata = self.dom.metadata();
+        if metadata.is_empty() {
+            return Ok(());
+        }
+
+        let mut chunk = ChunkBuilder::new(b"META", ChunkCompression::Uncompressed);
+
+        chunk.write_le_u32(metadata.len() as u32)?;
+
+        for (key, value) in metadata {
+            chunk.write_string(key)?;
+            chunk.write_string(value)?;
+        }
+
+        chunk.dump(&mut self.output)?;
+
         Ok(())
     }
 
@@ -511,7 +584,7 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
             return Ok(());
         }
 
-        let mut chunk = ChunkBuilder::new(b"SSTR", ChunkCompression::Compressed);
+        let mut chunk = self.serializer.compression.chunk_builder(b"SSTR");
 
         chunk.write_le_u32(0)?; // SSTR version number
         chunk.write_le_u32(self.shared_strings.len() as u32)?;
@@ -540,7 +613,7 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
                 type_info.instances.len()
             );
 
-            let mut chunk = ChunkBuilder::new(b"INST", ChunkCompression::Compressed);
+            let mut chunk = self.serializer.compression.chunk_builder(b"INST");
 
             chunk.write_le_u32(type_info.type_id)?;
             chunk.write_string(type_name)?;
@@ -588,648 +661,73 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
     pub fn serialize_properties(&mut self) -> Result<(), InnerError> {
         log::trace!("Writing properties");
 
+        let writer = PropWriter {
+            dom: self.dom,
+            compression: &self.serializer.compression,
+            id_to_referent: &self.id_to_referent,
+            shared_string_ids: &self.shared_string_ids,
+        };
+
+        let mut jobs = Vec::new();
         for (type_name, type_info) in &self.type_infos.values {
             for (prop_name, prop_info) in &type_info.properties {
-                profiling::scope!("serialize property", prop_name.borrow());
-                log::trace!(
-                    "Writing property {}.{} (type {:?})",
+                jobs.push(PropJob::Property {
                     type_name,
+                    type_info,
                     prop_name,
-                    prop_info.prop_type
-                );
-
-                let mut chunk = ChunkBuilder::new(b"PROP", ChunkCompression::Compressed);
-
-                chunk.write_le_u32(type_info.type_id)?;
-                chunk.write_string(&prop_info.serialized_name)?;
-                chunk.write_u8(prop_info.prop_type as u8)?;
-
-                let values = type_info
-                    .instances
-                    .iter()
-                    .map(|instance| {
-                        // We store the Name property in a different field for
-                        // convenience, but when serializing to the binary model
-                        // format we need to handle it just like other properties.
-                        if prop_name == "Name" {
-                            return Cow::Owned(Variant::String(instance.name.clone()));
-                        }
-
-                        // Most properties will be stored on instances using the
-                        // property's canonical name, so we'll try that first.
-                        if let Some(property) = instance.properties.get(prop_name.as_ref()) {
-                            return Cow::Borrowed(property);
-                        }
-
-                        // If there were any known aliases for this property
-                        // used as part of this file, we can check those next.
-                        for alias in &prop_info.aliases {
-                            if let Some(property) = instance.properties.get(alias) {
-                                return Cow::Borrowed(property);
-                            }
-                        }
-
-                        // Finally, we can fall back to the default value we
-                        // computed for this PropInfo. This is sourced from the
-                        // reflection database if available, or falls back to a
-                        // reasonable default.
-                        Cow::Borrowed(prop_info.default_value.borrow())
-                    })
-                    .map(|value| {
-                        if let Some(migration) = prop_info.migration {
-                            match migration.perform(&value) {
-                                Ok(new_value) => Cow::Owned(new_value),
-                                Err(_) => value,
-                            }
-                        } else {
-                            value
-                        }
-                    })
-                    .enumerate();
-
-                // Helper to generate a type mismatch error with context from
-                // this chunk.
-                let type_mismatch =
-                    |i: usize, bad_value: &Variant, valid_type_names: &'static str| {
-                        Err(InnerError::PropTypeMismatch {
-                            type_name: type_name.clone(),
-                            prop_name: prop_name.to_string(),
-                            valid_type_names,
-                            actual_type_name: format!("{:?}", bad_value.ty()),
-                            instance_full_name: self
-                                .full_name_for(type_info.instances[i].referent()),
-                        })
-                    };
-
-                let invalid_value = |i: usize, bad_value: &Variant| InnerError::InvalidPropValue {
-                    instance_full_name: self.full_name_for(type_info.instances[i].referent()),
-                    type_name: type_name.clone(),
-                    prop_name: prop_name.to_string(),
-                    prop_type: format!("{:?}", bad_value.ty()),
-                };
-
-                match prop_info.prop_type {
-                    Type::String => {
-                        for (i, rbx_value) in values {
-                            match rbx_value.as_ref() {
-                                Variant::String(value) => {
-                                    chunk.write_string(value)?;
-                                }
-                                Variant::Content(value) => {
-                                    chunk.write_string(value.as_ref())?;
-                                }
-                                Variant::BinaryString(value) => {
-                                    chunk.write_binary_string(value.as_ref())?;
-                                }
-                                Variant::Tags(value) => {
-                                    let buf = value.encode();
-                                    chunk.write_binary_string(&buf)?;
-                                }
-                                Variant::Attributes(value) => {
-                                    let mut buf = Vec::new();
-
-                                    value
-                                        .to_writer(&mut buf)
-                                        .map_err(|_| invalid_value(i, &rbx_value))?;
-
-                                    chunk.write_binary_string(&buf)?;
-                                }
-                                Variant::MaterialColors(value) => {
-                                    chunk.write_binary_string(&value.encode())?;
-                                }
-                                _ => {
-                                    return type_mismatch(
-                                        i,
-                                        &rbx_value,
-                                        "String, Content, Tags, Attributes, MaterialColors, or BinaryString",
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    Type::Bool => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Bool(value) = rbx_value.as_ref() {
-                                chunk.write_bool(*value)?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Bool");
-                            }
-                        }
-                    }
-                    Type::Int32 => {
-                        let mut buf = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Int32(value) = rbx_value.as_ref() {
-                                buf.push(*value);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Int32");
-                            }
-                        }
-
-                        chunk.write_interleaved_i32_array(buf.into_iter())?;
-                    }
-                    Type::Float32 => {
-                        let mut buf = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Float32(value) = rbx_value.as_ref() {
-                                buf.push(*value);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Float32");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(buf.into_iter())?;
-                    }
-                    Type::Float64 => {
-                        for (i, rbx_value) in values {
-                            match rbx_value.as_ref() {
-                                Variant::Float64(value) => {
-                                    chunk.write_le_f64(*value)?;
-                                }
-                                Variant::Float32(value) => {
-                                    chunk.write_le_f64(*value as f64)?;
-                                }
-                                _ => return type_mismatch(i, &rbx_value, "Float64"),
-                            }
-                        }
-                    }
-                    Type::UDim => {
-                        let mut scale = Vec::with_capacity(values.len());
-                        let mut offset = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::UDim(value) = rbx_value.as_ref() {
-                                scale.push(value.scale);
-                                offset.push(value.offset);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "UDim");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(scale.into_iter())?;
-                        chunk.write_interleaved_i32_array(offset.into_iter())?;
-                    }
-                    Type::UDim2 => {
-                        let mut scale_x = Vec::with_capacity(values.len());
-                        let mut scale_y = Vec::with_capacity(values.len());
-                        let mut offset_x = Vec::with_capacity(values.len());
-                        let mut offset_y = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::UDim2(value) = rbx_value.as_ref() {
-                                scale_x.push(value.x.scale);
-                                scale_y.push(value.y.scale);
-                                offset_x.push(value.x.offset);
-                                offset_y.push(value.y.offset);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "UDim2");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(scale_x.into_iter())?;
-                        chunk.write_interleaved_f32_array(scale_y.into_iter())?;
-                        chunk.write_interleaved_i32_array(offset_x.into_iter())?;
-                        chunk.write_interleaved_i32_array(offset_y.into_iter())?;
-                    }
-                    Type::Font => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Font(value) = rbx_value.as_ref() {
-                                chunk.write_string(&value.family)?;
-                                chunk.write_le_u16(value.weight.as_u16())?;
-                                chunk.write_u8(value.style.as_u8())?;
-                                chunk.write_string(
-                                    &value.cached_face_id.clone().unwrap_or_default(),
-                                )?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Font");
-                            }
-                        }
-                    }
-                    Type::Ray => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Ray(value) = rbx_value.as_ref() {
-                                chunk.write_le_f32(value.origin.x)?;
-                                chunk.write_le_f32(value.origin.y)?;
-                                chunk.write_le_f32(value.origin.z)?;
-                                chunk.write_le_f32(value.direction.x)?;
-                                chunk.write_le_f32(value.direction.y)?;
-                                chunk.write_le_f32(value.direction.x)?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Ray");
-                            }
-                        }
-                    }
-                    Type::Faces => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Faces(value) = rbx_value.as_ref() {
-                                chunk.write_u8(value.bits())?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Faces");
-                            }
-                        }
-                    }
-                    Type::Axes => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Axes(value) = rbx_value.as_ref() {
-                                chunk.write_u8(value.bits())?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Axes");
-                            }
-                        }
-                    }
-                    Type::BrickColor => {
-                        let mut numbers = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::BrickColor(value) = rbx_value.as_ref() {
-                                numbers.push(*value as u32);
-                            } else if let Variant::Int32(value) = rbx_value.as_ref() {
-                                numbers.push(*value as u32);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "BrickColor");
-                            }
-                        }
-
-                        chunk.write_interleaved_u32_array(&numbers)?;
-                    }
-                    Type::Color3 => {
-                        let mut r = Vec::with_capacity(values.len());
-                        let mut g = Vec::with_capacity(values.len());
-                        let mut b = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Color3(value) = rbx_value.as_ref() {
-                                r.push(value.r);
-                                g.push(value.g);
-                                b.push(value.b);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Color3");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(r.into_iter())?;
-                        chunk.write_interleaved_f32_array(g.into_iter())?;
-                        chunk.write_interleaved_f32_array(b.into_iter())?;
-                    }
-                    Type::Vector2 => {
-                        let mut x = Vec::with_capacity(values.len());
-                        let mut y = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Vector2(value) = rbx_value.as_ref() {
-                                x.push(value.x);
-                                y.push(value.y)
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Vector2");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(x.into_iter())?;
-                        chunk.write_interleaved_f32_array(y.into_iter())?;
-                    }
-                    Type::Vector3 => {
-                        let mut x = Vec::with_capacity(values.len());
-                        let mut y = Vec::with_capacity(values.len());
-                        let mut z = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Vector3(value) = rbx_value.as_ref() {
-                                x.push(value.x);
-                                y.push(value.y);
-                                z.push(value.z)
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Vector3");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(x.into_iter())?;
-                        chunk.write_interleaved_f32_array(y.into_iter())?;
-                        chunk.write_interleaved_f32_array(z.into_iter())?;
-                    }
-                    Type::CFrame => {
-                        let mut rotations = Vec::with_capacity(values.len());
-                        let mut x = Vec::with_capacity(values.len());
-                        let mut y = Vec::with_capacity(values.len());
-                        let mut z = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::CFrame(value) = rbx_value.as_ref() {
-                                rotations.push(value.orientation);
-                                x.push(value.position.x);
-                                y.push(value.position.y);
-                                z.push(value.position.z);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "CFrame");
-                            }
-                        }
-
-                        for matrix in rotations {
-                            if let Some(id) = matrix.to_basic_rotation_id() {
-                                chunk.write_u8(id)?;
-                            } else {
-                                chunk.write_u8(0x00)?;
-
-                                chunk.write_le_f32(matrix.x.x)?;
-                                chunk.write_le_f32(matrix.x.y)?;
-                                chunk.write_le_f32(matrix.x.z)?;
-
-                                chunk.write_le_f32(matrix.y.x)?;
-                                chunk.write_le_f32(matrix.y.y)?;
-                                chunk.write_le_f32(matrix.y.z)?;
-
-                                chunk.write_le_f32(matrix.z.x)?;
-                                chunk.write_le_f32(matrix.z.y)?;
-                                chunk.write_le_f32(matrix.z.z)?;
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(x.into_iter())?;
-                        chunk.write_interleaved_f32_array(y.into_iter())?;
-                        chunk.write_interleaved_f32_array(z.into_iter())?;
-                    }
-                    Type::Enum => {
-                        let mut buf = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Enum(value) = rbx_value.as_ref() {
-                                buf.push(value.to_u32());
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Enum");
-                            }
-                        }
-
-                        chunk.write_interleaved_u32_array(&buf)?;
-                    }
-                    Type::Ref => {
-                        let mut buf = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Ref(value) = rbx_value.as_ref() {
-                                if let Some(id) = self.id_to_referent.get(value) {
-                                    buf.push(*id);
-                                } else {
-                                    buf.push(-1);
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Ref");
-                            }
-                        }
-
-                        chunk.write_referent_array(buf.into_iter())?;
-                    }
-                    Type::Vector3int16 => {
-                        for (i, rbx_value) in values {
-                            if let Variant::Vector3int16(value) = rbx_value.as_ref() {
-                                chunk.write_le_i16(value.x)?;
-                                chunk.write_le_i16(value.y)?;
-                                chunk.write_le_i16(value.z)?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Vector3int16");
-                            }
-                        }
-                    }
-                    Type::NumberSequence => {
-                        for (i, rbx_value) in values {
-                            if let Variant::NumberSequence(value) = rbx_value.as_ref() {
-                                chunk.write_le_u32(value.keypoints.len() as u32)?;
-
-                                for keypoint in &value.keypoints {
-                                    chunk.write_le_f32(keypoint.time)?;
-                                    chunk.write_le_f32(keypoint.value)?;
-                                    chunk.write_le_f32(keypoint.envelope)?;
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "NumberSequence");
-                            }
-                        }
-                    }
-                    Type::ColorSequence => {
-                        for (i, rbx_value) in values {
-                            if let Variant::ColorSequence(value) = rbx_value.as_ref() {
-                                chunk.write_le_u32(value.keypoints.len() as u32)?;
-
-                                for keypoint in &value.keypoints {
-                                    chunk.write_le_f32(keypoint.time)?;
-                                    chunk.write_le_f32(keypoint.color.r)?;
-                                    chunk.write_le_f32(keypoint.color.g)?;
-                                    chunk.write_le_f32(keypoint.color.b)?;
-
-                                    // write out a dummy value for envelope, which is serialized but doesn't do anything
-                                    chunk.write_le_f32(0.0)?;
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "ColorSequence");
-                            }
-                        }
-                    }
-                    Type::NumberRange => {
-                        for (i, rbx_value) in values {
-                            if let Variant::NumberRange(value) = rbx_value.as_ref() {
-                                chunk.write_le_f32(value.min)?;
-                                chunk.write_le_f32(value.max)?;
-                            } else {
-                                return type_mismatch(i, &rbx_value, "NumberRange");
-                            }
-                        }
-                    }
-                    Type::Rect => {
-                        let mut x_min = Vec::with_capacity(values.len());
-                        let mut y_min = Vec::with_capacity(values.len());
-                        let mut x_max = Vec::with_capacity(values.len());
-                        let mut y_max = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::Rect(value) = rbx_value.as_ref() {
-                                x_min.push(value.min.x);
-                                y_min.push(value.min.y);
-                                x_max.push(value.max.x);
-                                y_max.push(value.max.y);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "Rect");
-                            }
-                        }
-
-                        chunk.write_interleaved_f32_array(x_min.into_iter())?;
-                        chunk.write_interleaved_f32_array(y_min.into_iter())?;
-                        chunk.write_interleaved_f32_array(x_max.into_iter())?;
-                        chunk.write_interleaved_f32_array(y_max.into_iter())?;
-                    }
-                    Type::PhysicalProperties => {
-                        for (i, rbx_value) in values {
-                            if let Variant::PhysicalProperties(value) = rbx_value.as_ref() {
-                                if let PhysicalProperties::Custom(props) = value {
-                                    chunk.write_u8(1)?;
-                                    chunk.write_le_f32(props.density)?;
-                                    chunk.write_le_f32(props.friction)?;
-                                    chunk.write_le_f32(props.elasticity)?;
-                                    chunk.write_le_f32(props.friction_weight)?;
-                                    chunk.write_le_f32(props.elasticity_weight)?;
-                                } else {
-                                    chunk.write_u8(0)?;
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "PhysicalProperties");
-                            }
-                        }
-                    }
-                    Type::Color3uint8 => {
-                        let mut r = Vec::with_capacity(values.len());
-                        let mut g = Vec::with_capacity(values.len());
-                        let mut b = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            match rbx_value.as_ref() {
-                                Variant::Color3uint8(value) => {
-                                    r.push(value.r);
-                                    g.push(value.g);
-                                    b.push(value.b);
-                                }
-                                Variant::Color3(value) => {
-                                    let color: Color3uint8 = (*value).into();
-
-                                    r.push(color.r);
-                                    g.push(color.g);
-                                    b.push(color.b);
-                                }
-                                _ => return type_mismatch(i, &rbx_value, "Color3uint8 or Color3"),
-                            }
-                        }
-
-                        chunk.write_all(r.as_slice())?;
-                        chunk.write_all(g.as_slice())?;
-                        chunk.write_all(b.as_slice())?;
-                    }
-                    Type::Int64 => {
-                        let mut buf = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            match rbx_value.as_ref() {
-                                Variant::Int64(value) => {
-                                    buf.push(*value);
-                                }
-                                Variant::Int32(value) => {
-                                    buf.push(*value as i64);
-                                }
-                                _ => return type_mismatch(i, &rbx_value, "Int64"),
-                            }
-                        }
-
-                        chunk.write_interleaved_i64_array(buf.into_iter())?;
-                    }
-                    Type::SharedString => {
-                        let mut entries = Vec::with_capacity(values.len());
-
-                        for (i, rbx_value) in values {
-                            if let Variant::SharedString(value) = rbx_value.as_ref() {
-                                if let Some(id) = self.shared_string_ids.get(value) {
-                                    entries.push(*id);
-                                } else {
-                                    panic!(
-                                        "SharedString {} was not found during type collection",
-                                        value.hash()
-                                    )
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "SharedString");
-                            }
-                        }
-
-                        chunk.write_interleaved_u32_array(&entries)?;
-                    }
-                    Type::OptionalCFrame => {
-                        let mut rotations = Vec::with_capacity(values.len());
-                        let mut bools = Vec::with_capacity(values.len());
-                        let mut x = Vec::with_capacity(values.len());
-                        let mut y = Vec::with_capacity(values.len());
-                        let mut z = Vec::with_capacity(values.len());
-
-                        chunk.write_u8(Type::CFrame as u8)?;
-
-                        for (i, rbx_value) in values {
-                            if let Variant::OptionalCFrame(value) = rbx_value.as_ref() {
-                                if let Some(value) = value {
-                                    rotations.push(value.orientation);
-                                    x.push(value.position.x);
-                                    y.push(value.position.y);
-                                    z.push(value.position.z);
-                                    bools.push(0x01);
-                                } else {
-                                    rotations.push(Matrix3::identity());
-                                    x.push(0.0);
-                                    y.push(0.0);
-                                    z.push(0.0);
-                                    bools.push(0x00);
-                                }
-                            } else {
-                                return type_mismatch(i, &rbx_value, "OptionalCFrame");
-                            }
-                        }
-
-                        for matrix in rotations {
-                            if let Some(id) = matrix.to_basic_rotation_id() {
-                                chunk.write_u8(id)?;
-                            } else {
-                                chunk.write_u8(0x00)?;
-
-                                chunk.write_le_f32(matrix.x.x)?;
-                                chunk.write_le_f32(matrix.x.y)?;
-                                chunk.write_le_f32(matrix.x.z)?;
-
-                                chunk.write_le_f32(matrix.y.x)?;
-                                chunk.write_le_f32(matrix.y.y)?;
-                                chunk.write_le_f32(matrix.y.z)?;
-
-                                chunk.write_le_f32(matrix.z.x)?;
-                                chunk.write_le_f32(matrix.z.y)?;
-                                chunk.write_le_f32(matrix.z.z)?;
-                            }
-                        }
+                    prop_info,
+                });
+            }
 
-                        chunk.write_interleaved_f32_array(x.into_iter())?;
-                        chunk.write_interleaved_f32_array(y.into_iter())?;
-                        chunk.write_interleaved_f32_array(z.into_iter())?;
+            for prop_name in &type_info.opaque_properties {
+                jobs.push(PropJob::Opaque {
+                    type_name,
+                    type_info,
+                    prop_name,
+                });
+            }
+        }
 
-                        chunk.write_u8(Type::Bool as u8)?;
-                        chunk.write_all(bools.as_slice())?;
-                    }
-                    Type::UniqueId => {
-                        let mut blobs = Vec::with_capacity(values.len());
-                        for (i, rbx_value) in values {
-                            if let Variant::UniqueId(value) = rbx_value.as_ref() {
-                                let mut blob = [0; 16];
-                                // This is maybe not the best solution to this
-                                // but we can always change it.
-                                blob[0..4].copy_from_slice(&value.index().to_be_bytes());
-                                blob[4..8].copy_from_slice(&value.time().to_be_bytes());
-                                blob[8..]
-                                    .copy_from_slice(&value.random().rotate_left(1).to_be_bytes());
-                                blobs.push(blob);
-                            } else {
-                                return type_mismatch(i, &rbx_value, "UniqueId");
-                            }
-                        }
+        let build = |job| match job {
+            PropJob::Property {
+                type_name,
+                type_info,
+                prop_name,
+                prop_info,
+            } => Ok(Some(writer.serialize_property(
+                type_name, type_info, prop_name, prop_info,
+            )?)),
+            PropJob::Opaque {
+                type_name,
+                type_info,
+                prop_name,
+            } => writer.serialize_opaque_property(type_name, type_info, prop_name),
+        };
+
+        if !self.serializer.parallel {
+            for job in jobs {
+                if let Some(chunk) = build(job)? {
+                    chunk.dump(&mut self.output)?;
+                }
+            }
 
-                        chunk.write_interleaved_bytes::<16>(&blobs)?;
-                    }
-                    Type::SecurityCapabilities => {
-                        let mut capabilities = Vec::with_capacity(values.len());
+            return Ok(());
+        }
 
-                        for (i, rbx_value) in values {
-                            if let Variant::SecurityCapabilities(value) = rbx_value.as_ref() {
-                                capabilities.push(value.bits() as i64)
-                            } else {
-                                return type_mismatch(i, &rbx_value, "SecurityCapabilities");
-                            }
-                        }
+        // PROP chunks don't depend on each other, so they can be encoded and
+        // compressed in any order, but they're always written in job order so
+        // that the output doesn't depend on the `rayon` feature.
+        let chunks = parallel::map(jobs, |job| {
+            let mut buffer = Vec::new();
+            if let Some(chunk) = build(job)? {
+                chunk.dump(&mut buffer)?;
+            }
 
-                        chunk.write_interleaved_i64_array(capabilities.into_iter())?;
-                    }
-                }
+            Ok::<_, InnerError>(buffer)
+        });
 
-                chunk.dump(&mut self.output)?;
-            }
+        for chunk in chunks {
+            self.output.write_all(&chunk?)?;
         }
 
         Ok(())
@@ -1241,7 +739,7 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
     pub fn serialize_parents(&mut self) -> Result<(), InnerError> {
         log::trace!("Writing parent relationships");
 
-        let mut chunk = ChunkBuilder::new(b"PRNT", ChunkCompression::Compressed);
+        let mut chunk = self.serializer.compression.chunk_builder(b"PRNT");
 
         chunk.write_u8(0)?; // PRNT version 0
         chunk.write_le_u32(self.relevant_instances.len() as u32)?;
@@ -1275,6 +773,30 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
         Ok(())
     }
 
+    /// Write out chunks that were preserved from another file without being
+    /// understood, exactly as they were given.
+    pub fn serialize_unknown_chunks(
+        &mut self,
+        unknown_chunks: &[UnknownChunk],
+    ) -> Result<(), InnerError> {
+        log::trace!("Writing {} unknown chunks", unknown_chunks.len());
+
+        for unknown_chunk in unknown_chunks {
+            let mut chunk = if unknown_chunk.compressed {
+                self.serializer
+                    .compression
+                    .chunk_builder(&unknown_chunk.name)
+            } else {
+                ChunkBuilder::new(&unknown_chunk.name, ChunkCompression::Uncompressed)
+            };
+
+            chunk.write_all(&unknown_chunk.data)?;
+            chunk.dump(&mut self.output)?;
+        }
+
+        Ok(())
+    }
+
     /// Write the fixed, uncompressed end chunk used to verify that the file
     /// hasn't been truncated mistakenly. This chunk is named END\0, with a zero
     /// byte at the end.
@@ -1289,27 +811,6 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
         Ok(())
     }
 
-    /// Equivalent to Instance:GetFullName() from Roblox.
-    fn full_name_for(&self, subject_ref: Ref) -> String {
-        let mut components = Vec::new();
-        let mut current_id = subject_ref;
-
-        while current_id.is_some() {
-            let instance = self.dom.get_by_ref(current_id).unwrap();
-            components.push(instance.name.as_str());
-            current_id = instance.parent();
-        }
-
-        let mut name = String::new();
-        for component in components.iter().rev() {
-            name.push_str(component);
-            name.push('.');
-        }
-        name.pop();
-
-        name
-    }
-
     fn fallback_default_value(rbx_type: VariantType) -> Option<Variant> {
         Some(match rbx_type {
             VariantType::String => Variant::String(String::new()),
@@ -1375,3 +876,782 @@ impl<'dom, 'db, W: Write> SerializerState<'dom, 'db, W> {
         })
     }
 }
+
+/// A PROP chunk that needs to be written.
+enum PropJob<'a, 'dom, 'db> {
+    /// A property that is encoded from the values on each instance.
+    Property {
+        type_name: &'a str,
+        type_info: &'a TypeInfo<'dom, 'db>,
+        prop_name: &'a str,
+        prop_info: &'a PropInfo<'db>,
+    },
+
+    /// A property holding opaque values, which are written back out as-is.
+    Opaque {
+        type_name: &'a str,
+        type_info: &'a TypeInfo<'dom, 'db>,
+        prop_name: &'a str,
+    },
+}
+
+/// The parts of `SerializerState` that are needed to encode PROP chunks. This
+/// is split out from `SerializerState` so that it can be shared between
+/// threads without the output needing to be shared too.
+struct PropWriter<'a, 'dom> {
+    dom: &'dom WeakDom,
+    compression: &'a CompressionPolicy,
+    id_to_referent: &'a HashMap<Ref, i32>,
+    shared_string_ids: &'a HashMap<SharedString, u32>,
+}
+
+impl<'a, 'dom> PropWriter<'a, 'dom> {
+    /// Encodes a PROP chunk holding the values of one property for every
+    /// instance of a type.
+    fn serialize_property<'db>(
+        &self,
+        type_name: &str,
+        type_info: &TypeInfo<'dom, 'db>,
+        prop_name: &str,
+        prop_info: &PropInfo<'db>,
+    ) -> Result<ChunkBuilder, InnerError> {
+        profiling::scope!("serialize property", prop_name);
+        log::trace!(
+            "Writing property {}.{} (type {:?})",
+            type_name,
+            prop_name,
+            prop_info.prop_type
+        );
+
+        let mut chunk = self.compression.chunk_builder(b"PROP");
+
+        chunk.write_le_u32(type_info.type_id)?;
+        chunk.write_string(&prop_info.serialized_name)?;
+        chunk.write_u8(prop_info.prop_type as u8)?;
+
+        let values = type_info
+            .instances
+            .iter()
+            .map(|instance| {
+                // We store the Name property in a different field for
+                // convenience, but when serializing to the binary model
+                // format we need to handle it just like other properties.
+                if prop_name == "Name" {
+                    return Cow::Owned(Variant::String(instance.name.clone()));
+                }
+
+                // Most properties will be stored on instances using the
+                // property's canonical name, so we'll try that first.
+                if let Some(property) = instance.properties.get(prop_name) {
+                    return Cow::Borrowed(property);
+                }
+
+                // If there were any known aliases for this property
+                // used as part of this file, we can check those next.
+                for alias in &prop_info.aliases {
+                    if let Some(property) = instance.properties.get(alias) {
+                        return Cow::Borrowed(property);
+                    }
+                }
+
+                // Finally, we can fall back to the default value we
+                // computed for this PropInfo. This is sourced from the
+                // reflection database if available, or falls back to a
+                // reasonable default.
+                Cow::Borrowed(prop_info.default_value.borrow())
+            })
+            .map(|value| {
+                if let Some(migration) = prop_info.migration {
+                    match migration.perform(&value) {
+                        Ok(new_value) => Cow::Owned(new_value),
+                        Err(_) => value,
+                    }
+                } else {
+                    value
+                }
+            })
+            .enumerate();
+
+        // Helper to generate a type mismatch error with context from
+        // this chunk.
+        let type_mismatch = |i: usize, bad_value: &Variant, valid_type_names: &'static str| {
+            Err(InnerError::PropTypeMismatch {
+                type_name: type_name.to_owned(),
+                prop_name: prop_name.to_string(),
+                valid_type_names,
+                actual_type_name: format!("{:?}", bad_value.ty()),
+                instance_full_name: full_name_for(self.dom, type_info.instances[i].referent()),
+            })
+        };
+
+        let invalid_value = |i: usize, bad_value: &Variant| InnerError::InvalidPropValue {
+            instance_full_name: full_name_for(self.dom, type_info.instances[i].referent()),
+            type_name: type_name.to_owned(),
+            prop_name: prop_name.to_string(),
+            prop_type: format!("{:?}", bad_value.ty()),
+        };
+
+        match prop_info.prop_type {
+            Type::String => {
+                for (i, rbx_value) in values {
+                    match rbx_value.as_ref() {
+                        Variant::String(value) => {
+                            chunk.write_string(value)?;
+                        }
+                        Variant::Content(value) => {
+                            chunk.write_string(value.as_ref())?;
+                        }
+                        Variant::BinaryString(value) => {
+                            chunk.write_binary_string(value.as_ref())?;
+                        }
+                        Variant::Tags(value) => {
+                            let buf = value.encode();
+                            chunk.write_binary_string(&buf)?;
+                        }
+                        Variant::Attributes(value) => {
+                            let mut buf = Vec::new();
+
+                            value
+                                .to_writer(&mut buf)
+                                .map_err(|_| invalid_value(i, &rbx_value))?;
+
+                            chunk.write_binary_string(&buf)?;
+                        }
+                        Variant::MaterialColors(value) => {
+                            chunk.write_binary_string(&value.encode())?;
+                        }
+                        _ => {
+                            return type_mismatch(
+                                i,
+                                &rbx_value,
+                                "String, Content, Tags, Attributes, MaterialColors, or BinaryString",
+                            );
+                        }
+                    }
+                }
+            }
+            Type::Bool => {
+                for (i, rbx_value) in values {
+                    if let Variant::Bool(value) = rbx_value.as_ref() {
+                        chunk.write_bool(*value)?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Bool");
+                    }
+                }
+            }
+            Type::Int32 => {
+                let mut buf = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Int32(value) = rbx_value.as_ref() {
+                        buf.push(*value);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Int32");
+                    }
+                }
+
+                chunk.write_interleaved_i32_array(buf.into_iter())?;
+            }
+            Type::Float32 => {
+                let mut buf = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Float32(value) = rbx_value.as_ref() {
+                        buf.push(*value);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Float32");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(buf.into_iter())?;
+            }
+            Type::Float64 => {
+                for (i, rbx_value) in values {
+                    match rbx_value.as_ref() {
+                        Variant::Float64(value) => {
+                            chunk.write_le_f64(*value)?;
+                        }
+                        Variant::Float32(value) => {
+                            chunk.write_le_f64(*value as f64)?;
+                        }
+                        _ => return type_mismatch(i, &rbx_value, "Float64"),
+                    }
+                }
+            }
+            Type::UDim => {
+                let mut scale = Vec::with_capacity(values.len());
+                let mut offset = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::UDim(value) = rbx_value.as_ref() {
+                        scale.push(value.scale);
+                        offset.push(value.offset);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "UDim");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(scale.into_iter())?;
+                chunk.write_interleaved_i32_array(offset.into_iter())?;
+            }
+            Type::UDim2 => {
+                let mut scale_x = Vec::with_capacity(values.len());
+                let mut scale_y = Vec::with_capacity(values.len());
+                let mut offset_x = Vec::with_capacity(values.len());
+                let mut offset_y = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::UDim2(value) = rbx_value.as_ref() {
+                        scale_x.push(value.x.scale);
+                        scale_y.push(value.y.scale);
+                        offset_x.push(value.x.offset);
+                        offset_y.push(value.y.offset);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "UDim2");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(scale_x.into_iter())?;
+                chunk.write_interleaved_f32_array(scale_y.into_iter())?;
+                chunk.write_interleaved_i32_array(offset_x.into_iter())?;
+                chunk.write_interleaved_i32_array(offset_y.into_iter())?;
+            }
+            Type::Font => {
+                for (i, rbx_value) in values {
+                    if let Variant::Font(value) = rbx_value.as_ref() {
+                        chunk.write_string(&value.family)?;
+                        chunk.write_le_u16(value.weight.as_u16())?;
+                        chunk.write_u8(value.style.as_u8())?;
+                        chunk.write_string(&value.cached_face_id.clone().unwrap_or_default())?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Font");
+                    }
+                }
+            }
+            Type::Ray => {
+                for (i, rbx_value) in values {
+                    if let Variant::Ray(value) = rbx_value.as_ref() {
+                        chunk.write_le_f32(value.origin.x)?;
+                        chunk.write_le_f32(value.origin.y)?;
+                        chunk.write_le_f32(value.origin.z)?;
+                        chunk.write_le_f32(value.direction.x)?;
+                        chunk.write_le_f32(value.direction.y)?;
+                        chunk.write_le_f32(value.direction.x)?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Ray");
+                    }
+                }
+            }
+            Type::Faces => {
+                for (i, rbx_value) in values {
+                    if let Variant::Faces(value) = rbx_value.as_ref() {
+                        chunk.write_u8(value.bits())?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Faces");
+                    }
+                }
+            }
+            Type::Axes => {
+                for (i, rbx_value) in values {
+                    if let Variant::Axes(value) = rbx_value.as_ref() {
+                        chunk.write_u8(value.bits())?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Axes");
+                    }
+                }
+            }
+            Type::BrickColor => {
+                let mut numbers = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::BrickColor(value) = rbx_value.as_ref() {
+                        numbers.push(*value as u32);
+                    } else if let Variant::Int32(value) = rbx_value.as_ref() {
+                        numbers.push(*value as u32);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "BrickColor");
+                    }
+                }
+
+                chunk.write_interleaved_u32_array(&numbers)?;
+            }
+            Type::Color3 => {
+                let mut r = Vec::with_capacity(values.len());
+                let mut g = Vec::with_capacity(values.len());
+                let mut b = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Color3(value) = rbx_value.as_ref() {
+                        r.push(value.r);
+                        g.push(value.g);
+                        b.push(value.b);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Color3");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(r.into_iter())?;
+                chunk.write_interleaved_f32_array(g.into_iter())?;
+                chunk.write_interleaved_f32_array(b.into_iter())?;
+            }
+            Type::Vector2 => {
+                let mut x = Vec::with_capacity(values.len());
+                let mut y = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Vector2(value) = rbx_value.as_ref() {
+                        x.push(value.x);
+                        y.push(value.y)
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Vector2");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(x.into_iter())?;
+                chunk.write_interleaved_f32_array(y.into_iter())?;
+            }
+            Type::Vector3 => {
+                let mut x = Vec::with_capacity(values.len());
+                let mut y = Vec::with_capacity(values.len());
+                let mut z = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Vector3(value) = rbx_value.as_ref() {
+                        x.push(value.x);
+                        y.push(value.y);
+                        z.push(value.z)
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Vector3");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(x.into_iter())?;
+                chunk.write_interleaved_f32_array(y.into_iter())?;
+                chunk.write_interleaved_f32_array(z.into_iter())?;
+            }
+            Type::CFrame => {
+                let mut rotations = Vec::with_capacity(values.len());
+                let mut x = Vec::with_capacity(values.len());
+                let mut y = Vec::with_capacity(values.len());
+                let mut z = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::CFrame(value) = rbx_value.as_ref() {
+                        rotations.push(value.orientation);
+                        x.push(value.position.x);
+                        y.push(value.position.y);
+                        z.push(value.position.z);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "CFrame");
+                    }
+                }
+
+                for matrix in rotations {
+                    if let Some(id) = matrix.to_basic_rotation_id() {
+                        chunk.write_u8(id)?;
+                    } else {
+                        chunk.write_u8(0x00)?;
+
+                        chunk.write_le_f32(matrix.x.x)?;
+                        chunk.write_le_f32(matrix.x.y)?;
+                        chunk.write_le_f32(matrix.x.z)?;
+
+                        chunk.write_le_f32(matrix.y.x)?;
+                        chunk.write_le_f32(matrix.y.y)?;
+                        chunk.write_le_f32(matrix.y.z)?;
+
+                        chunk.write_le_f32(matrix.z.x)?;
+                        chunk.write_le_f32(matrix.z.y)?;
+                        chunk.write_le_f32(matrix.z.z)?;
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(x.into_iter())?;
+                chunk.write_interleaved_f32_array(y.into_iter())?;
+                chunk.write_interleaved_f32_array(z.into_iter())?;
+            }
+            Type::Enum => {
+                let mut buf = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Enum(value) = rbx_value.as_ref() {
+                        buf.push(value.to_u32());
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Enum");
+                    }
+                }
+
+                chunk.write_interleaved_u32_array(&buf)?;
+            }
+            Type::Ref => {
+                let mut buf = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Ref(value) = rbx_value.as_ref() {
+                        if let Some(id) = self.id_to_referent.get(value) {
+                            buf.push(*id);
+                        } else {
+                            buf.push(-1);
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Ref");
+                    }
+                }
+
+                chunk.write_referent_array(buf.into_iter())?;
+            }
+            Type::Vector3int16 => {
+                for (i, rbx_value) in values {
+                    if let Variant::Vector3int16(value) = rbx_value.as_ref() {
+                        chunk.write_le_i16(value.x)?;
+                        chunk.write_le_i16(value.y)?;
+                        chunk.write_le_i16(value.z)?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Vector3int16");
+                    }
+                }
+            }
+            Type::NumberSequence => {
+                for (i, rbx_value) in values {
+                    if let Variant::NumberSequence(value) = rbx_value.as_ref() {
+                        chunk.write_le_u32(value.keypoints.len() as u32)?;
+
+                        for keypoint in &value.keypoints {
+                            chunk.write_le_f32(keypoint.time)?;
+                            chunk.write_le_f32(keypoint.value)?;
+                            chunk.write_le_f32(keypoint.envelope)?;
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "NumberSequence");
+                    }
+                }
+            }
+            Type::ColorSequence => {
+                for (i, rbx_value) in values {
+                    if let Variant::ColorSequence(value) = rbx_value.as_ref() {
+                        chunk.write_le_u32(value.keypoints.len() as u32)?;
+
+                        for keypoint in &value.keypoints {
+                            chunk.write_le_f32(keypoint.time)?;
+                            chunk.write_le_f32(keypoint.color.r)?;
+                            chunk.write_le_f32(keypoint.color.g)?;
+                            chunk.write_le_f32(keypoint.color.b)?;
+
+                            // write out a dummy value for envelope, which is serialized but doesn't do anything
+                            chunk.write_le_f32(0.0)?;
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "ColorSequence");
+                    }
+                }
+            }
+            Type::NumberRange => {
+                for (i, rbx_value) in values {
+                    if let Variant::NumberRange(value) = rbx_value.as_ref() {
+                        chunk.write_le_f32(value.min)?;
+                        chunk.write_le_f32(value.max)?;
+                    } else {
+                        return type_mismatch(i, &rbx_value, "NumberRange");
+                    }
+                }
+            }
+            Type::Rect => {
+                let mut x_min = Vec::with_capacity(values.len());
+                let mut y_min = Vec::with_capacity(values.len());
+                let mut x_max = Vec::with_capacity(values.len());
+                let mut y_max = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::Rect(value) = rbx_value.as_ref() {
+                        x_min.push(value.min.x);
+                        y_min.push(value.min.y);
+                        x_max.push(value.max.x);
+                        y_max.push(value.max.y);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "Rect");
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(x_min.into_iter())?;
+                chunk.write_interleaved_f32_array(y_min.into_iter())?;
+                chunk.write_interleaved_f32_array(x_max.into_iter())?;
+                chunk.write_interleaved_f32_array(y_max.into_iter())?;
+            }
+            Type::PhysicalProperties => {
+                for (i, rbx_value) in values {
+                    if let Variant::PhysicalProperties(value) = rbx_value.as_ref() {
+                        if let PhysicalProperties::Custom(props) = value {
+                            chunk.write_u8(1)?;
+                            chunk.write_le_f32(props.density)?;
+                            chunk.write_le_f32(props.friction)?;
+                            chunk.write_le_f32(props.elasticity)?;
+                            chunk.write_le_f32(props.friction_weight)?;
+                            chunk.write_le_f32(props.elasticity_weight)?;
+                        } else {
+                            chunk.write_u8(0)?;
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "PhysicalProperties");
+                    }
+                }
+            }
+            Type::Color3uint8 => {
+                let mut r = Vec::with_capacity(values.len());
+                let mut g = Vec::with_capacity(values.len());
+                let mut b = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    match rbx_value.as_ref() {
+                        Variant::Color3uint8(value) => {
+                            r.push(value.r);
+                            g.push(value.g);
+                            b.push(value.b);
+                        }
+                        Variant::Color3(value) => {
+                            let color: Color3uint8 = (*value).into();
+
+                            r.push(color.r);
+                            g.push(color.g);
+                            b.push(color.b);
+                        }
+                        _ => return type_mismatch(i, &rbx_value, "Color3uint8 or Color3"),
+                    }
+                }
+
+                chunk.write_all(r.as_slice())?;
+                chunk.write_all(g.as_slice())?;
+                chunk.write_all(b.as_slice())?;
+            }
+            Type::Int64 => {
+                let mut buf = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    match rbx_value.as_ref() {
+                        Variant::Int64(value) => {
+                            buf.push(*value);
+                        }
+                        Variant::Int32(value) => {
+                            buf.push(*value as i64);
+                        }
+                        _ => return type_mismatch(i, &rbx_value, "Int64"),
+                    }
+                }
+
+                chunk.write_interleaved_i64_array(buf.into_iter())?;
+            }
+            Type::SharedString => {
+                let mut entries = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::SharedString(value) = rbx_value.as_ref() {
+                        if let Some(id) = self.shared_string_ids.get(value) {
+                            entries.push(*id);
+                        } else {
+                            panic!(
+                                "SharedString {} was not found during type collection",
+                                value.hash()
+                            )
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "SharedString");
+                    }
+                }
+
+                chunk.write_interleaved_u32_array(&entries)?;
+            }
+            Type::OptionalCFrame => {
+                let mut rotations = Vec::with_capacity(values.len());
+                let mut bools = Vec::with_capacity(values.len());
+                let mut x = Vec::with_capacity(values.len());
+                let mut y = Vec::with_capacity(values.len());
+                let mut z = Vec::with_capacity(values.len());
+
+                chunk.write_u8(Type::CFrame as u8)?;
+
+                for (i, rbx_value) in values {
+                    if let Variant::OptionalCFrame(value) = rbx_value.as_ref() {
+                        if let Some(value) = value {
+                            rotations.push(value.orientation);
+                            x.push(value.position.x);
+                            y.push(value.position.y);
+                            z.push(value.position.z);
+                            bools.push(0x01);
+                        } else {
+                            rotations.push(Matrix3::identity());
+                            x.push(0.0);
+                            y.push(0.0);
+                            z.push(0.0);
+                            bools.push(0x00);
+                        }
+                    } else {
+                        return type_mismatch(i, &rbx_value, "OptionalCFrame");
+                    }
+                }
+
+                for matrix in rotations {
+                    if let Some(id) = matrix.to_basic_rotation_id() {
+                        chunk.write_u8(id)?;
+                    } else {
+                        chunk.write_u8(0x00)?;
+
+                        chunk.write_le_f32(matrix.x.x)?;
+                        chunk.write_le_f32(matrix.x.y)?;
+                        chunk.write_le_f32(matrix.x.z)?;
+
+                        chunk.write_le_f32(matrix.y.x)?;
+                        chunk.write_le_f32(matrix.y.y)?;
+                        chunk.write_le_f32(matrix.y.z)?;
+
+                        chunk.write_le_f32(matrix.z.x)?;
+                        chunk.write_le_f32(matrix.z.y)?;
+                        chunk.write_le_f32(matrix.z.z)?;
+                    }
+                }
+
+                chunk.write_interleaved_f32_array(x.into_iter())?;
+                chunk.write_interleaved_f32_array(y.into_iter())?;
+                chunk.write_interleaved_f32_array(z.into_iter())?;
+
+                chunk.write_u8(Type::Bool as u8)?;
+                chunk.write_all(bools.as_slice())?;
+            }
+            Type::UniqueId => {
+                let mut blobs = Vec::with_capacity(values.len());
+                for (i, rbx_value) in values {
+                    if let Variant::UniqueId(value) = rbx_value.as_ref() {
+                        let mut blob = [0; 16];
+                        // This is maybe not the best solution to this
+                        // but we can always change it.
+                        blob[0..4].copy_from_slice(&value.index().to_be_bytes());
+                        blob[4..8].copy_from_slice(&value.time().to_be_bytes());
+                        blob[8..].copy_from_slice(&value.random().rotate_left(1).to_be_bytes());
+                        blobs.push(blob);
+                    } else {
+                        return type_mismatch(i, &rbx_value, "UniqueId");
+                    }
+                }
+
+                chunk.write_interleaved_bytes::<16>(&blobs)?;
+            }
+            Type::SecurityCapabilities => {
+                let mut capabilities = Vec::with_capacity(values.len());
+
+                for (i, rbx_value) in values {
+                    if let Variant::SecurityCapabilities(value) = rbx_value.as_ref() {
+                        capabilities.push(value.bits() as i64)
+                    } else {
+                        return type_mismatch(i, &rbx_value, "SecurityCapabilities");
+                    }
+                }
+
+                chunk.write_interleaved_i64_array(capabilities.into_iter())?;
+            }
+        }
+
+        Ok(chunk)
+    }
+
+    /// Encodes a PROP chunk for a property holding opaque values, or returns
+    /// `None` if the values can't be written.
+    fn serialize_opaque_property<'db>(
+        &self,
+        type_name: &str,
+        type_info: &TypeInfo<'dom, 'db>,
+        prop_name: &str,
+    ) -> Result<Option<ChunkBuilder>, InnerError> {
+        let (type_id, data) = match opaque_chunk_data(&type_info.instances, prop_name) {
+            Some(found) => found,
+            None => {
+                log::warn!(
+                    "Property {}.{} holds opaque values that no longer line up with \
+                     the instances they were read from and will not be written",
+                    type_name,
+                    prop_name
+                );
+                return Ok(None);
+            }
+        };
+
+        log::trace!(
+            "Writing opaque property {}.{} (type ID {})",
+            type_name,
+            prop_name,
+            type_id
+        );
+
+        let mut chunk = self.compression.chunk_builder(b"PROP");
+
+        chunk.write_le_u32(type_info.type_id)?;
+        chunk.write_string(prop_name)?;
+        chunk.write_u8(type_id)?;
+        chunk.write_all(data)?;
+
+        Ok(Some(chunk))
+    }
+}
+
+/// Equivalent to Instance:GetFullName() from Roblox.
+fn full_name_for(dom: &WeakDom, subject_ref: Ref) -> String {
+    let mut components = Vec::new();
+    let mut current_id = subject_ref;
+
+    while current_id.is_some() {
+        let instance = dom.get_by_ref(current_id).unwrap();
+        components.push(instance.name.as_str());
+        current_id = instance.parent();
+    }
+
+    let mut name = String::new();
+    for component in components.iter().rev() {
+        name.push_str(component);
+        name.push('.');
+    }
+    name.pop();
+
+    name
+}
+
+/// Returns the position an instance had in the binary `PROP` chunk that the
+/// opaque value of the given property was read from.
+fn opaque_index(instance: &Instance, prop_name: &str) -> u32 {
+    match instance.properties.get(prop_name) {
+        Some(Variant::Opaque(opaque)) => match opaque.format() {
+            OpaqueFormat::Binary { index, .. } => *index,
+            _ => u32::MAX,
+        },
+        _ => u32::MAX,
+    }
+}
+
+/// Finds the type ID and data of the `PROP` chunk that the opaque values of a
+/// property were read from. Opaque values can only be written if every
+/// instance has one from the same chunk, in the same order as the chunk.
+fn opaque_chunk_data<'dom>(
+    instances: &[&'dom Instance],
+    prop_name: &str,
+) -> Option<(u8, &'dom [u8])> {
+    let mut found: Option<(u8, &Opaque)> = None;
+
+    for (i, instance) in instances.iter().enumerate() {
+        let opaque = match instance.properties.get(prop_name) {
+            Some(Variant::Opaque(opaque)) => opaque,
+            _ => return None,
+        };
+
+        let type_id = match opaque.format() {
+            OpaqueFormat::Binary { type_id, index } if *index as usize == i => *type_id,
+            _ => return None,
+        };
+
+        match found {
+            Some((first_type_id, first)) => {
+                if type_id != first_type_id || !opaque.shares_data(first) {
+                    return None;
+                }
+            }
+            None => found = Some((type_id, opaque)),
+        }
+    }
+
+    found.map(|(type_id, opaque)| (type_id, opaque.data()))
+}
@@ -0,0 +1,225 @@
+use rbx_dom_weak::{InstanceBuilder, WeakDom};
+
+use crate::{text_deserializer::DecodedModel, to_writer, DecodeError, Deserializer};
+
+fn assemble(yaml: &str) -> Vec<u8> {
+    let model: DecodedModel = serde_yaml::from_str(yaml).expect("failed to parse model");
+
+    let mut buffer = Vec::new();
+    model
+        .to_writer(&mut buffer)
+        .expect("failed to assemble model");
+    buffer
+}
+
+fn decode(buffer: &[u8]) -> DecodeError {
+    Deserializer::new()
+        .deserialize(buffer)
+        .expect_err("model should not have decoded")
+}
+
+/// Ensures that errors in property values say which chunk, property and
+/// instance they came from, whether the file is decoded all at once or
+/// streamed.
+#[test]
+fn invalid_prop_value() {
+    let buffer = assemble(
+        r#"
+        num_types: 1
+        num_instances: 2
+        chunks:
+          - Sstr:
+              version: 0
+              entries:
+                - data: "68 65 6c 6c 6f"
+          - Inst:
+              type_id: 0
+              type_name: Model
+              object_format: 0
+              referents: [4, 9]
+          - Prop:
+              type_id: 0
+              prop_name: ModelMeshData
+              prop_type: SharedString
+              values: [0, 3]
+          - Prnt:
+              version: 0
+              links: [[4, -1], [9, -1]]
+          - End
+        "#,
+    );
+
+    let error = decode(&buffer);
+    let offset = error.offset().expect("error should have an offset") as usize;
+
+    assert_eq!(error.chunk_name(), Some(*b"PROP"));
+    assert_eq!(&buffer[offset..offset + 4], b"PROP");
+    assert_eq!(error.class_name(), Some("Model"));
+    assert_eq!(error.property_name(), Some("ModelMeshData"));
+    assert_eq!(error.referent(), Some(9));
+    assert!(error.to_string().ends_with(&format!(
+        "(in PROP chunk at byte {}, property Model.ModelMeshData, instance 9)",
+        offset
+    )));
+
+    let streamed = Deserializer::new()
+        .deserialize_streaming(buffer.as_slice())
+        .unwrap()
+        .find_map(Result::err)
+        .expect("stream should have returned an error");
+
+    assert_eq!(streamed.to_string(), error.to_string());
+}
+
+#[test]
+fn unknown_parent() {
+    let buffer = assemble(
+        r#"
+        num_types: 1
+        num_instances: 1
+        chunks:
+          - Inst:
+              type_id: 0
+              type_name: Folder
+              object_format: 0
+              referents: [0]
+          - Prnt:
+              version: 0
+              links: [[0, 5]]
+          - End
+        "#,
+    );
+
+    let error = decode(&buffer);
+    let offset = error.offset().unwrap() as usize;
+
+    assert_eq!(error.chunk_name(), Some(*b"PRNT"));
+    assert_eq!(&buffer[offset..offset + 4], b"PRNT");
+    assert_eq!(error.referent(), Some(0));
+    assert_eq!(error.class_name(), None);
+}
+
+/// Ensures that instances given more than one parent are reported as errors,
+/// and only keep their first parent when decoding leniently.
+#[test]
+fn duplicate_parent() {
+    let buffer = assemble(
+        r#"
+        num_types: 1
+        num_instances: 3
+        chunks:
+          - Inst:
+              type_id: 0
+              type_name: Folder
+              object_format: 0
+              referents: [0, 1, 2]
+          - Prnt:
+              version: 0
+              links: [[0, -1], [1, -1], [2, 0], [2, 1]]
+          - End
+        "#,
+    );
+
+    let error = decode(&buffer);
+    assert_eq!(error.chunk_name(), Some(*b"PRNT"));
+    assert_eq!(error.referent(), Some(2));
+
+    let (dom, warnings) = Deserializer::new()
+        .deserialize_lenient(buffer.as_slice())
+        .expect("lenient decoding should not fail");
+
+    let roots = dom.root().children();
+    assert_eq!(dom.get_by_ref(roots[0]).unwrap().children().len(), 1);
+    assert_eq!(dom.get_by_ref(roots[1]).unwrap().children().len(), 0);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].referent(), Some(2));
+}
+
+/// Ensures that files which end in the middle of a chunk are reported as
+/// errors instead of panicking.
+#[test]
+fn truncated_file() {
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = dom.insert(dom.root_ref(), InstanceBuilder::new("Folder"));
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &dom, &[folder]).unwrap();
+    buffer.truncate(buffer.len() - 4);
+
+    let error = decode(&buffer);
+    let offset = error.offset().unwrap() as usize;
+
+    assert_eq!(error.chunk_name(), Some(*b"END\0"));
+    assert_eq!(&buffer[offset..offset + 4], b"END\0");
+}
+
+/// Ensures that decoding leniently skips the parts of a file that can't be
+/// decoded and keeps everything else.
+#[test]
+fn lenient() {
+    let buffer = assemble(
+        r#"
+        num_types: 1
+        num_instances: 3
+        chunks:
+          - Inst:
+              type_id: 0
+              type_name: Model
+              object_format: 0
+              referents: [0, 1, 2]
+          - Prop:
+              type_id: 0
+              prop_name: Name
+              prop_type: String
+              values: [First, Second, Third]
+          - Prop:
+              type_id: 0
+              prop_name: ModelMeshData
+              prop_type: SharedString
+              values: [0, 0, 0]
+          - Prnt:
+              version: 0
+              links: [[0, -1], [1, 0], [2, 8]]
+          - End
+        "#,
+    );
+
+    assert!(Deserializer::new().deserialize(buffer.as_slice()).is_err());
+
+    let (dom, warnings) = Deserializer::new()
+        .deserialize_lenient(buffer.as_slice())
+        .expect("lenient decoding should not fail");
+
+    let first = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(first.name, "First");
+    assert_eq!(first.properties.get("ModelMeshData"), None);
+    assert_eq!(dom.root().children().len(), 1);
+    assert_eq!(first.children().len(), 1);
+    assert_eq!(dom.get_by_ref(first.children()[0]).unwrap().name, "Second");
+
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].property_name(), Some("ModelMeshData"));
+    assert_eq!(warnings[1].chunk_name(), Some(*b"PRNT"));
+    assert_eq!(warnings[1].referent(), Some(2));
+}
+
+#[test]
+fn lenient_truncated_file() {
+    let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = dom.insert(
+        dom.root_ref(),
+        InstanceBuilder::new("Folder").with_name("Kept"),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &dom, &[folder]).unwrap();
+    buffer.truncate(buffer.len() - 12);
+
+    let (dom, warnings) = Deserializer::new()
+        .deserialize_lenient(buffer.as_slice())
+        .expect("lenient decoding should not fail");
+
+    let folder = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(folder.name, "Kept");
+    assert_eq!(warnings.len(), 1);
+}
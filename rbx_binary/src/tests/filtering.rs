@@ -0,0 +1,98 @@
+use rbx_dom_weak::{types::Variant, InstanceBuilder, WeakDom};
+
+use crate::{to_writer, Deserializer};
+
+fn encode() -> Vec<u8> {
+    let dom = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("Map")
+                .with_child(
+                    InstanceBuilder::new("Model").with_child(
+                        InstanceBuilder::new("Script")
+                            .with_name("Spawner")
+                            .with_property("Source", "print('spawn')"),
+                    ),
+                )
+                .with_child(
+                    InstanceBuilder::new("Part")
+                        .with_name("Floor")
+                        .with_property("Transparency", 0.5f32),
+                )
+                .with_child(
+                    InstanceBuilder::new("StringValue")
+                        .with_name("Version")
+                        .with_property("Value", "1.0"),
+                ),
+        ),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &dom, dom.root().children()).expect("failed to encode model");
+    buffer
+}
+
+fn find<'a>(dom: &'a WeakDom, name: &str) -> Option<&'a rbx_dom_weak::Instance> {
+    dom.descendants(dom.root_ref())
+        .find(|instance| instance.name == name)
+}
+
+#[test]
+fn only_classes() {
+    let dom = Deserializer::new()
+        .only_classes(["LuaSourceContainer"])
+        .deserialize(encode().as_slice())
+        .unwrap();
+
+    let spawner = find(&dom, "Spawner").unwrap();
+    assert_eq!(
+        spawner.properties.get("Source"),
+        Some(&Variant::String("print('spawn')".into()))
+    );
+
+    let floor = find(&dom, "Floor").unwrap();
+    assert!(floor.properties.is_empty());
+
+    let version = find(&dom, "Version").unwrap();
+    assert!(version.properties.is_empty());
+}
+
+#[test]
+fn only_properties() {
+    let dom = Deserializer::new()
+        .only_properties(["Value", "Transparency"])
+        .deserialize(encode().as_slice())
+        .unwrap();
+
+    let spawner = find(&dom, "Spawner").unwrap();
+    assert!(spawner.properties.is_empty());
+
+    let floor = find(&dom, "Floor").unwrap();
+    assert_eq!(floor.properties.len(), 1);
+    assert_eq!(
+        floor.properties.get("Transparency"),
+        Some(&Variant::Float32(0.5))
+    );
+
+    let version = find(&dom, "Version").unwrap();
+    assert_eq!(
+        version.properties.get("Value"),
+        Some(&Variant::String("1.0".into()))
+    );
+}
+
+#[test]
+fn prune_filtered() {
+    let dom = Deserializer::new()
+        .only_classes(["Script"])
+        .prune_filtered(true)
+        .deserialize(encode().as_slice())
+        .unwrap();
+
+    let names: Vec<&str> = dom
+        .descendants(dom.root_ref())
+        .map(|instance| instance.name.as_str())
+        .collect();
+
+    assert_eq!(names, ["Map", "Model", "Spawner"]);
+}
@@ -1,5 +1,9 @@
 mod core_read_write;
+mod decode_errors;
+mod filtering;
 mod models;
 mod places;
 mod serializer;
+mod streaming;
+mod text_format;
 mod util;
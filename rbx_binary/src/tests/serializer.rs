@@ -1,9 +1,19 @@
+use std::sync::Arc;
+
 use rbx_dom_weak::{
-    types::{BrickColor, Color3, Color3uint8, Enum, Font, Ref, Region3, SharedString, Vector3},
+    types::{
+        BrickColor, Color3, Color3uint8, Enum, Font, Opaque, Ref, Region3, SharedString, Variant,
+        Vector3,
+    },
     InstanceBuilder, WeakDom,
 };
 
-use crate::{text_deserializer::DecodedModel, to_writer};
+use crate::{
+    chunk::RawChunk,
+    deserializer::FileHeader,
+    text_deserializer::{DecodedChunk, DecodedModel},
+    to_writer, ChunkCompression, Deserializer, Serializer, UnknownChunk,
+};
 
 /// A basic test to make sure we can serialize the simplest instance: a Folder.
 #[test]
@@ -193,3 +203,340 @@ fn default_shared_string() {
     let decoded = DecodedModel::from_reader(buf.as_slice());
     insta::assert_yaml_snapshot!(decoded);
 }
+
+/// Ensures that metadata stored on the DOM is written to a META chunk and read
+/// back into the DOM.
+#[test]
+fn metadata_round_trip() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    tree.metadata_mut()
+        .insert("ExplicitAutoJoints".to_owned(), "true".to_owned());
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[]).expect("failed to encode model");
+
+    let decoded = DecodedModel::from_reader(buffer.as_slice());
+    insta::assert_yaml_snapshot!(decoded);
+
+    let round_tripped = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    assert_eq!(round_tripped.metadata(), tree.metadata());
+}
+
+/// Ensures that chunks rbx_binary doesn't understand survive being read and
+/// written again.
+#[test]
+fn unknown_chunks_round_trip() {
+    let tree =
+        WeakDom::new(InstanceBuilder::new("DataModel").with_child(InstanceBuilder::new("Folder")));
+    let unknown_chunks = [
+        UnknownChunk {
+            name: *b"SIGN",
+            data: vec![1, 2, 3, 4],
+            compressed: false,
+        },
+        UnknownChunk {
+            name: *b"FUTR",
+            data: b"hello, future".to_vec(),
+            compressed: true,
+        },
+    ];
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .serialize_with_unknown_chunks(&mut buffer, &tree, tree.root().children(), &unknown_chunks)
+        .expect("failed to encode model");
+
+    let decoded = DecodedModel::from_reader(buffer.as_slice());
+    insta::assert_yaml_snapshot!(decoded);
+
+    let (_, round_tripped) = Deserializer::new()
+        .deserialize_with_unknown_chunks(buffer.as_slice())
+        .expect("failed to decode model");
+    assert_eq!(round_tripped, unknown_chunks);
+}
+
+/// Ensures that values with a type rbx_binary doesn't understand survive being
+/// read and written again.
+#[test]
+fn opaque_round_trip() {
+    let data: Arc<[u8]> = Arc::from(vec![1, 2, 3, 4, 5, 6]);
+    let tree = WeakDom::new(
+        InstanceBuilder::new("DataModel")
+            .with_child(
+                InstanceBuilder::new("Folder")
+                    .with_property("Mystery", Opaque::binary(0x7f, 1, data.clone())),
+            )
+            .with_child(
+                InstanceBuilder::new("Folder")
+                    .with_property("Mystery", Opaque::binary(0x7f, 0, data.clone())),
+            ),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, tree.root().children()).expect("failed to encode model");
+
+    let decoded = DecodedModel::from_reader(buffer.as_slice());
+    insta::assert_yaml_snapshot!(decoded);
+
+    let round_tripped = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let values: Vec<&Variant> = round_tripped
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| &round_tripped.get_by_ref(referent).unwrap().properties["Mystery"])
+        .collect();
+
+    assert_eq!(
+        values,
+        [
+            &Variant::Opaque(Opaque::binary(0x7f, 1, data.clone())),
+            &Variant::Opaque(Opaque::binary(0x7f, 0, data)),
+        ]
+    );
+
+    let mut rewritten = Vec::new();
+    to_writer(
+        &mut rewritten,
+        &round_tripped,
+        round_tripped.root().children(),
+    )
+    .expect("failed to encode model");
+    assert_eq!(rewritten, buffer);
+}
+
+/// Ensures that a file with many PROP chunks is written the same way whether
+/// or not chunks are encoded in parallel, and that it survives being read and
+/// written again.
+#[test]
+fn many_prop_chunks() {
+    let mut root = InstanceBuilder::new("DataModel");
+    for i in 0..3 {
+        root = root
+            .with_child(InstanceBuilder::new("IntValue").with_property("Value", i))
+            .with_child(
+                InstanceBuilder::new("StringValue").with_property("Value", format!("value {}", i)),
+            )
+            .with_child(
+                InstanceBuilder::new("Part")
+                    .with_property("Anchored", i % 2 == 0)
+                    .with_property("Size", Vector3::new(i as f32, 1.0, 2.0)),
+            );
+    }
+    let tree = WeakDom::new(root);
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, tree.root().children()).expect("failed to encode model");
+
+    let decoded = DecodedModel::from_reader(buffer.as_slice());
+    insta::assert_yaml_snapshot!(decoded);
+
+    for parallel in [false, true] {
+        let mut output = Vec::new();
+        Serializer::new()
+            .parallel(parallel)
+            .serialize(&mut output, &tree, tree.root().children())
+            .expect("failed to encode model");
+        assert_eq!(output, buffer);
+    }
+
+    let round_tripped = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let mut rewritten = Vec::new();
+    to_writer(
+        &mut rewritten,
+        &round_tripped,
+        round_tripped.root().children(),
+    )
+    .expect("failed to encode model");
+    assert_eq!(rewritten, buffer);
+}
+
+/// Ensures that PROP chunks are decoded one at a time unless they're decoded in
+/// parallel, and that both ways of reading them produce the same tree.
+#[test]
+fn prop_chunks_decoded_one_at_a_time() {
+    let mut root = InstanceBuilder::new("DataModel");
+    for i in 0..3 {
+        root = root
+            .with_child(InstanceBuilder::new("IntValue").with_property("Value", i))
+            .with_child(
+                InstanceBuilder::new("Part")
+                    .with_property("Anchored", i % 2 == 0)
+                    .with_property("Size", Vector3::new(i as f32, 1.0, 2.0)),
+            );
+    }
+    let tree = WeakDom::new(root);
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, tree.root().children()).expect("failed to encode model");
+
+    let largest_run = |parallel| {
+        Deserializer::new()
+            .parallel(parallel)
+            .largest_prop_run(buffer.as_slice())
+            .expect("failed to decode model")
+    };
+    assert_eq!(largest_run(false), 1);
+    assert!(largest_run(true) > 1);
+
+    for parallel in [false, true] {
+        let decoded = Deserializer::new()
+            .parallel(parallel)
+            .deserialize(buffer.as_slice())
+            .expect("failed to decode model");
+
+        let mut rewritten = Vec::new();
+        to_writer(&mut rewritten, &decoded, decoded.root().children())
+            .expect("failed to encode model");
+        assert_eq!(rewritten, buffer);
+    }
+}
+
+/// Ensures that chunks can be written and read with zstd compression.
+#[test]
+fn zstd_compression() {
+    let tree = WeakDom::new(InstanceBuilder::new("DataModel").with_child(
+        InstanceBuilder::new("StringValue").with_property("Value", "hello, ".repeat(100)),
+    ));
+
+    let mut lz4 = Vec::new();
+    to_writer(&mut lz4, &tree, tree.root().children()).expect("failed to encode model");
+
+    let mut zstd = Vec::new();
+    Serializer::new()
+        .compression(ChunkCompression::Zstd { level: 3 })
+        .serialize(&mut zstd, &tree, tree.root().children())
+        .expect("failed to encode model");
+
+    assert_ne!(zstd, lz4);
+    assert!(zstd
+        .windows(4)
+        .any(|window| window == [0x28, 0xb5, 0x2f, 0xfd]));
+
+    let round_tripped = Deserializer::new()
+        .deserialize(zstd.as_slice())
+        .expect("failed to decode model");
+
+    let mut rewritten = Vec::new();
+    to_writer(
+        &mut rewritten,
+        &round_tripped,
+        round_tripped.root().children(),
+    )
+    .expect("failed to encode model");
+    assert_eq!(rewritten, lz4);
+}
+
+/// Ensures that chunks are compressed according to the serializer's
+/// compression settings.
+#[test]
+fn compression_policy() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("DataModel")
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "a".repeat(500)))
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "b")),
+    );
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .compression(ChunkCompression::Uncompressed)
+        .chunk_compression(b"PROP", ChunkCompression::Zstd { level: 1 })
+        .compression_threshold(100)
+        .serialize(&mut buffer, &tree, tree.root().children())
+        .expect("failed to encode model");
+
+    let mut compressed = Vec::new();
+    let mut reader = buffer.as_slice();
+    FileHeader::decode(&mut reader).expect("invalid file header");
+    loop {
+        let chunk = RawChunk::read(&mut reader).expect("invalid chunk");
+        let name = *chunk.name();
+        let is_compressed = chunk.decompress().expect("invalid chunk").compressed;
+
+        if is_compressed {
+            compressed.push(name);
+        }
+
+        if &name == b"END\0" {
+            break;
+        }
+    }
+
+    // Only the PROP chunk holding the long string value is big enough to be
+    // compressed.
+    assert_eq!(compressed, [*b"PROP"]);
+
+    let round_tripped = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+    let values: Vec<_> = round_tripped
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| round_tripped.get_by_ref(referent).unwrap().properties["Value"].clone())
+        .collect();
+    assert_eq!(
+        values,
+        [
+            Variant::String("a".repeat(500)),
+            Variant::String("b".to_owned())
+        ]
+    );
+}
+
+/// Ensures that deterministic serializers write equal DOMs as the same bytes,
+/// regardless of referents or the order properties were added in, and number
+/// classes in the order they're written.
+#[test]
+fn deterministic_output() {
+    let build = |reversed: bool| {
+        let mut properties = vec![
+            ("Size", Variant::Vector3(Vector3::new(1.0, 2.0, 3.0))),
+            ("size", Variant::Vector3(Vector3::new(4.0, 5.0, 6.0))),
+            ("Anchored", Variant::Bool(true)),
+            ("BrickColor", Variant::BrickColor(BrickColor::Alder)),
+        ];
+        if reversed {
+            properties.reverse();
+        }
+
+        WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(InstanceBuilder::new("Part").with_properties(properties))
+                .with_child(InstanceBuilder::new("Model").with_child(
+                    InstanceBuilder::new("Folder").with_property(
+                        "SharedData",
+                        Variant::SharedString(SharedString::new(b"shared".to_vec())),
+                    ),
+                )),
+        )
+    };
+
+    let serializer = Serializer::new().deterministic(true);
+    let serialize = |tree: &WeakDom| {
+        let mut buffer = Vec::new();
+        serializer
+            .serialize(&mut buffer, tree, tree.root().children())
+            .expect("failed to encode model");
+        buffer
+    };
+
+    let first = serialize(&build(false));
+    let second = serialize(&build(true));
+    assert_eq!(first, second);
+
+    let decoded = DecodedModel::from_reader(first.as_slice());
+    let types: Vec<_> = decoded
+        .chunks
+        .iter()
+        .filter_map(|chunk| match chunk {
+            DecodedChunk::Inst {
+                type_id, type_name, ..
+            } => Some((*type_id, type_name.as_str())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(types, [(0, "Folder"), (1, "Model"), (2, "Part")]);
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use rbx_dom_weak::{
+    types::{Content, Ref, Variant},
+    InstanceBuilder, WeakDom,
+};
+
+use crate::{to_writer, DecodeEvent, Deserializer};
+
+fn encode(dom: &WeakDom) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, dom, dom.root().children()).expect("failed to encode model");
+    buffer
+}
+
+/// Streams a model and makes sure every instance, name, property and parent
+/// is reported with consistent refs.
+#[test]
+fn stream_events() {
+    let dom = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("Assets")
+                .with_child(
+                    InstanceBuilder::new("Decal")
+                        .with_name("Logo")
+                        .with_property("Texture", Content::from("rbxassetid://1")),
+                )
+                .with_child(
+                    InstanceBuilder::new("Decal")
+                        .with_name("Banner")
+                        .with_property("Texture", Content::from("rbxassetid://2")),
+                ),
+        ),
+    );
+    let buffer = encode(&dom);
+
+    let deserializer = Deserializer::new();
+    let events: Vec<DecodeEvent> = deserializer
+        .deserialize_streaming(buffer.as_slice())
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let mut classes = HashMap::new();
+    let mut names = HashMap::new();
+    let mut textures = HashMap::new();
+    let mut parents = HashMap::new();
+
+    for event in events {
+        match event {
+            DecodeEvent::Instances {
+                class_name,
+                referents,
+            } => {
+                for referent in referents {
+                    classes.insert(referent, class_name.clone());
+                }
+            }
+            DecodeEvent::Property {
+                class_name,
+                property_name,
+                values,
+            } => {
+                for (referent, value) in values {
+                    assert_eq!(classes[&referent], class_name);
+
+                    match (property_name.as_str(), value) {
+                        ("Name", Variant::String(name)) => {
+                            names.insert(name, referent);
+                        }
+                        ("Texture", Variant::Content(content)) => {
+                            textures.insert(referent, content);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            DecodeEvent::Parents { pairs } => parents.extend(pairs),
+            _ => {}
+        }
+    }
+
+    assert_eq!(classes.len(), 3);
+
+    let assets = names["Assets"];
+    let logo = names["Logo"];
+    let banner = names["Banner"];
+
+    assert_eq!(classes[&assets], "Folder");
+    assert_eq!(textures[&logo], Content::from("rbxassetid://1"));
+    assert_eq!(textures[&banner], Content::from("rbxassetid://2"));
+    assert_eq!(parents[&assets], Ref::none());
+    assert_eq!(parents[&logo], assets);
+    assert_eq!(parents[&banner], assets);
+}
+
+/// A truncated file should produce an error and then end the stream.
+#[test]
+fn stream_truncated() {
+    let dom =
+        WeakDom::new(InstanceBuilder::new("DataModel").with_child(InstanceBuilder::new("Folder")));
+    let buffer = encode(&dom);
+    let truncated = &buffer[..buffer.len() - 10];
+
+    let deserializer = Deserializer::new();
+    let mut stream = deserializer.deserialize_streaming(truncated).unwrap();
+
+    assert!(stream.by_ref().any(|event| event.is_err()));
+    assert!(stream.next().is_none());
+}
@@ -0,0 +1,139 @@
+use rbx_dom_weak::{
+    types::{CFrame, Color3uint8, Enum, Font, Matrix3, Variant, Vector3},
+    InstanceBuilder, WeakDom,
+};
+
+use crate::{text_deserializer::DecodedModel, to_writer, Deserializer};
+
+fn encode(dom: &WeakDom) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, dom, dom.root().children()).expect("failed to encode model");
+    buffer
+}
+
+fn assemble(model: &DecodedModel) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    model
+        .to_writer(&mut buffer)
+        .expect("failed to assemble model");
+    buffer
+}
+
+/// Ensures that assembling the text representation of a file produces the same
+/// file, both directly and after going through YAML.
+#[test]
+fn round_trip() {
+    let mut dom = WeakDom::new(
+        InstanceBuilder::new("DataModel")
+            .with_child(
+                InstanceBuilder::new("Part")
+                    .with_property(
+                        "CFrame",
+                        CFrame::new(
+                            Vector3::new(1.0, 2.0, 3.0),
+                            Matrix3::new(
+                                Vector3::new(0.0, 0.6, 0.8),
+                                Vector3::new(1.0, 0.0, 0.0),
+                                Vector3::new(0.0, 0.8, -0.6),
+                            ),
+                        ),
+                    )
+                    .with_property("Color", Color3uint8::new(255, 128, 0))
+                    .with_property("Material", Enum::from_u32(256))
+                    .with_property("Anchored", true),
+            )
+            .with_child(InstanceBuilder::new("Model").with_property(
+                "WorldPivotData",
+                Some(CFrame::new(
+                    Vector3::new(4.0, 5.0, 6.0),
+                    Matrix3::identity(),
+                )),
+            ))
+            .with_child(InstanceBuilder::new("Model"))
+            .with_child(InstanceBuilder::new("NumberValue").with_property("Value", 0.1f64))
+            .with_child(
+                InstanceBuilder::new("TextLabel").with_property("FontFace", Font::default()),
+            )
+            .with_child(InstanceBuilder::new("ObjectValue")),
+    );
+
+    let part = dom.root().children()[0];
+    let object_value = dom.root().children()[5];
+    dom.get_by_ref_mut(object_value)
+        .unwrap()
+        .properties
+        .insert("Value".to_owned(), Variant::Ref(part));
+    dom.metadata_mut()
+        .insert("ExplicitAutoJoints".to_owned(), "true".to_owned());
+
+    let buffer = encode(&dom);
+    let model = DecodedModel::from_reader(buffer.as_slice());
+    assert_eq!(assemble(&model), buffer);
+
+    let yaml = serde_yaml::to_string(&model).expect("failed to serialize model");
+    let from_yaml: DecodedModel = serde_yaml::from_str(&yaml).expect("failed to parse model");
+    assert_eq!(assemble(&from_yaml), buffer);
+}
+
+/// Ensures that hand-written files can be assembled, including ones that
+/// rbx_binary would refuse to read.
+#[test]
+fn hand_written() {
+    let valid: DecodedModel = serde_yaml::from_str(
+        r#"
+        num_types: 1
+        num_instances: 1
+        chunks:
+          - Inst:
+              type_id: 0
+              type_name: StringValue
+              object_format: 0
+              referents: [0]
+          - Prop:
+              type_id: 0
+              prop_name: Name
+              prop_type: String
+              values: [Greeting]
+          - Prop:
+              type_id: 0
+              prop_name: Value
+              prop_type: String
+              values: ["hello, world"]
+          - Prnt:
+              version: 0
+              links: [[0, -1]]
+          - End
+        "#,
+    )
+    .expect("failed to parse model");
+
+    let dom = Deserializer::new()
+        .deserialize(assemble(&valid).as_slice())
+        .expect("failed to decode model");
+    let instance = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(instance.name, "Greeting");
+    assert_eq!(
+        instance.properties.get("Value"),
+        Some(&Variant::String("hello, world".to_owned()))
+    );
+
+    // This PROP chunk refers to a type that was never declared.
+    let invalid: DecodedModel = serde_yaml::from_str(
+        r#"
+        num_types: 0
+        num_instances: 0
+        chunks:
+          - Prop:
+              type_id: 7
+              prop_name: Name
+              prop_type: String
+              values: []
+          - End
+        "#,
+    )
+    .expect("failed to parse model");
+
+    assert!(Deserializer::new()
+        .deserialize(assemble(&invalid).as_slice())
+        .is_err());
+}
@@ -1,10 +1,21 @@
 //! Deserializer that reads a file and creates a debug representation of it.
 //! It's intended to be used to snapshot test the binary serializer without
 //! suffering from same-inverse-bug problems.
+//!
+//! The debug representation can also be deserialized with serde and turned
+//! back into a binary file with `DecodedModel::to_writer`, which is useful for
+//! hand-crafting unusual or malformed files. Shared strings must be given as an
+//! entry with a `data` field when deserializing, since only their length and
+//! hash are included when serializing.
 
 #![allow(missing_docs)]
 
-use std::{collections::HashMap, convert::TryInto, fmt::Write, io::Read};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fmt::{self, Write},
+    io::Read,
+};
 
 use rbx_dom_weak::types::{
     Axes, BrickColor, CFrame, Color3, Color3uint8, ColorSequence, ColorSequenceKeypoint,
@@ -12,11 +23,15 @@ use rbx_dom_weak::types::{
     NumberSequence, NumberSequenceKeypoint, PhysicalProperties, Ray, Rect, SecurityCapabilities,
     SharedString, UDim, UDim2, UniqueId, Vector2, Vector3, Vector3int16,
 };
-use serde::{ser::SerializeSeq, Serialize, Serializer};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, Visitor},
+    ser::SerializeSeq,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 use crate::{chunk::Chunk, core::RbxReadExt, deserializer::FileHeader, types::Type};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DecodedModel {
     pub num_types: u32,
     pub num_instances: u32,
@@ -729,7 +744,7 @@ impl DecodedValues {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum DecodedPropType {
     Known(Type),
@@ -738,7 +753,7 @@ pub enum DecodedPropType {
 
 /// Holds a string with the same semantics as Roblox does. It can be UTF-8, but
 /// might not be.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RobloxString {
     String(String),
@@ -754,21 +769,32 @@ impl From<Vec<u8>> for RobloxString {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum DecodedChunk {
     Meta {
         entries: Vec<(String, String)>,
 
-        #[serde(with = "unknown_buffer", skip_serializing_if = "Vec::is_empty")]
+        #[serde(
+            with = "unknown_buffer",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
         remaining: Vec<u8>,
     },
 
     Sstr {
         version: u32,
-        #[serde(serialize_with = "shared_string_serializer")]
+        #[serde(
+            serialize_with = "shared_string_serializer",
+            deserialize_with = "shared_string_deserializer"
+        )]
         entries: Vec<SharedString>,
 
-        #[serde(with = "unknown_buffer", skip_serializing_if = "Vec::is_empty")]
+        #[serde(
+            with = "unknown_buffer",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
         remaining: Vec<u8>,
     },
 
@@ -778,10 +804,15 @@ pub enum DecodedChunk {
         object_format: u8,
         referents: Vec<i32>,
 
-        #[serde(with = "unknown_buffer", skip_serializing_if = "Vec::is_empty")]
+        #[serde(
+            with = "unknown_buffer",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
         remaining: Vec<u8>,
     },
 
+    #[serde(deserialize_with = "deserialize_prop")]
     Prop {
         type_id: u32,
         prop_name: String,
@@ -790,7 +821,11 @@ pub enum DecodedChunk {
         #[serde(skip_serializing_if = "Option::is_none")]
         values: Option<DecodedValues>,
 
-        #[serde(with = "unknown_buffer", skip_serializing_if = "Vec::is_empty")]
+        #[serde(
+            with = "unknown_buffer",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
         remaining: Vec<u8>,
     },
 
@@ -798,7 +833,11 @@ pub enum DecodedChunk {
         version: u8,
         links: Vec<(i32, i32)>,
 
-        #[serde(with = "unknown_buffer", skip_serializing_if = "Vec::is_empty")]
+        #[serde(
+            with = "unknown_buffer",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
         remaining: Vec<u8>,
     },
 
@@ -840,12 +879,161 @@ where
     state.end()
 }
 
+#[derive(Deserialize)]
+struct DeserializedSharedString {
+    #[serde(deserialize_with = "unknown_buffer::deserialize")]
+    data: Vec<u8>,
+}
+
+fn shared_string_deserializer<'de, D>(deserializer: D) -> Result<Vec<SharedString>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = Vec::<DeserializedSharedString>::deserialize(deserializer)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| SharedString::new(entry.data))
+        .collect())
+}
+
+/// The fields of `DecodedChunk::Prop`, in order.
+type PropFields = (u32, String, DecodedPropType, Option<DecodedValues>, Vec<u8>);
+
+/// Deserializes the fields of a `DecodedChunk::Prop`. The type of `values`
+/// can't be determined from the values alone, so `prop_type` must come before
+/// `values`, like it does when serializing.
+fn deserialize_prop<'de, D>(deserializer: D) -> Result<PropFields, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    const FIELDS: &[&str] = &["type_id", "prop_name", "prop_type", "values", "remaining"];
+
+    #[derive(Deserialize)]
+    struct Remaining(#[serde(with = "unknown_buffer")] Vec<u8>);
+
+    struct PropVisitor;
+
+    impl<'de> Visitor<'de> for PropVisitor {
+        type Value = PropFields;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a PROP chunk")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut type_id = None;
+            let mut prop_name = None;
+            let mut prop_type = None;
+            let mut values = None;
+            let mut remaining = Vec::new();
+
+            while let Some(key) = map.next_key::<String>()? {
+                match key.as_str() {
+                    "type_id" => type_id = Some(map.next_value()?),
+                    "prop_name" => prop_name = Some(map.next_value()?),
+                    "prop_type" => prop_type = Some(map.next_value()?),
+                    "values" => {
+                        let value_type = match &prop_type {
+                            Some(DecodedPropType::Known(ty)) => *ty,
+                            Some(DecodedPropType::Unknown(_)) => {
+                                return Err(de::Error::custom(
+                                    "PROP chunks with an unknown type cannot have values",
+                                ))
+                            }
+                            None => {
+                                return Err(de::Error::custom(
+                                    "prop_type must come before values in PROP chunks",
+                                ))
+                            }
+                        };
+
+                        values = Some(map.next_value_seed(ValuesSeed(value_type))?);
+                    }
+                    "remaining" => remaining = map.next_value::<Remaining>()?.0,
+                    _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                }
+            }
+
+            Ok((
+                type_id.ok_or_else(|| de::Error::missing_field("type_id"))?,
+                prop_name.ok_or_else(|| de::Error::missing_field("prop_name"))?,
+                prop_type.ok_or_else(|| de::Error::missing_field("prop_type"))?,
+                values,
+                remaining,
+            ))
+        }
+    }
+
+    deserializer.deserialize_struct("Prop", FIELDS, PropVisitor)
+}
+
+/// Deserializes `DecodedValues` of a known type.
+struct ValuesSeed(Type);
+
+impl<'de> DeserializeSeed<'de> for ValuesSeed {
+    type Value = DecodedValues;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Ok(match self.0 {
+            Type::String => DecodedValues::String(Deserialize::deserialize(deserializer)?),
+            Type::Bool => DecodedValues::Bool(Deserialize::deserialize(deserializer)?),
+            Type::Int32 => DecodedValues::Int32(Deserialize::deserialize(deserializer)?),
+            Type::Float32 => DecodedValues::Float32(Deserialize::deserialize(deserializer)?),
+            Type::Float64 => DecodedValues::Float64(Deserialize::deserialize(deserializer)?),
+            Type::UDim => DecodedValues::UDim(Deserialize::deserialize(deserializer)?),
+            Type::UDim2 => DecodedValues::UDim2(Deserialize::deserialize(deserializer)?),
+            Type::Ray => DecodedValues::Ray(Deserialize::deserialize(deserializer)?),
+            Type::Faces => DecodedValues::Faces(Deserialize::deserialize(deserializer)?),
+            Type::Axes => DecodedValues::Axes(Deserialize::deserialize(deserializer)?),
+            Type::BrickColor => DecodedValues::BrickColor(Deserialize::deserialize(deserializer)?),
+            Type::Color3 => DecodedValues::Color3(Deserialize::deserialize(deserializer)?),
+            Type::Vector2 => DecodedValues::Vector2(Deserialize::deserialize(deserializer)?),
+            Type::Vector3 => DecodedValues::Vector3(Deserialize::deserialize(deserializer)?),
+            Type::CFrame => DecodedValues::CFrame(Deserialize::deserialize(deserializer)?),
+            Type::Enum => DecodedValues::Enum(Deserialize::deserialize(deserializer)?),
+            Type::Ref => DecodedValues::Ref(Deserialize::deserialize(deserializer)?),
+            Type::Vector3int16 => {
+                DecodedValues::Vector3int16(Deserialize::deserialize(deserializer)?)
+            }
+            Type::NumberSequence => {
+                DecodedValues::NumberSequence(Deserialize::deserialize(deserializer)?)
+            }
+            Type::ColorSequence => {
+                DecodedValues::ColorSequence(Deserialize::deserialize(deserializer)?)
+            }
+            Type::NumberRange => {
+                DecodedValues::NumberRange(Deserialize::deserialize(deserializer)?)
+            }
+            Type::Rect => DecodedValues::Rect(Deserialize::deserialize(deserializer)?),
+            Type::PhysicalProperties => {
+                DecodedValues::PhysicalProperties(Deserialize::deserialize(deserializer)?)
+            }
+            Type::Color3uint8 => {
+                DecodedValues::Color3uint8(Deserialize::deserialize(deserializer)?)
+            }
+            Type::Int64 => DecodedValues::Int64(Deserialize::deserialize(deserializer)?),
+            Type::SharedString => {
+                DecodedValues::SharedString(Deserialize::deserialize(deserializer)?)
+            }
+            Type::OptionalCFrame => {
+                DecodedValues::OptionalCFrame(Deserialize::deserialize(deserializer)?)
+            }
+            Type::UniqueId => DecodedValues::UniqueId(Deserialize::deserialize(deserializer)?),
+            Type::Font => DecodedValues::Font(Deserialize::deserialize(deserializer)?),
+            Type::SecurityCapabilities => {
+                DecodedValues::SecurityCapabilities(Deserialize::deserialize(deserializer)?)
+            }
+        })
+    }
+}
+
 /// Contains data that we haven't decoded for a chunk. Using `unknown_buffer`
 /// should generally be a placeholder since it's results are opaque, but stable.
 mod unknown_buffer {
-    use std::fmt;
+    use std::{borrow::Cow, fmt};
 
-    use serde::Serializer;
+    use serde::{de, Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -854,6 +1042,18 @@ mod unknown_buffer {
         serializer.collect_str(&SliceBytes(value))
     }
 
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Cow::<str>::deserialize(deserializer)?;
+
+        value
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(de::Error::custom))
+            .collect()
+    }
+
     struct SliceBytes<'a>(&'a [u8]);
 
     impl fmt::Display for SliceBytes<'_> {
@@ -0,0 +1,382 @@
+//! Serializer that turns the debug representation created by the text
+//! deserializer back into a binary file.
+//!
+//! No validation is done on the chunks being written, so this can be used to
+//! create files that rbx_binary would never write itself, like files with
+//! missing chunks or mismatched instance counts.
+
+use std::io::{self, Write};
+
+use rbx_dom_weak::types::{CFrame, Matrix3, PhysicalProperties, Vector3};
+
+use crate::{
+    chunk::{ChunkBuilder, ChunkCompression},
+    core::{RbxWriteExt, FILE_FOOTER, FILE_MAGIC_HEADER, FILE_SIGNATURE, FILE_VERSION},
+    text_deserializer::{DecodedChunk, DecodedModel, DecodedPropType, DecodedValues, RobloxString},
+    types::Type,
+};
+
+impl DecodedModel {
+    /// Writes this model out as a binary file. Chunks are written in order,
+    /// exactly as they're described.
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(FILE_MAGIC_HEADER)?;
+        writer.write_all(FILE_SIGNATURE)?;
+        writer.write_le_u16(FILE_VERSION)?;
+        writer.write_le_u32(self.num_types)?;
+        writer.write_le_u32(self.num_instances)?;
+        writer.write_all(&[0; 8])?;
+
+        for chunk in &self.chunks {
+            encode_chunk(chunk)?.dump(&mut writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_chunk(chunk: &DecodedChunk) -> io::Result<ChunkBuilder> {
+    Ok(match chunk {
+        DecodedChunk::Meta { entries, remaining } => {
+            let mut builder = ChunkBuilder::new(b"META", ChunkCompression::Uncompressed);
+
+            builder.write_le_u32(entries.len() as u32)?;
+            for (key, value) in entries {
+                builder.write_string(key)?;
+                builder.write_string(value)?;
+            }
+
+            builder.write_all(remaining)?;
+            builder
+        }
+        DecodedChunk::Sstr {
+            version,
+            entries,
+            remaining,
+        } => {
+            let mut builder = ChunkBuilder::new(b"SSTR", ChunkCompression::Compressed);
+
+            builder.write_le_u32(*version)?;
+            builder.write_le_u32(entries.len() as u32)?;
+            for entry in entries {
+                builder.write_all(&[0; 16])?;
+                builder.write_binary_string(entry.data())?;
+            }
+
+            builder.write_all(remaining)?;
+            builder
+        }
+        DecodedChunk::Inst {
+            type_id,
+            type_name,
+            object_format,
+            referents,
+            remaining,
+        } => {
+            let mut builder = ChunkBuilder::new(b"INST", ChunkCompression::Compressed);
+
+            builder.write_le_u32(*type_id)?;
+            builder.write_string(type_name)?;
+            builder.write_u8(*object_format)?;
+            builder.write_le_u32(referents.len() as u32)?;
+            builder.write_referent_array(referents.iter().copied())?;
+
+            builder.write_all(remaining)?;
+            builder
+        }
+        DecodedChunk::Prop {
+            type_id,
+            prop_name,
+            prop_type,
+            values,
+            remaining,
+        } => {
+            let mut builder = ChunkBuilder::new(b"PROP", ChunkCompression::Compressed);
+
+            builder.write_le_u32(*type_id)?;
+            builder.write_string(prop_name)?;
+            builder.write_u8(match prop_type {
+                DecodedPropType::Known(ty) => *ty as u8,
+                DecodedPropType::Unknown(byte) => *byte,
+            })?;
+
+            if let Some(values) = values {
+                encode_values(&mut builder, values)?;
+            }
+
+            builder.write_all(remaining)?;
+            builder
+        }
+        DecodedChunk::Prnt {
+            version,
+            links,
+            remaining,
+        } => {
+            let mut builder = ChunkBuilder::new(b"PRNT", ChunkCompression::Compressed);
+
+            builder.write_u8(*version)?;
+            builder.write_le_u32(links.len() as u32)?;
+            builder.write_referent_array(links.iter().map(|&(subject, _)| subject))?;
+            builder.write_referent_array(links.iter().map(|&(_, parent)| parent))?;
+
+            builder.write_all(remaining)?;
+            builder
+        }
+        DecodedChunk::End => {
+            let mut builder = ChunkBuilder::new(b"END\0", ChunkCompression::Uncompressed);
+            builder.write_all(FILE_FOOTER)?;
+            builder
+        }
+        DecodedChunk::Unknown { name, contents } => {
+            let mut chunk_name = [0; 4];
+            for (byte, name_byte) in chunk_name.iter_mut().zip(name.bytes()) {
+                *byte = name_byte;
+            }
+
+            let mut builder = ChunkBuilder::new(&chunk_name, ChunkCompression::Uncompressed);
+            builder.write_all(contents)?;
+            builder
+        }
+    })
+}
+
+fn encode_values<W: Write>(writer: &mut W, values: &DecodedValues) -> io::Result<()> {
+    match values {
+        DecodedValues::String(values) => {
+            for value in values {
+                match value {
+                    RobloxString::String(value) => writer.write_string(value)?,
+                    RobloxString::BinaryString(value) => writer.write_binary_string(value)?,
+                }
+            }
+        }
+        DecodedValues::Bool(values) => {
+            for &value in values {
+                writer.write_bool(value)?;
+            }
+        }
+        DecodedValues::Int32(values) => {
+            writer.write_interleaved_i32_array(values.iter().copied())?;
+        }
+        DecodedValues::Float32(values) => {
+            writer.write_interleaved_f32_array(values.iter().copied())?;
+        }
+        DecodedValues::Float64(values) => {
+            for &value in values {
+                writer.write_le_f64(value)?;
+            }
+        }
+        DecodedValues::UDim(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.scale))?;
+            writer.write_interleaved_i32_array(values.iter().map(|value| value.offset))?;
+        }
+        DecodedValues::UDim2(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.x.scale))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.y.scale))?;
+            writer.write_interleaved_i32_array(values.iter().map(|value| value.x.offset))?;
+            writer.write_interleaved_i32_array(values.iter().map(|value| value.y.offset))?;
+        }
+        DecodedValues::Ray(values) => {
+            for value in values {
+                writer.write_le_f32(value.origin.x)?;
+                writer.write_le_f32(value.origin.y)?;
+                writer.write_le_f32(value.origin.z)?;
+                writer.write_le_f32(value.direction.x)?;
+                writer.write_le_f32(value.direction.y)?;
+                writer.write_le_f32(value.direction.z)?;
+            }
+        }
+        DecodedValues::Faces(values) => {
+            for value in values {
+                writer.write_u8(value.bits())?;
+            }
+        }
+        DecodedValues::Axes(values) => {
+            for value in values {
+                writer.write_u8(value.bits())?;
+            }
+        }
+        DecodedValues::BrickColor(values) => {
+            let numbers: Vec<u32> = values.iter().map(|&value| value as u32).collect();
+            writer.write_interleaved_u32_array(&numbers)?;
+        }
+        DecodedValues::Color3(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.r))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.g))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.b))?;
+        }
+        DecodedValues::Vector2(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.x))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.y))?;
+        }
+        DecodedValues::Vector3(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.x))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.y))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.z))?;
+        }
+        DecodedValues::CFrame(values) => {
+            for value in values {
+                encode_rotation(writer, &value.orientation)?;
+            }
+
+            encode_positions(writer, values.iter())?;
+        }
+        DecodedValues::Enum(values) => {
+            let numbers: Vec<u32> = values.iter().map(|value| value.to_u32()).collect();
+            writer.write_interleaved_u32_array(&numbers)?;
+        }
+        DecodedValues::Ref(values) => {
+            writer.write_referent_array(values.iter().copied())?;
+        }
+        DecodedValues::Vector3int16(values) => {
+            for value in values {
+                writer.write_le_i16(value.x)?;
+                writer.write_le_i16(value.y)?;
+                writer.write_le_i16(value.z)?;
+            }
+        }
+        DecodedValues::NumberSequence(values) => {
+            for value in values {
+                writer.write_le_u32(value.keypoints.len() as u32)?;
+
+                for keypoint in &value.keypoints {
+                    writer.write_le_f32(keypoint.time)?;
+                    writer.write_le_f32(keypoint.value)?;
+                    writer.write_le_f32(keypoint.envelope)?;
+                }
+            }
+        }
+        DecodedValues::ColorSequence(values) => {
+            for value in values {
+                writer.write_le_u32(value.keypoints.len() as u32)?;
+
+                for keypoint in &value.keypoints {
+                    writer.write_le_f32(keypoint.time)?;
+                    writer.write_le_f32(keypoint.color.r)?;
+                    writer.write_le_f32(keypoint.color.g)?;
+                    writer.write_le_f32(keypoint.color.b)?;
+
+                    // The envelope isn't part of the debug representation.
+                    writer.write_le_f32(0.0)?;
+                }
+            }
+        }
+        DecodedValues::NumberRange(values) => {
+            for value in values {
+                writer.write_le_f32(value.min)?;
+                writer.write_le_f32(value.max)?;
+            }
+        }
+        DecodedValues::Rect(values) => {
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.min.x))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.min.y))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.max.x))?;
+            writer.write_interleaved_f32_array(values.iter().map(|value| value.max.y))?;
+        }
+        DecodedValues::PhysicalProperties(values) => {
+            for value in values {
+                match value {
+                    PhysicalProperties::Custom(props) => {
+                        writer.write_u8(1)?;
+                        writer.write_le_f32(props.density)?;
+                        writer.write_le_f32(props.friction)?;
+                        writer.write_le_f32(props.elasticity)?;
+                        writer.write_le_f32(props.friction_weight)?;
+                        writer.write_le_f32(props.elasticity_weight)?;
+                    }
+                    PhysicalProperties::Default => writer.write_u8(0)?,
+                }
+            }
+        }
+        DecodedValues::Color3uint8(values) => {
+            let r: Vec<u8> = values.iter().map(|value| value.r).collect();
+            let g: Vec<u8> = values.iter().map(|value| value.g).collect();
+            let b: Vec<u8> = values.iter().map(|value| value.b).collect();
+
+            writer.write_all(&r)?;
+            writer.write_all(&g)?;
+            writer.write_all(&b)?;
+        }
+        DecodedValues::Int64(values) => {
+            writer.write_interleaved_i64_array(values.iter().copied())?;
+        }
+        DecodedValues::SharedString(values) => {
+            writer.write_interleaved_u32_array(values)?;
+        }
+        DecodedValues::OptionalCFrame(values) => {
+            let identity = CFrame::new(Vector3::new(0.0, 0.0, 0.0), Matrix3::identity());
+            let cframes: Vec<&CFrame> = values
+                .iter()
+                .map(|value| value.as_ref().unwrap_or(&identity))
+                .collect();
+
+            writer.write_u8(Type::CFrame as u8)?;
+
+            for value in &cframes {
+                encode_rotation(writer, &value.orientation)?;
+            }
+
+            encode_positions(writer, cframes.into_iter())?;
+
+            writer.write_u8(Type::Bool as u8)?;
+            for value in values {
+                writer.write_bool(value.is_some())?;
+            }
+        }
+        DecodedValues::UniqueId(values) => {
+            let blobs: Vec<[u8; 16]> = values
+                .iter()
+                .map(|value| {
+                    let mut blob = [0; 16];
+                    blob[0..4].copy_from_slice(&value.index().to_be_bytes());
+                    blob[4..8].copy_from_slice(&value.time().to_be_bytes());
+                    blob[8..].copy_from_slice(&value.random().rotate_left(1).to_be_bytes());
+                    blob
+                })
+                .collect();
+
+            writer.write_interleaved_bytes::<16>(&blobs)?;
+        }
+        DecodedValues::Font(values) => {
+            for value in values {
+                writer.write_string(&value.family)?;
+                writer.write_le_u16(value.weight.as_u16())?;
+                writer.write_u8(value.style.as_u8())?;
+                writer.write_string(value.cached_face_id.as_deref().unwrap_or_default())?;
+            }
+        }
+        DecodedValues::SecurityCapabilities(values) => {
+            writer.write_interleaved_i64_array(values.iter().map(|value| value.bits() as i64))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_rotation<W: Write>(writer: &mut W, matrix: &Matrix3) -> io::Result<()> {
+    if let Some(id) = matrix.to_basic_rotation_id() {
+        writer.write_u8(id)?;
+    } else {
+        writer.write_u8(0x00)?;
+
+        for row in [matrix.x, matrix.y, matrix.z] {
+            writer.write_le_f32(row.x)?;
+            writer.write_le_f32(row.y)?;
+            writer.write_le_f32(row.z)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_positions<'a, W, I>(writer: &mut W, values: I) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = &'a CFrame> + Clone,
+{
+    writer.write_interleaved_f32_array(values.clone().map(|value| value.position.x))?;
+    writer.write_interleaved_f32_array(values.clone().map(|value| value.position.y))?;
+    writer.write_interleaved_f32_array(values.map(|value| value.position.z))?;
+
+    Ok(())
+}
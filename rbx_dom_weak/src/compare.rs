@@ -0,0 +1,614 @@
+//! Structural comparison and hashing of subtrees, which may be in different
+//! [`WeakDom`] objects.
+//!
+//! Referents are different in every DOM, so instances are identified by their
+//! position in their subtree instead. `Ref` properties that point at an
+//! instance inside of the subtree are compared by that position, while `Ref`
+//! properties that point outside of it are compared as-is.
+//!
+//! `UniqueId` properties are ignored, since they're different for every
+//! instance by design.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, iter,
+};
+
+use rbx_types::{Ref, Variant};
+use serde::{
+    ser::{self, Serialize},
+    Serializer,
+};
+
+use crate::{Instance, WeakDom};
+
+/// Returns whether the subtree rooted at `ref_a` in `dom_a` is structurally
+/// equal to the subtree rooted at `ref_b` in `dom_b`.
+///
+/// Two subtrees are equal when their instances have the same classes, names,
+/// and properties, and their children are equal in the same order.
+///
+/// ## Panics
+/// Panics if `ref_a` does not refer to an instance in `dom_a` or `ref_b` does
+/// not refer to an instance in `dom_b`.
+pub fn subtree_eq(dom_a: &WeakDom, ref_a: Ref, dom_b: &WeakDom, ref_b: Ref) -> bool {
+    let a = Subtree::new(dom_a, ref_a);
+    let b = Subtree::new(dom_b, ref_b);
+
+    a.instances.len() == b.instances.len()
+        && a.instances
+            .iter()
+            .zip(&b.instances)
+            .all(|(instance_a, instance_b)| instance_eq(&a, instance_a, &b, instance_b))
+}
+
+/// Computes a hash of the subtree rooted at `referent` that is the same for
+/// every subtree it's equal to according to [`subtree_eq`].
+///
+/// The hash is stable across runs and platforms, which makes it suitable for
+/// finding identical models in a collection of files. It may change between
+/// versions of rbx_dom_weak.
+///
+/// ## Panics
+/// Panics if `referent` does not refer to an instance in `dom`.
+pub fn subtree_hash(dom: &WeakDom, referent: Ref) -> u64 {
+    let subtree = Subtree::new(dom, referent);
+    let mut hasher = StableHasher::new();
+
+    for instance in &subtree.instances {
+        hasher.write_str(&instance.class);
+        hasher.write_str(&instance.name);
+        hasher.write_u64(instance.children().len() as u64);
+
+        let properties: BTreeMap<&str, &Variant> = compared_properties(instance).collect();
+        hasher.write_u64(properties.len() as u64);
+
+        for (name, value) in properties {
+            hasher.write_str(name);
+
+            match value {
+                Variant::Ref(value) => match subtree.ref_key(*value) {
+                    RefKey::None => hasher.write_bytes(&[0]),
+                    RefKey::Internal(position) => {
+                        hasher.write_bytes(&[1]);
+                        hasher.write_u64(position as u64);
+                    }
+                    // Refs outside the subtree are equal only if they're the
+                    // same referent, which isn't stable across runs.
+                    RefKey::External(_) => hasher.write_bytes(&[2]),
+                },
+                // SharedStrings refuse to be serialized as part of a Variant.
+                Variant::SharedString(value) => {
+                    hasher.write_bytes(&[3]);
+                    hasher.write_u64(value.data().len() as u64);
+                    hasher.write_bytes(value.data());
+                }
+                _ => {
+                    hasher.write_bytes(&[4]);
+
+                    // Equal values fail in the same place, so a value that
+                    // can't be serialized still hashes consistently.
+                    let _ = value.serialize(&mut hasher);
+                }
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+/// The instances of a subtree in depth-first order, along with the position of
+/// each one in that order.
+struct Subtree<'a> {
+    instances: Vec<&'a Instance>,
+    positions: HashMap<Ref, usize>,
+}
+
+#[derive(PartialEq)]
+enum RefKey {
+    None,
+    Internal(usize),
+    External(Ref),
+}
+
+impl<'a> Subtree<'a> {
+    fn new(dom: &'a WeakDom, referent: Ref) -> Self {
+        let root = dom
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot compare an instance that does not exist"));
+
+        let instances: Vec<&Instance> = iter::once(root)
+            .chain(dom.descendants_dfs(referent))
+            .collect();
+
+        let positions = instances
+            .iter()
+            .enumerate()
+            .map(|(position, instance)| (instance.referent(), position))
+            .collect();
+
+        Self {
+            instances,
+            positions,
+        }
+    }
+
+    fn ref_key(&self, value: Ref) -> RefKey {
+        if value.is_none() {
+            RefKey::None
+        } else if let Some(&position) = self.positions.get(&value) {
+            RefKey::Internal(position)
+        } else {
+            RefKey::External(value)
+        }
+    }
+}
+
+fn instance_eq(a: &Subtree, instance_a: &Instance, b: &Subtree, instance_b: &Instance) -> bool {
+    if instance_a.class != instance_b.class
+        || instance_a.name != instance_b.name
+        || instance_a.children().len() != instance_b.children().len()
+        || compared_properties(instance_a).count() != compared_properties(instance_b).count()
+    {
+        return false;
+    }
+
+    compared_properties(instance_a).all(|(name, value_a)| {
+        match (value_a, instance_b.properties.get(name)) {
+            (Variant::Ref(value_a), Some(Variant::Ref(value_b))) => {
+                a.ref_key(*value_a) == b.ref_key(*value_b)
+            }
+            (value_a, Some(value_b)) => value_a == value_b,
+            (_, None) => false,
+        }
+    })
+}
+
+fn compared_properties(instance: &Instance) -> impl Iterator<Item = (&str, &Variant)> {
+    instance
+        .properties
+        .iter()
+        .filter(|(name, _)| name.as_str() != "UniqueId")
+        .map(|(name, value)| (name.as_str(), value))
+}
+
+/// A 64-bit FNV-1a hasher. Unlike the hashers in the standard library, its
+/// output is guaranteed to stay the same between runs and platforms.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Values are hashed by serializing them straight into the hasher. Sequences
+/// and maps mark each element with a 1 and their end with a 0, since their
+/// length isn't always known up front.
+///
+/// Floats are hashed by their bits, except that `-0.0` is hashed as `0.0`
+/// because the two are equal.
+impl Serializer for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, value: bool) -> Result<(), HashError> {
+        self.write_bytes(&[value as u8]);
+        Ok(())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), HashError> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), HashError> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), HashError> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), HashError> {
+        self.write_u64(value as u64);
+        Ok(())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), HashError> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), HashError> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), HashError> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), HashError> {
+        self.write_u64(value);
+        Ok(())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), HashError> {
+        let value = if value == 0.0 { 0.0 } else { value };
+        self.write_bytes(&value.to_bits().to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), HashError> {
+        let value = if value == 0.0 { 0.0 } else { value };
+        self.write_u64(value.to_bits());
+        Ok(())
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), HashError> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), HashError> {
+        self.write_str(value);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), HashError> {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), HashError> {
+        self.write_bytes(&[0]);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), HashError> {
+        self.write_bytes(&[1]);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), HashError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), HashError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), HashError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), HashError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), HashError> {
+        self.write_u64(variant_index.into());
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, HashError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, HashError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, HashError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, HashError> {
+        self.write_u64(variant_index.into());
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, HashError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, HashError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, HashError> {
+        self.write_u64(variant_index.into());
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), HashError> {
+        self.write_bytes(&[1]);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        self.write_bytes(&[0]);
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), HashError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), HashError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), HashError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), HashError> {
+        self.write_bytes(&[1]);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), HashError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        self.write_bytes(&[0]);
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), HashError> {
+        // Fields can be skipped, so they're marked with their names.
+        self.write_str(key);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut StableHasher {
+    type Ok = ();
+    type Error = HashError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), HashError> {
+        self.write_str(key);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Returned when a value refuses to be serialized into a [`StableHasher`].
+#[derive(Debug)]
+struct HashError;
+
+impl fmt::Display for HashError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "value cannot be hashed")
+    }
+}
+
+impl std::error::Error for HashError {}
+
+impl ser::Error for HashError {
+    fn custom<T: fmt::Display>(_message: T) -> Self {
+        HashError
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::UniqueId;
+
+    use crate::InstanceBuilder;
+
+    fn model() -> InstanceBuilder {
+        let part = InstanceBuilder::new("Part")
+            .with_name("Handle")
+            .with_property("Size", rbx_types::Vector3::new(1.0, 2.0, 3.0))
+            .with_property("UniqueId", UniqueId::now().unwrap());
+        let part_ref = part.referent();
+
+        InstanceBuilder::new("Model")
+            .with_name("Sword")
+            .with_property("PrimaryPart", part_ref)
+            .with_child(part)
+            .with_child(InstanceBuilder::new("Script"))
+    }
+
+    #[test]
+    fn equal_across_doms() {
+        let dom_a = WeakDom::new(InstanceBuilder::new("Folder").with_child(model()));
+        let dom_b = WeakDom::new(model());
+
+        let ref_a = dom_a.root().children()[0];
+        let ref_b = dom_b.root_ref();
+
+        assert!(subtree_eq(&dom_a, ref_a, &dom_b, ref_b));
+        assert_eq!(subtree_hash(&dom_a, ref_a), subtree_hash(&dom_b, ref_b));
+        assert!(!subtree_eq(
+            &dom_a,
+            dom_a.root_ref(),
+            &dom_b,
+            dom_b.root_ref()
+        ));
+    }
+
+    #[test]
+    fn clones_are_equal() {
+        let mut dom = WeakDom::new(model());
+        let root = dom.root_ref();
+        let cloned = dom.clone_within(root);
+
+        assert!(subtree_eq(&dom, root, &dom, cloned));
+        assert_eq!(subtree_hash(&dom, root), subtree_hash(&dom, cloned));
+    }
+
+    #[test]
+    fn differences() {
+        let dom = WeakDom::new(model());
+        let root = dom.root_ref();
+        let hash = subtree_hash(&dom, root);
+
+        let mut renamed = dom.clone();
+        let handle = renamed.root().children()[0];
+        renamed.set_name(handle, "Blade");
+        assert!(!subtree_eq(&dom, root, &renamed, root));
+        assert_ne!(subtree_hash(&renamed, root), hash);
+
+        let mut changed = dom.clone();
+        changed.set_property(handle, "Size", rbx_types::Vector3::new(1.0, 2.0, 4.0));
+        assert!(!subtree_eq(&dom, root, &changed, root));
+        assert_ne!(subtree_hash(&changed, root), hash);
+
+        let mut reordered = dom.clone();
+        reordered.transfer_within(handle, root);
+        assert!(!subtree_eq(&dom, root, &reordered, root));
+        assert_ne!(subtree_hash(&reordered, root), hash);
+
+        // PrimaryPart points at the script instead of the handle.
+        let mut retargeted = dom.clone();
+        let script = retargeted.root().children()[1];
+        retargeted.set_property(root, "PrimaryPart", script);
+        assert!(!subtree_eq(&dom, root, &retargeted, root));
+        assert_ne!(subtree_hash(&retargeted, root), hash);
+    }
+
+    #[test]
+    fn equal_values_hash_equally() {
+        let positive = WeakDom::new(
+            InstanceBuilder::new("NumberValue")
+                .with_property("Value", 0.0f32)
+                .with_property("Offset", rbx_types::Vector3::new(0.0, 1.0, 0.0))
+                .with_property("Data", rbx_types::SharedString::new(b"data".to_vec())),
+        );
+        let negative = WeakDom::new(
+            InstanceBuilder::new("NumberValue")
+                .with_property("Value", -0.0f32)
+                .with_property("Offset", rbx_types::Vector3::new(-0.0, 1.0, -0.0))
+                .with_property("Data", rbx_types::SharedString::new(b"data".to_vec())),
+        );
+
+        let (a, b) = (positive.root_ref(), negative.root_ref());
+        assert!(subtree_eq(&positive, a, &negative, b));
+        assert_eq!(subtree_hash(&positive, a), subtree_hash(&negative, b));
+
+        let mut changed = negative.clone();
+        changed.set_property(b, "Data", rbx_types::SharedString::new(b"other".to_vec()));
+        assert!(!subtree_eq(&positive, a, &changed, b));
+        assert_ne!(subtree_hash(&positive, a), subtree_hash(&changed, b));
+    }
+}
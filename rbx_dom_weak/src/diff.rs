@@ -0,0 +1,1186 @@
+//! Structural diffing between two [`WeakDom`] objects.
+//!
+//! Instances in the two DOMs are matched up with each other before they are
+//! compared. The roots of both DOMs always match. After that, instances with
+//! the same `UniqueId` property are matched, followed by instances that have
+//! the same name and class under parents that have already been matched.
+//! Finally, any instance whose name and class are unique among the remaining
+//! instances in both DOMs is matched, which allows instances that have moved
+//! to a new parent to be detected.
+//!
+//! The resulting [`DomPatch`] can be applied to the old DOM with
+//! [`apply_patch`] to turn it into the new one.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, iter, mem,
+};
+
+use rbx_types::{Ref, Variant};
+use serde::{Deserialize, Serialize};
+
+use crate::{Instance, InstanceBuilder, WeakDom};
+
+/// Computes the differences between two `WeakDom` objects, describing how to
+/// turn `old` into `new`.
+///
+/// Referents in the returned patch, including those inside of `Ref`
+/// properties, refer to instances in `old`. The exception is instances that
+/// only exist in `new`, which keep their referents from `new` unless those
+/// referents are also used in `old`, in which case they're given new ones.
+pub fn diff(old: &WeakDom, new: &WeakDom) -> DomPatch {
+    Matching::new(old, new).patch()
+}
+
+/// Describes the differences between two `WeakDom` objects. Created by
+/// [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomPatch {
+    /// Subtrees that only exist in the new DOM.
+    pub added: Vec<AddedInstance>,
+
+    /// Subtrees that only exist in the old DOM.
+    pub removed: Vec<RemovedInstance>,
+
+    /// Instances that exist in both DOMs, but have a different parent.
+    pub moved: Vec<MovedInstance>,
+
+    /// Instances that exist in both DOMs, but have different names, classes,
+    /// or properties.
+    pub changed: Vec<ChangedInstance>,
+}
+
+impl DomPatch {
+    /// Returns whether the patch contains no changes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// A subtree that was added to the DOM.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddedInstance {
+    /// The referent of the instance that the subtree was added to.
+    pub parent: Ref,
+
+    /// The root of the added subtree.
+    pub instance: InstanceSnapshot,
+}
+
+/// A copy of an instance and any of its descendants that were added along
+/// with it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceSnapshot {
+    /// The referent of the instance in the new DOM.
+    pub referent: Ref,
+
+    /// The instance's name.
+    pub name: String,
+
+    /// The instance's class.
+    pub class: String,
+
+    /// The instance's properties.
+    pub properties: BTreeMap<String, Variant>,
+
+    /// The instance's children that were added along with it. Children that
+    /// existed before are described by [`MovedInstance`] instead.
+    pub children: Vec<InstanceSnapshot>,
+}
+
+// Snapshots can be arbitrarily deep, so cloning, comparing, and dropping them
+// is done without recursing.
+impl Clone for InstanceSnapshot {
+    fn clone(&self) -> Self {
+        build_tree(
+            self,
+            |snapshot| &snapshot.children,
+            |snapshot, children| InstanceSnapshot {
+                referent: snapshot.referent,
+                name: snapshot.name.clone(),
+                class: snapshot.class.clone(),
+                properties: snapshot.properties.clone(),
+                children,
+            },
+        )
+    }
+}
+
+impl PartialEq for InstanceSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+
+        while let Some((a, b)) = stack.pop() {
+            if a.referent != b.referent
+                || a.name != b.name
+                || a.class != b.class
+                || a.properties != b.properties
+                || a.children.len() != b.children.len()
+            {
+                return false;
+            }
+
+            stack.extend(a.children.iter().zip(&b.children));
+        }
+
+        true
+    }
+}
+
+impl Drop for InstanceSnapshot {
+    fn drop(&mut self) {
+        let mut stack = mem::take(&mut self.children);
+
+        while let Some(mut child) = stack.pop() {
+            stack.append(&mut child.children);
+        }
+    }
+}
+
+/// A subtree that was removed from the DOM.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedInstance {
+    /// The referent of the root of the removed subtree.
+    pub referent: Ref,
+
+    /// The name of the removed instance.
+    pub name: String,
+
+    /// The class of the removed instance.
+    pub class: String,
+}
+
+/// An instance that was moved to a new parent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedInstance {
+    /// The referent of the moved instance.
+    pub referent: Ref,
+
+    /// The referent of the instance's parent in the old DOM.
+    pub old_parent: Ref,
+
+    /// The referent of the instance's parent in the new DOM. This may refer to
+    /// an instance described by [`DomPatch::added`].
+    pub new_parent: Ref,
+}
+
+/// An instance whose name, class, or properties changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedInstance {
+    /// The referent of the changed instance.
+    pub referent: Ref,
+
+    /// Each of the changes made to the instance, sorted by property name.
+    pub changes: Vec<PropertyChange>,
+}
+
+/// A change to a single property of an instance.
+///
+/// Changes to an instance's name and class are described with the property
+/// names `Name` and `ClassName`, respectively.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyChange {
+    /// The name of the property.
+    pub name: String,
+
+    /// The value of the property in the old DOM, if it was set.
+    pub old: Option<Variant>,
+
+    /// The value of the property in the new DOM, if it is set.
+    pub new: Option<Variant>,
+}
+
+/// Applies a patch created by [`diff`] to a `WeakDom`.
+///
+/// The patch is expected to have been created with `dom` (or a DOM with the
+/// same referents, like a clone of it) as the old DOM. Added instances keep
+/// the referents they have in the patch, so none of them may already be in
+/// use.
+///
+/// The patch is checked before any changes are made, so if an error is
+/// returned, `dom` is left untouched.
+pub fn apply_patch(dom: &mut WeakDom, patch: &DomPatch) -> Result<(), PatchError> {
+    validate_patch(dom, patch)?;
+
+    for added in &patch.added {
+        dom.insert(added.parent, snapshot_to_builder(&added.instance));
+    }
+
+    for moved in &patch.moved {
+        dom.transfer_within(moved.referent, moved.new_parent);
+    }
+
+    for changed in &patch.changed {
+        let referent = changed.referent;
+
+        for change in &changed.changes {
+            match (change.name.as_str(), &change.new) {
+                ("Name", Some(Variant::String(name))) => dom.set_name(referent, name.as_str()),
+                ("ClassName", Some(Variant::String(class))) => {
+                    dom.set_class(referent, class.as_str())
+                }
+                (name, Some(value)) => {
+                    dom.set_property(referent, name, value.clone());
+                }
+                (name, None) => {
+                    dom.remove_property(referent, name);
+                }
+            }
+        }
+    }
+
+    for removed in &patch.removed {
+        // Removed subtrees can be nested inside of each other, in which case
+        // the inner subtree is already gone by the time we get to it.
+        if dom.get_by_ref(removed.referent).is_some() {
+            dom.destroy(removed.referent);
+        }
+    }
+
+    Ok(())
+}
+
+/// An error that can occur when applying a [`DomPatch`] to a `WeakDom`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchError {
+    /// The patch refers to an instance that isn't in the DOM and isn't added by
+    /// the patch.
+    MissingInstance(Ref),
+
+    /// The patch would make the given instance a descendant of itself.
+    Cycle(Ref),
+
+    /// The patch would move or remove the root instance of the DOM.
+    RootInstance,
+
+    /// The patch adds an instance whose referent is nil, is already in the
+    /// DOM, or is used by another added instance.
+    InvalidReferent(Ref),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::MissingInstance(referent) => {
+                write!(formatter, "patch refers to missing instance {}", referent)
+            }
+            PatchError::Cycle(referent) => write!(
+                formatter,
+                "patch would make instance {} a descendant of itself",
+                referent
+            ),
+            PatchError::RootInstance => {
+                write!(formatter, "patch would move or remove the root instance")
+            }
+            PatchError::InvalidReferent(referent) => write!(
+                formatter,
+                "patch adds an instance with nil, existing, or duplicate referent {}",
+                referent
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Records the parent of each instance in an added subtree, making sure that
+/// every referent in it is new.
+fn collect_added(
+    dom: &WeakDom,
+    parent: Ref,
+    snapshot: &InstanceSnapshot,
+    added_parents: &mut HashMap<Ref, Ref>,
+) -> Result<(), PatchError> {
+    let mut stack = vec![(parent, snapshot)];
+
+    while let Some((parent, snapshot)) = stack.pop() {
+        let referent = snapshot.referent;
+
+        if referent.is_none()
+            || dom.get_by_ref(referent).is_some()
+            || added_parents.insert(referent, parent).is_some()
+        {
+            return Err(PatchError::InvalidReferent(referent));
+        }
+
+        stack.extend(snapshot.children.iter().map(|child| (referent, child)));
+    }
+
+    Ok(())
+}
+
+fn validate_patch(dom: &WeakDom, patch: &DomPatch) -> Result<(), PatchError> {
+    let mut added_parents = HashMap::new();
+
+    for added in &patch.added {
+        collect_added(dom, added.parent, &added.instance, &mut added_parents)?;
+    }
+
+    let exists = |referent: Ref| dom.get_by_ref(referent).is_some();
+    let known = |referent: Ref| exists(referent) || added_parents.contains_key(&referent);
+    let require = |check: bool, referent: Ref| {
+        if check {
+            Ok(())
+        } else {
+            Err(PatchError::MissingInstance(referent))
+        }
+    };
+
+    for added in &patch.added {
+        require(exists(added.parent), added.parent)?;
+    }
+
+    for changed in &patch.changed {
+        require(known(changed.referent), changed.referent)?;
+    }
+
+    for removed in &patch.removed {
+        if removed.referent == dom.root_ref() {
+            return Err(PatchError::RootInstance);
+        }
+
+        require(exists(removed.referent), removed.referent)?;
+    }
+
+    let mut new_parents = HashMap::new();
+
+    for moved in &patch.moved {
+        if moved.referent == dom.root_ref() {
+            return Err(PatchError::RootInstance);
+        }
+
+        require(exists(moved.referent), moved.referent)?;
+        require(known(moved.new_parent), moved.new_parent)?;
+        new_parents.insert(moved.referent, moved.new_parent);
+    }
+
+    // Walk up from each instance's new parent to make sure that we never end
+    // up back at the instance.
+    for moved in &patch.moved {
+        let mut visited = HashSet::new();
+        let mut current = moved.new_parent;
+
+        while current.is_some() {
+            if current == moved.referent || !visited.insert(current) {
+                return Err(PatchError::Cycle(moved.referent));
+            }
+
+            current = match new_parents.get(&current) {
+                Some(&parent) => parent,
+                None => match added_parents.get(&current) {
+                    Some(&parent) => parent,
+                    None => dom.get_by_ref(current).unwrap().parent(),
+                },
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates an `InstanceBuilder` from a snapshot that keeps the snapshot's
+/// referents.
+pub(crate) fn snapshot_to_builder(snapshot: &InstanceSnapshot) -> InstanceBuilder {
+    build_tree(
+        snapshot,
+        |snapshot| &snapshot.children,
+        |snapshot, children| {
+            let mut builder = InstanceBuilder::new(snapshot.class.as_str())
+                .with_name(snapshot.name.as_str())
+                .with_properties(
+                    snapshot
+                        .properties
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.clone())),
+                )
+                .with_children(children);
+
+            builder.referent = snapshot.referent;
+            builder
+        },
+    )
+}
+
+/// Creates a snapshot of the instance with the given referent and all of its
+/// descendants.
+pub(crate) fn capture_snapshot(dom: &WeakDom, referent: Ref) -> InstanceSnapshot {
+    build_tree(
+        dom.get_by_ref(referent).unwrap(),
+        |instance| {
+            instance
+                .children()
+                .iter()
+                .map(|child| dom.get_by_ref(*child).unwrap())
+        },
+        |instance, children| InstanceSnapshot {
+            referent: instance.referent(),
+            name: instance.name.clone(),
+            class: instance.class.clone(),
+            properties: instance
+                .properties
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            children,
+        },
+    )
+}
+
+/// Converts a tree of nodes into a tree of some other type without recursing,
+/// so that very deep trees don't overflow the stack.
+///
+/// `children_of` returns the children of a node, and `create` turns a node
+/// into its output along with the outputs of its children, in order.
+fn build_tree<N, I, T>(
+    root: N,
+    mut children_of: impl FnMut(N) -> I,
+    mut create: impl FnMut(N, Vec<T>) -> T,
+) -> T
+where
+    N: Copy,
+    I: IntoIterator<Item = N>,
+{
+    // Nodes in breadth-first order, along with the index of their parent.
+    let mut nodes = vec![(root, None)];
+    let mut index = 0;
+
+    while index < nodes.len() {
+        let node = nodes[index].0;
+        nodes.extend(
+            children_of(node)
+                .into_iter()
+                .map(|child| (child, Some(index))),
+        );
+        index += 1;
+    }
+
+    // Every node comes after its parent, so going backwards finishes all of a
+    // node's children before the node itself. They're finished last to first.
+    let mut finished: Vec<Vec<T>> = nodes.iter().map(|_| Vec::new()).collect();
+
+    for (index, (node, parent)) in nodes.into_iter().enumerate().rev() {
+        let mut children = mem::take(&mut finished[index]);
+        children.reverse();
+
+        let output = create(node, children);
+
+        match parent {
+            Some(parent) => finished[parent].push(output),
+            None => return output,
+        }
+    }
+
+    unreachable!("the root node is always finished last")
+}
+
+/// Tracks which instances in the old DOM correspond to which instances in the
+/// new DOM.
+struct Matching<'a> {
+    old: &'a WeakDom,
+    new: &'a WeakDom,
+    old_to_new: HashMap<Ref, Ref>,
+    new_to_old: HashMap<Ref, Ref>,
+
+    /// New referents for unmatched instances in the new DOM whose referents
+    /// are also used in the old DOM, so that applying the patch to the old
+    /// DOM doesn't add an instance that's already there.
+    fresh: HashMap<Ref, Ref>,
+}
+
+impl<'a> Matching<'a> {
+    fn new(old: &'a WeakDom, new: &'a WeakDom) -> Self {
+        let mut matching = Matching {
+            old,
+            new,
+            old_to_new: HashMap::new(),
+            new_to_old: HashMap::new(),
+            fresh: HashMap::new(),
+        };
+
+        matching.pair(old.root_ref(), new.root_ref());
+        matching.match_unique_ids();
+        matching.match_children();
+        matching.match_unique_names();
+
+        // Instances matched by name can bring unmatched children along with
+        // them, so we need another pass to pick those up.
+        matching.match_children();
+
+        for instance in all_instances(new) {
+            let referent = instance.referent();
+
+            if !matching.new_to_old.contains_key(&referent) && old.get_by_ref(referent).is_some() {
+                matching.fresh.insert(referent, Ref::new());
+            }
+        }
+
+        matching
+    }
+
+    fn pair(&mut self, old_ref: Ref, new_ref: Ref) {
+        self.old_to_new.insert(old_ref, new_ref);
+        self.new_to_old.insert(new_ref, old_ref);
+    }
+
+    fn match_unique_ids(&mut self) {
+        let old = self.old;
+        let new = self.new;
+
+        let old_ids: HashMap<_, _> = all_instances(old)
+            .filter_map(|instance| match instance.properties.get("UniqueId") {
+                Some(Variant::UniqueId(id)) => Some((*id, instance.referent())),
+                _ => None,
+            })
+            .collect();
+
+        for instance in all_instances(new) {
+            if let Some(Variant::UniqueId(id)) = instance.properties.get("UniqueId") {
+                if let Some(&old_ref) = old_ids.get(id) {
+                    if !self.old_to_new.contains_key(&old_ref)
+                        && !self.new_to_old.contains_key(&instance.referent())
+                    {
+                        self.pair(old_ref, instance.referent());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Matches unmatched children of matched instances by name and class.
+    /// Duplicates are paired up in the order they appear in their parents.
+    fn match_children(&mut self) {
+        let old = self.old;
+        let new = self.new;
+
+        // Parents are always visited before their children, so instances that
+        // get matched here will have their own children matched later on.
+        for new_instance in all_instances(new) {
+            let old_ref = match self.new_to_old.get(&new_instance.referent()) {
+                Some(&old_ref) => old_ref,
+                None => continue,
+            };
+
+            let mut candidates: Vec<&Instance> = old
+                .get_by_ref(old_ref)
+                .unwrap()
+                .children()
+                .iter()
+                .filter(|child| !self.old_to_new.contains_key(child))
+                .map(|child| old.get_by_ref(*child).unwrap())
+                .collect();
+
+            for new_child_ref in new_instance.children() {
+                if self.new_to_old.contains_key(new_child_ref) {
+                    continue;
+                }
+
+                let new_child = new.get_by_ref(*new_child_ref).unwrap();
+                let position = candidates.iter().position(|candidate| {
+                    candidate.name == new_child.name && candidate.class == new_child.class
+                });
+
+                if let Some(index) = position {
+                    let old_child = candidates.remove(index);
+                    self.pair(old_child.referent(), new_child.referent());
+                }
+            }
+        }
+    }
+
+    /// Matches remaining instances whose name and class are unique among the
+    /// unmatched instances of both DOMs.
+    fn match_unique_names(&mut self) {
+        let old_names = unique_names(self.old, &self.old_to_new);
+        let new_names = unique_names(self.new, &self.new_to_old);
+
+        for (key, new_ref) in new_names {
+            if let (Some(new_ref), Some(Some(old_ref))) = (new_ref, old_names.get(&key)) {
+                self.pair(*old_ref, new_ref);
+            }
+        }
+    }
+
+    /// Translates a referent from the new DOM into the referent of its match
+    /// in the old DOM. Unmatched referents are returned unchanged, unless
+    /// they're also used in the old DOM.
+    fn translate(&self, referent: Ref) -> Ref {
+        self.new_to_old
+            .get(&referent)
+            .or_else(|| self.fresh.get(&referent))
+            .copied()
+            .unwrap_or(referent)
+    }
+
+    fn translate_value(&self, value: &Variant) -> Variant {
+        match value {
+            Variant::Ref(referent) => Variant::Ref(self.translate(*referent)),
+            other => other.clone(),
+        }
+    }
+
+    fn patch(&self) -> DomPatch {
+        let mut patch = DomPatch::default();
+
+        self.push_changes(&mut patch, self.old.root(), self.new.root());
+
+        for new_instance in self.new.descendants(self.new.root_ref()) {
+            let new_parent = self.translate(new_instance.parent());
+
+            match self.new_to_old.get(&new_instance.referent()) {
+                Some(&old_ref) => {
+                    let old_instance = self.old.get_by_ref(old_ref).unwrap();
+
+                    if old_instance.parent() != new_parent {
+                        patch.moved.push(MovedInstance {
+                            referent: old_ref,
+                            old_parent: old_instance.parent(),
+                            new_parent,
+                        });
+                    }
+
+                    self.push_changes(&mut patch, old_instance, new_instance);
+                }
+                None => {
+                    // Unmatched instances with unmatched parents are included
+                    // in the snapshot of their parent.
+                    if self.new_to_old.contains_key(&new_instance.parent()) {
+                        patch.added.push(AddedInstance {
+                            parent: new_parent,
+                            instance: self.snapshot(new_instance),
+                        });
+                    }
+                }
+            }
+        }
+
+        for old_instance in self.old.descendants(self.old.root_ref()) {
+            if !self.old_to_new.contains_key(&old_instance.referent())
+                && self.old_to_new.contains_key(&old_instance.parent())
+            {
+                patch.removed.push(RemovedInstance {
+                    referent: old_instance.referent(),
+                    name: old_instance.name.clone(),
+                    class: old_instance.class.clone(),
+                });
+            }
+        }
+
+        patch
+    }
+
+    fn push_changes(&self, patch: &mut DomPatch, old_instance: &Instance, new_instance: &Instance) {
+        let mut changes = Vec::new();
+
+        if old_instance.class != new_instance.class {
+            changes.push(PropertyChange {
+                name: "ClassName".to_owned(),
+                old: Some(Variant::String(old_instance.class.clone())),
+                new: Some(Variant::String(new_instance.class.clone())),
+            });
+        }
+
+        if old_instance.name != new_instance.name {
+            changes.push(PropertyChange {
+                name: "Name".to_owned(),
+                old: Some(Variant::String(old_instance.name.clone())),
+                new: Some(Variant::String(new_instance.name.clone())),
+            });
+        }
+
+        let names: BTreeSet<&String> = old_instance
+            .properties
+            .keys()
+            .chain(new_instance.properties.keys())
+            .collect();
+
+        for name in names {
+            let old_value = old_instance.properties.get(name);
+            let new_value = new_instance
+                .properties
+                .get(name)
+                .map(|value| self.translate_value(value));
+
+            if old_value != new_value.as_ref() {
+                changes.push(PropertyChange {
+                    name: name.clone(),
+                    old: old_value.cloned(),
+                    new: new_value,
+                });
+            }
+        }
+
+        if !changes.is_empty() {
+            changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+            patch.changed.push(ChangedInstance {
+                referent: old_instance.referent(),
+                changes,
+            });
+        }
+    }
+
+    fn snapshot(&self, instance: &Instance) -> InstanceSnapshot {
+        build_tree(
+            instance,
+            |instance| {
+                instance
+                    .children()
+                    .iter()
+                    .filter(|child| !self.new_to_old.contains_key(child))
+                    .map(|child| self.new.get_by_ref(*child).unwrap())
+            },
+            |instance, children| InstanceSnapshot {
+                referent: self.translate(instance.referent()),
+                name: instance.name.clone(),
+                class: instance.class.clone(),
+                properties: instance
+                    .properties
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.translate_value(value)))
+                    .collect(),
+                children,
+            },
+        )
+    }
+}
+
+/// Returns an iterator over every instance in the DOM, starting with the root.
+fn all_instances(dom: &WeakDom) -> impl Iterator<Item = &Instance> {
+    iter::once(dom.root()).chain(dom.descendants(dom.root_ref()))
+}
+
+/// Groups the unmatched instances in a DOM by name and class. Each group
+/// contains the referent of its instance if it has exactly one, or `None` if
+/// it has several.
+fn unique_names<'a>(
+    dom: &'a WeakDom,
+    matched: &HashMap<Ref, Ref>,
+) -> HashMap<(&'a str, &'a str), Option<Ref>> {
+    let mut names = HashMap::new();
+
+    for instance in all_instances(dom) {
+        if matched.contains_key(&instance.referent()) {
+            continue;
+        }
+
+        names
+            .entry((instance.name.as_str(), instance.class.as_str()))
+            .and_modify(|entry| *entry = None)
+            .or_insert_with(|| Some(instance.referent()));
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::UniqueId;
+
+    use crate::InstanceBuilder;
+
+    fn base() -> InstanceBuilder {
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("Workspace")
+                .with_child(
+                    InstanceBuilder::new("Part")
+                        .with_name("Floor")
+                        .with_property("Anchored", true),
+                )
+                .with_child(
+                    InstanceBuilder::new("Model")
+                        .with_name("House")
+                        .with_child(InstanceBuilder::new("Part").with_name("Door")),
+                ),
+        )
+    }
+
+    #[test]
+    fn identical() {
+        let old = WeakDom::new(base());
+        let new = WeakDom::new(base());
+
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn property_changes() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let floor = new.find_by_path("Workspace.Floor").unwrap();
+        let properties = &mut new.get_by_ref_mut(floor).unwrap().properties;
+        properties.insert("Anchored".to_owned(), Variant::Bool(false));
+        properties.insert("Transparency".to_owned(), Variant::Float32(0.5));
+
+        let patch = diff(&old, &new);
+        assert!(patch.added.is_empty());
+        assert!(patch.removed.is_empty());
+        assert!(patch.moved.is_empty());
+        assert_eq!(
+            patch.changed,
+            [ChangedInstance {
+                referent: old.find_by_path("Workspace.Floor").unwrap(),
+                changes: vec![
+                    PropertyChange {
+                        name: "Anchored".to_owned(),
+                        old: Some(Variant::Bool(true)),
+                        new: Some(Variant::Bool(false)),
+                    },
+                    PropertyChange {
+                        name: "Transparency".to_owned(),
+                        old: None,
+                        new: Some(Variant::Float32(0.5)),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let house = new.find_by_path("Workspace.House").unwrap();
+        new.destroy(house);
+
+        let workspace = new.find_by_path("Workspace").unwrap();
+        let tree = new.insert(
+            workspace,
+            InstanceBuilder::new("Model")
+                .with_name("Tree")
+                .with_child(InstanceBuilder::new("Part").with_name("Trunk")),
+        );
+
+        let patch = diff(&old, &new);
+        assert!(patch.moved.is_empty());
+        assert!(patch.changed.is_empty());
+
+        assert_eq!(
+            patch.removed,
+            [RemovedInstance {
+                referent: old.find_by_path("Workspace.House").unwrap(),
+                name: "House".to_owned(),
+                class: "Model".to_owned(),
+            }]
+        );
+
+        assert_eq!(patch.added.len(), 1);
+        let added = &patch.added[0];
+        assert_eq!(added.parent, old.find_by_path("Workspace").unwrap());
+        assert_eq!(added.instance.referent, tree);
+        assert_eq!(added.instance.name, "Tree");
+        assert_eq!(added.instance.children.len(), 1);
+        assert_eq!(added.instance.children[0].name, "Trunk");
+    }
+
+    #[test]
+    fn moved() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let door = new.find_by_path("Workspace.House.Door").unwrap();
+        let workspace = new.find_by_path("Workspace").unwrap();
+        new.transfer_within(door, workspace);
+
+        let patch = diff(&old, &new);
+        assert!(patch.added.is_empty());
+        assert!(patch.removed.is_empty());
+        assert!(patch.changed.is_empty());
+        assert_eq!(
+            patch.moved,
+            [MovedInstance {
+                referent: old.find_by_path("Workspace.House.Door").unwrap(),
+                old_parent: old.find_by_path("Workspace.House").unwrap(),
+                new_parent: old.find_by_path("Workspace").unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn moved_into_added() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let workspace = new.find_by_path("Workspace").unwrap();
+        let folder = new.insert(workspace, InstanceBuilder::new("Folder"));
+        let floor = new.find_by_path("Workspace.Floor").unwrap();
+        new.transfer_within(floor, folder);
+
+        let patch = diff(&old, &new);
+        assert_eq!(patch.added.len(), 1);
+        assert_eq!(patch.added[0].instance.referent, folder);
+        assert!(patch.added[0].instance.children.is_empty());
+        assert_eq!(
+            patch.moved,
+            [MovedInstance {
+                referent: old.find_by_path("Workspace.Floor").unwrap(),
+                old_parent: old.find_by_path("Workspace").unwrap(),
+                new_parent: folder,
+            }]
+        );
+    }
+
+    #[test]
+    fn renamed_with_unique_id() {
+        let id = UniqueId::now().unwrap();
+        let with_id = |name: &str| {
+            WeakDom::new(
+                InstanceBuilder::new("Folder").with_child(
+                    InstanceBuilder::new("Part")
+                        .with_name(name)
+                        .with_property("UniqueId", id),
+                ),
+            )
+        };
+
+        let old = with_id("Before");
+        let new = with_id("After");
+
+        let patch = diff(&old, &new);
+        assert!(patch.added.is_empty());
+        assert!(patch.removed.is_empty());
+        assert_eq!(
+            patch.changed,
+            [ChangedInstance {
+                referent: old.root().children()[0],
+                changes: vec![PropertyChange {
+                    name: "Name".to_owned(),
+                    old: Some(Variant::String("Before".to_owned())),
+                    new: Some(Variant::String("After".to_owned())),
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let old = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part").with_property("Index", 1))
+                .with_child(InstanceBuilder::new("Part").with_property("Index", 2)),
+        );
+        let mut new = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part").with_property("Index", 1))
+                .with_child(InstanceBuilder::new("Part").with_property("Index", 2)),
+        );
+
+        let second = new.root().children()[1];
+        new.destroy(second);
+
+        let patch = diff(&old, &new);
+        assert!(patch.changed.is_empty());
+        assert_eq!(patch.removed.len(), 1);
+        assert_eq!(patch.removed[0].referent, old.root().children()[1]);
+    }
+
+    #[test]
+    fn refs_are_translated() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let floor = new.find_by_path("Workspace.Floor").unwrap();
+        let door = new.find_by_path("Workspace.House.Door").unwrap();
+        new.get_by_ref_mut(floor)
+            .unwrap()
+            .properties
+            .insert("Target".to_owned(), Variant::Ref(door));
+
+        let patch = diff(&old, &new);
+        assert_eq!(patch.changed.len(), 1);
+        assert_eq!(
+            patch.changed[0].changes[0].new,
+            Some(Variant::Ref(
+                old.find_by_path("Workspace.House.Door").unwrap()
+            ))
+        );
+    }
+
+    fn assert_round_trip(old: &WeakDom, new: &WeakDom) {
+        let patch = diff(old, new);
+        let mut patched = old.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+
+        assert!(diff(&patched, new).is_empty());
+    }
+
+    #[test]
+    fn apply_round_trip() {
+        let old = WeakDom::new(base());
+        let mut new = WeakDom::new(base());
+
+        let workspace = new.find_by_path("Workspace").unwrap();
+        let floor = new.find_by_path("Workspace.Floor").unwrap();
+        let door = new.find_by_path("Workspace.House.Door").unwrap();
+        let house = new.find_by_path("Workspace.House").unwrap();
+
+        let tree = new.insert(
+            workspace,
+            InstanceBuilder::new("Model")
+                .with_name("Tree")
+                .with_child(InstanceBuilder::new("Part").with_name("Trunk")),
+        );
+        new.transfer_within(door, tree);
+        new.destroy(house);
+
+        let instance = new.get_by_ref_mut(floor).unwrap();
+        instance.name = "Ground".to_owned();
+        instance.properties.remove("Anchored");
+        instance
+            .properties
+            .insert("Target".to_owned(), Variant::Ref(tree));
+
+        assert_round_trip(&old, &new);
+
+        let patch = diff(&old, &new);
+        let mut patched = old.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+
+        let ground = patched.find_by_path("Workspace.Ground").unwrap();
+        assert_eq!(
+            patched.get_by_ref(ground).unwrap().properties["Target"],
+            Variant::Ref(patched.find_by_path("Workspace.Tree").unwrap())
+        );
+    }
+
+    #[test]
+    fn large_depth_tree() {
+        // Snapshots of added subtrees used to be built recursively, which
+        // overflowed the stack for deep trees. See WeakDom's test of the same
+        // name.
+        const N: usize = i16::MAX as usize;
+
+        let mut base = InstanceBuilder::new("Folder");
+        for _ in 0..N {
+            base = InstanceBuilder::new("Folder").with_child(base);
+        }
+
+        let old = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let mut new = old.clone();
+        new.insert(new.root_ref(), base);
+
+        let patch = diff(&old, &new);
+        assert_eq!(patch.added.len(), 1);
+
+        let mut patched = old.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+        assert_eq!(patched.descendants(patched.root_ref()).count(), N + 1);
+    }
+
+    #[test]
+    fn reused_referents() {
+        // Door can't be matched once its name and class both change, so it's
+        // removed and added again. The added copy needs a new referent, since
+        // the old one is still in use until the removal is applied.
+        let old = WeakDom::new(base());
+        let mut new = old.clone();
+        let door = new.find_by_path("Workspace.House.Door").unwrap();
+        new.set_name(door, "Window");
+        new.set_class(door, "Model");
+
+        let patch = diff(&old, &new);
+        assert_eq!(patch.added.len(), 1);
+        assert_ne!(patch.added[0].instance.referent, door);
+        assert_eq!(patch.removed[0].referent, door);
+
+        assert_round_trip(&old, &new);
+    }
+
+    #[test]
+    fn apply_invalid() {
+        let mut dom = WeakDom::new(base());
+        let floor = dom.find_by_path("Workspace.Floor").unwrap();
+        let missing = Ref::new();
+
+        let patch = DomPatch {
+            removed: vec![RemovedInstance {
+                referent: missing,
+                name: "Missing".to_owned(),
+                class: "Part".to_owned(),
+            }],
+            ..DomPatch::default()
+        };
+        assert_eq!(
+            apply_patch(&mut dom, &patch),
+            Err(PatchError::MissingInstance(missing))
+        );
+
+        let workspace = dom.find_by_path("Workspace").unwrap();
+        let patch = DomPatch {
+            moved: vec![MovedInstance {
+                referent: workspace,
+                old_parent: dom.root_ref(),
+                new_parent: floor,
+            }],
+            ..DomPatch::default()
+        };
+        assert_eq!(
+            apply_patch(&mut dom, &patch),
+            Err(PatchError::Cycle(workspace))
+        );
+
+        let patch = DomPatch {
+            changed: vec![ChangedInstance {
+                referent: floor,
+                changes: Vec::new(),
+            }],
+            removed: vec![RemovedInstance {
+                referent: dom.root_ref(),
+                name: "DataModel".to_owned(),
+                class: "DataModel".to_owned(),
+            }],
+            ..DomPatch::default()
+        };
+        assert_eq!(apply_patch(&mut dom, &patch), Err(PatchError::RootInstance));
+
+        let snapshot = capture_snapshot(&dom, floor);
+        let patch = DomPatch {
+            added: vec![AddedInstance {
+                parent: workspace,
+                instance: snapshot.clone(),
+            }],
+            ..DomPatch::default()
+        };
+        assert_eq!(
+            apply_patch(&mut dom, &patch),
+            Err(PatchError::InvalidReferent(floor))
+        );
+
+        let mut snapshot = snapshot;
+        snapshot.referent = Ref::new();
+        let patch = DomPatch {
+            added: vec![
+                AddedInstance {
+                    parent: workspace,
+                    instance: snapshot.clone(),
+                },
+                AddedInstance {
+                    parent: floor,
+                    instance: snapshot.clone(),
+                },
+            ],
+            ..DomPatch::default()
+        };
+        assert_eq!(
+            apply_patch(&mut dom, &patch),
+            Err(PatchError::InvalidReferent(snapshot.referent))
+        );
+
+        // None of the patches above should have been partially applied.
+        assert!(diff(&WeakDom::new(base()), &dom).is_empty());
+    }
+}
@@ -1,8 +1,17 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
+#[cfg(feature = "reflection")]
+use rbx_reflection::ReflectionDatabase;
 use rbx_types::{Ref, UniqueId, Variant};
 
-use crate::instance::{Instance, InstanceBuilder};
+use crate::{
+    diff::capture_snapshot,
+    index::Indexes,
+    instance::{Instance, InstanceBuilder},
+    journal::{DomChange, Journal},
+    path,
+    refs::RefTracker,
+};
 
 /// Represents a DOM containing one or more Roblox instances.
 ///
@@ -11,11 +20,15 @@ use crate::instance::{Instance, InstanceBuilder};
 ///
 /// When constructing instances, you'll want to create [`InstanceBuilder`]
 /// objects and insert them into the tree.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WeakDom {
-    instances: HashMap<Ref, Instance>,
+    pub(crate) instances: HashMap<Ref, Instance>,
     root_ref: Ref,
     unique_ids: HashSet<UniqueId>,
+    pub(crate) journal: Option<Journal>,
+    pub(crate) indexes: Option<Indexes>,
+    pub(crate) refs: Option<RefTracker>,
+    metadata: BTreeMap<String, String>,
 }
 
 impl WeakDom {
@@ -25,6 +38,10 @@ impl WeakDom {
             instances: HashMap::new(),
             root_ref: builder.referent,
             unique_ids: HashSet::new(),
+            journal: None,
+            indexes: None,
+            refs: None,
+            metadata: BTreeMap::new(),
         };
 
         dom.insert(Ref::none(), builder);
@@ -53,6 +70,22 @@ impl WeakDom {
         self.instances.get_mut(&self.root_ref).unwrap()
     }
 
+    /// Returns the metadata associated with the file this `WeakDom` was read
+    /// from, like `ExplicitAutoJoints`.
+    ///
+    /// Metadata is stored in `META` chunks in binary files and `<Meta>` tags
+    /// in XML files. It doesn't belong to any instance, but affects how Roblox
+    /// interprets the file.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Returns a _mutable_ reference to the metadata associated with this
+    /// `WeakDom`.
+    pub fn metadata_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.metadata
+    }
+
     /// Returns a reference to an instance by referent, or `None` if it is not
     /// found.
     pub fn get_by_ref(&self, referent: Ref) -> Option<&Instance> {
@@ -65,6 +98,294 @@ impl WeakDom {
         self.instances.get_mut(&referent)
     }
 
+    /// Returns an iterator over the descendants of the instance with the given
+    /// referent, in breadth-first order. The instance itself is not included.
+    ///
+    /// Siblings are visited in the same order as they appear in their parent's
+    /// list of children. If `referent` does not refer to an instance in the
+    /// DOM, the iterator is empty.
+    pub fn descendants(&self, referent: Ref) -> Descendants<'_> {
+        let mut queue = VecDeque::new();
+
+        if let Some(instance) = self.get_by_ref(referent) {
+            queue.extend(instance.children.iter().copied());
+        }
+
+        Descendants { dom: self, queue }
+    }
+
+    /// Returns an iterator over the descendants of the instance with the given
+    /// referent, in depth-first (pre-order) order. The instance itself is not
+    /// included.
+    ///
+    /// Each instance is visited before its children, and siblings are visited
+    /// in the same order as they appear in their parent's list of children. If
+    /// `referent` does not refer to an instance in the DOM, the iterator is
+    /// empty.
+    pub fn descendants_dfs(&self, referent: Ref) -> DescendantsDfs<'_> {
+        let mut stack = Vec::new();
+
+        if let Some(instance) = self.get_by_ref(referent) {
+            stack.extend(instance.children.iter().rev().copied());
+        }
+
+        DescendantsDfs { dom: self, stack }
+    }
+
+    /// Returns an iterator over the ancestors of the instance with the given
+    /// referent, starting with its parent and ending with the root of its
+    /// tree. The instance itself is not included.
+    ///
+    /// If `referent` does not refer to an instance in the DOM, the iterator is
+    /// empty.
+    pub fn ancestors(&self, referent: Ref) -> Ancestors<'_> {
+        let next = self
+            .get_by_ref(referent)
+            .map(|instance| instance.parent)
+            .unwrap_or_else(Ref::none);
+
+        Ancestors { dom: self, next }
+    }
+
+    /// Returns a walker that visits the descendants of the instance with the
+    /// given referent mutably, in the same order as
+    /// [`WeakDom::descendants`].
+    ///
+    /// The walker does not borrow the `WeakDom`, so the DOM can be used freely
+    /// in between calls to [`DescendantsMut::next`].
+    pub fn descendants_mut(&self, referent: Ref) -> DescendantsMut {
+        let mut queue = VecDeque::new();
+
+        if let Some(instance) = self.get_by_ref(referent) {
+            queue.extend(instance.children.iter().copied());
+        }
+
+        DescendantsMut {
+            queue,
+            depth_first: false,
+        }
+    }
+
+    /// Returns a walker that visits the descendants of the instance with the
+    /// given referent mutably, in the same order as
+    /// [`WeakDom::descendants_dfs`].
+    ///
+    /// The walker does not borrow the `WeakDom`, so the DOM can be used freely
+    /// in between calls to [`DescendantsMut::next`].
+    pub fn descendants_dfs_mut(&self, referent: Ref) -> DescendantsMut {
+        let mut queue = VecDeque::new();
+
+        if let Some(instance) = self.get_by_ref(referent) {
+            queue.extend(instance.children.iter().copied());
+        }
+
+        DescendantsMut {
+            queue,
+            depth_first: true,
+        }
+    }
+
+    /// Returns a walker that visits the ancestors of the instance with the
+    /// given referent mutably, in the same order as [`WeakDom::ancestors`].
+    ///
+    /// The walker does not borrow the `WeakDom`, so the DOM can be used freely
+    /// in between calls to [`AncestorsMut::next`].
+    pub fn ancestors_mut(&self, referent: Ref) -> AncestorsMut {
+        let next = self
+            .get_by_ref(referent)
+            .map(|instance| instance.parent)
+            .unwrap_or_else(Ref::none);
+
+        AncestorsMut { next }
+    }
+
+    /// Returns the referent of the first child of the given instance with the
+    /// given name, like Roblox's `Instance:FindFirstChild`.
+    ///
+    /// Returns `None` if there is no such child or if `referent` does not refer
+    /// to an instance in the DOM.
+    pub fn find_first_child(&self, referent: Ref, name: &str) -> Option<Ref> {
+        self.get_by_ref(referent)?
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.instances[child].name == name)
+    }
+
+    /// Returns the referent of the first child of the given instance whose
+    /// class is exactly `class`, like Roblox's
+    /// `Instance:FindFirstChildOfClass`.
+    ///
+    /// Returns `None` if there is no such child or if `referent` does not refer
+    /// to an instance in the DOM.
+    pub fn find_first_child_of_class(&self, referent: Ref, class: &str) -> Option<Ref> {
+        self.get_by_ref(referent)?
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.instances[child].class == class)
+    }
+
+    /// Returns the referent of the first descendant of the given instance with
+    /// the given name, like Roblox's `Instance:FindFirstDescendant`.
+    ///
+    /// Descendants are searched in the same order as
+    /// [`WeakDom::descendants`], so the shallowest match is returned.
+    pub fn find_first_descendant(&self, referent: Ref, name: &str) -> Option<Ref> {
+        self.descendants(referent)
+            .find(|instance| instance.name == name)
+            .map(Instance::referent)
+    }
+
+    /// Returns an iterator over the descendants of the given instance whose
+    /// class is `class` or inherits from it, like calling Roblox's
+    /// `Instance:IsA` on each descendant.
+    ///
+    /// Inheritance is determined using the given reflection database. Classes
+    /// that are not present in the database only match themselves. Descendants
+    /// are visited in the same order as [`WeakDom::descendants`].
+    #[cfg(feature = "reflection")]
+    pub fn descendants_of_class<'a>(
+        &'a self,
+        referent: Ref,
+        class: &'a str,
+        database: &'a ReflectionDatabase<'_>,
+    ) -> impl Iterator<Item = &'a Instance> + 'a {
+        self.descendants(referent)
+            .filter(move |instance| database.is_a(&instance.class, class))
+    }
+
+    /// Returns the full name of the given instance, like Roblox's
+    /// `Instance:GetFullName`.
+    ///
+    /// The name is made up of the names of the instance's ancestors, separated
+    /// by periods. Just like in Roblox, a `DataModel` at the top of the tree is
+    /// not included, so a part in the workspace is named `Workspace.Part`.
+    ///
+    /// Names are not escaped. To get a path that can be passed back to
+    /// [`WeakDom::find_by_path`], use [`WeakDom::get_path`] instead.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn get_full_name(&self, referent: Ref) -> String {
+        let components = self.path_components(referent);
+
+        let mut name = String::new();
+        for (i, component) in components.iter().rev().enumerate() {
+            if i > 0 {
+                name.push('.');
+            }
+
+            name.push_str(component);
+        }
+
+        name
+    }
+
+    /// Returns a path to the given instance that can be passed to
+    /// [`WeakDom::find_by_path`] to find it again.
+    ///
+    /// This is similar to [`WeakDom::get_full_name`], except that any periods,
+    /// slashes, or backslashes in names are escaped with a backslash, and the
+    /// root of the DOM is never included, whatever its class is. The path to
+    /// the root itself is empty.
+    ///
+    /// Paths are made of names alone, so the path only leads back to the same
+    /// instance when those names are enough to find it:
+    ///
+    /// - If the instance or one of its ancestors has an earlier sibling with
+    ///   the same name, `find_by_path` finds that sibling instead.
+    /// - If the instance is a child of the root with an empty name, its path
+    ///   is empty, which refers to the root.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn get_path(&self, referent: Ref) -> String {
+        let mut components = self.path_components(referent);
+
+        // path_components only leaves out a DataModel at the top of the tree,
+        // but find_by_path always starts from the root.
+        if referent == self.root_ref || self.root().class != "DataModel" {
+            components.pop();
+        }
+
+        let mut path = String::new();
+        for (i, component) in components.iter().rev().enumerate() {
+            if i > 0 {
+                path.push('.');
+            }
+
+            path::push_escaped(&mut path, component);
+        }
+
+        path
+    }
+
+    /// Resolves a path like `Workspace.Map.Spawn` or `Workspace/Map/Spawn`
+    /// starting from the root of the DOM, returning the referent of the
+    /// instance it points to.
+    ///
+    /// Components may be separated with either periods or slashes. Names that
+    /// contain a separator can be written by escaping it with a backslash, like
+    /// `Version\.2`. Each component is resolved using
+    /// [`WeakDom::find_first_child`], so if several siblings share a name, the
+    /// first one is used.
+    ///
+    /// When the root of the DOM is a `DataModel`, the path may start with
+    /// `game`, just like in Luau. An empty path refers to the root.
+    pub fn find_by_path(&self, path: &str) -> Option<Ref> {
+        let mut components = path::split_path(path);
+
+        if components.first().map(String::as_str) == Some("game")
+            && self.root().class == "DataModel"
+            && self.find_first_child(self.root_ref, "game").is_none()
+        {
+            components.remove(0);
+        }
+
+        self.resolve_components(self.root_ref, &components)
+    }
+
+    /// Resolves a path relative to the instance with the given referent. The
+    /// path uses the same syntax as [`WeakDom::find_by_path`], but a leading
+    /// `game` component is not treated specially.
+    ///
+    /// An empty path refers to `referent` itself.
+    pub fn find_by_path_from(&self, referent: Ref, path: &str) -> Option<Ref> {
+        let components = path::split_path(path);
+        self.resolve_components(referent, &components)
+    }
+
+    fn resolve_components(&self, referent: Ref, components: &[String]) -> Option<Ref> {
+        self.get_by_ref(referent)?;
+
+        components.iter().try_fold(referent, |current, name| {
+            self.find_first_child(current, name)
+        })
+    }
+
+    /// Collects the names that make up the full name of an instance, from the
+    /// instance itself up to the top of the tree.
+    fn path_components(&self, referent: Ref) -> Vec<&str> {
+        let instance = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot get the name of an instance that does not exist"));
+
+        let mut components = vec![instance.name.as_str()];
+        let mut top = instance;
+
+        for ancestor in self.ancestors(referent) {
+            components.push(ancestor.name.as_str());
+            top = ancestor;
+        }
+
+        if top.referent != referent && top.class == "DataModel" {
+            components.pop();
+        }
+
+        components
+    }
+
     /// Insert a new instance into the DOM with the given parent. The parent is allowed to
     /// be the none Ref.
     ///
@@ -105,11 +426,16 @@ impl WeakDom {
             }
         }
 
+        self.record_added(root_referent);
         root_referent
     }
 
     /// Destroy the instance with the given referent.
     ///
+    /// If ref tracking is enabled, every `Ref` property that points at one of
+    /// the destroyed instances is set to `Ref::none()`. See
+    /// [`WeakDom::enable_ref_tracking`].
+    ///
     /// ## Panics
     /// Panics if `referent` does not refer to an instance in the DOM.
     ///
@@ -126,18 +452,29 @@ impl WeakDom {
             .unwrap_or_else(|| panic!("cannot destroy an instance that does not exist"));
 
         let parent_ref = instance.parent;
-        if parent_ref.is_some() {
-            let parent = self.instances.get_mut(&parent_ref).unwrap();
-            parent.children.retain(|&child| child != referent);
-        }
 
-        let mut to_remove = VecDeque::new();
-        to_remove.push_back(referent);
+        // Clearing refs is part of the same step in the journal, so undoing
+        // the destroy restores them too.
+        self.transaction(|dom| {
+            dom.record_removed(referent);
 
-        while let Some(referent) = to_remove.pop_front() {
-            let instance = self.inner_remove(referent);
-            to_remove.extend(instance.children);
-        }
+            if parent_ref.is_some() {
+                let parent = dom.instances.get_mut(&parent_ref).unwrap();
+                parent.children.retain(|&child| child != referent);
+            }
+
+            let mut destroyed = Vec::new();
+            let mut to_remove = VecDeque::new();
+            to_remove.push_back(referent);
+
+            while let Some(referent) = to_remove.pop_front() {
+                let instance = dom.inner_remove(referent);
+                to_remove.extend(instance.children);
+                destroyed.push(referent);
+            }
+
+            dom.clear_refs_to(&destroyed);
+        });
     }
 
     /// Move the instance with the given referent to a new `WeakDom`, parenting
@@ -157,6 +494,7 @@ impl WeakDom {
             panic!("cannot transfer the root instance of WeakDom");
         }
 
+        self.record_removed(referent);
         let mut instance = self.inner_remove(referent);
 
         // Remove the instance being moved from its parent's list of children.
@@ -193,6 +531,8 @@ impl WeakDom {
             panic!("cannot move an instance into an instance that does not exist")
         });
         dest_parent.children.push(referent);
+
+        dest.record_added(referent);
     }
 
     /// Move the instance with the given referent to a new parent within the
@@ -220,6 +560,7 @@ impl WeakDom {
         // Tell the instance who its new parent is.
         let parent_ref = instance.parent;
         instance.parent = dest_parent_ref;
+        let old_index = self.child_index(referent, parent_ref);
 
         // Remove the instance's referent from its parent's list of children.
         if parent_ref.is_some() {
@@ -233,6 +574,128 @@ impl WeakDom {
             .get_mut(&dest_parent_ref)
             .unwrap_or_else(|| panic!("cannot move into an instance that does not exist"));
         dest_parent.children.push(referent);
+        let new_index = dest_parent.children.len() - 1;
+
+        self.record(DomChange::Moved {
+            referent,
+            old_parent: parent_ref,
+            old_index,
+            new_parent: dest_parent_ref,
+            new_index,
+        });
+    }
+
+    /// Sets the property with the given name on the instance with the given
+    /// referent, returning the property's previous value.
+    ///
+    /// Unlike modifying [`Instance::properties`] directly, this change is
+    /// recorded in the journal if it's enabled.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn set_property<K: Into<String>, V: Into<Variant>>(
+        &mut self,
+        referent: Ref,
+        name: K,
+        value: V,
+    ) -> Option<Variant> {
+        let name = name.into();
+        let value = value.into();
+        let instance = self
+            .instances
+            .get_mut(&referent)
+            .unwrap_or_else(|| panic!("cannot set a property on an instance that does not exist"));
+
+        let old = instance.properties.insert(name.clone(), value.clone());
+
+        if name == "Tags" || is_ref(Some(&value)) || is_ref(old.as_ref()) {
+            self.reindex(referent);
+        }
+
+        if self.journal.is_some() {
+            self.record(DomChange::Property {
+                referent,
+                name,
+                old: old.clone(),
+                new: Some(value),
+            });
+        }
+
+        old
+    }
+
+    /// Removes the property with the given name from the instance with the
+    /// given referent, returning its previous value.
+    ///
+    /// Unlike modifying [`Instance::properties`] directly, this change is
+    /// recorded in the journal if it's enabled.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn remove_property(&mut self, referent: Ref, name: &str) -> Option<Variant> {
+        let instance = self.instances.get_mut(&referent).unwrap_or_else(|| {
+            panic!("cannot remove a property from an instance that does not exist")
+        });
+
+        let old = instance.properties.remove(name);
+
+        if name == "Tags" || is_ref(old.as_ref()) {
+            self.reindex(referent);
+        }
+
+        if old.is_some() {
+            self.record(DomChange::Property {
+                referent,
+                name: name.to_owned(),
+                old: old.clone(),
+                new: None,
+            });
+        }
+
+        old
+    }
+
+    /// Changes the name of the instance with the given referent.
+    ///
+    /// Unlike modifying [`Instance::name`] directly, this change is recorded
+    /// in the journal if it's enabled.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn set_name<S: Into<String>>(&mut self, referent: Ref, name: S) {
+        let instance = self
+            .instances
+            .get_mut(&referent)
+            .unwrap_or_else(|| panic!("cannot rename an instance that does not exist"));
+
+        let new = name.into();
+        let old = std::mem::replace(&mut instance.name, new.clone());
+        self.reindex(referent);
+
+        if old != new {
+            self.record(DomChange::Name { referent, old, new });
+        }
+    }
+
+    /// Changes the class of the instance with the given referent.
+    ///
+    /// Unlike modifying [`Instance::class`] directly, this change is recorded
+    /// in the journal if it's enabled.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn set_class<S: Into<String>>(&mut self, referent: Ref, class: S) {
+        let instance = self.instances.get_mut(&referent).unwrap_or_else(|| {
+            panic!("cannot change the class of an instance that does not exist")
+        });
+
+        let new = class.into();
+        let old = std::mem::replace(&mut instance.class, new.clone());
+        self.reindex(referent);
+
+        if old != new {
+            self.record(DomChange::Class { referent, old, new });
+        }
     }
 
     /// Clone the instance with the given `referent` and all its descendants
@@ -243,6 +706,9 @@ impl WeakDom {
     /// Any Ref properties that point to instances contained in the subtree are
     /// rewritten to point to the cloned instances.
     pub fn clone_within(&mut self, referent: Ref) -> Ref {
+        // The clone is recorded as a single change once it's complete, rather
+        // than once for every instance in it.
+        let journal = self.journal.take();
         let mut ctx = CloneContext::default();
         let root_builder = ctx.clone_ref_as_builder(self, referent);
         let root_ref = self.insert(Ref::none(), root_builder);
@@ -253,6 +719,9 @@ impl WeakDom {
         }
 
         ctx.rewrite_refs(self);
+
+        self.journal = journal;
+        self.record_added(root_ref);
         root_ref
     }
 
@@ -269,6 +738,7 @@ impl WeakDom {
     /// properties will not necessarily be preserved in the destination dom. If you're
     /// cloning multiple instances, prefer `clone_multiple_into_external` instead!
     pub fn clone_into_external(&self, referent: Ref, dest: &mut WeakDom) -> Ref {
+        let journal = dest.journal.take();
         let mut ctx = CloneContext::default();
         let root_builder = ctx.clone_ref_as_builder(self, referent);
         let root_ref = dest.insert(Ref::none(), root_builder);
@@ -279,12 +749,16 @@ impl WeakDom {
         }
 
         ctx.rewrite_refs(dest);
+
+        dest.journal = journal;
+        dest.record_added(root_ref);
         root_ref
     }
 
     /// Similar to `clone_into_external`, but clones multiple subtrees all at once. This
     /// method will preserve Ref properties that point across the cloned subtrees.
     pub fn clone_multiple_into_external(&self, referents: &[Ref], dest: &mut WeakDom) -> Vec<Ref> {
+        let journal = dest.journal.take();
         let mut ctx = CloneContext::default();
         let mut root_refs = Vec::with_capacity(referents.len());
 
@@ -299,9 +773,58 @@ impl WeakDom {
         }
 
         ctx.rewrite_refs(dest);
+
+        dest.journal = journal;
+        for root_ref in &root_refs {
+            dest.record_added(*root_ref);
+        }
+
         root_refs
     }
 
+    /// Moves the instance with the given referent to the given position in its
+    /// parent's list of children. Does nothing if the instance has no parent.
+    pub(crate) fn set_child_index(&mut self, referent: Ref, index: usize) {
+        let parent_ref = self.instances[&referent].parent;
+
+        if let Some(parent) = self.instances.get_mut(&parent_ref) {
+            parent.children.retain(|&child| child != referent);
+            let index = index.min(parent.children.len());
+            parent.children.insert(index, referent);
+        }
+    }
+
+    fn child_index(&self, referent: Ref, parent_ref: Ref) -> usize {
+        self.instances
+            .get(&parent_ref)
+            .and_then(|parent| parent.children.iter().position(|&child| child == referent))
+            .unwrap_or(0)
+    }
+
+    fn record_added(&mut self, referent: Ref) {
+        if self.journal.is_some() {
+            let parent = self.instances[&referent].parent;
+
+            self.record(DomChange::Added {
+                parent,
+                index: self.child_index(referent, parent),
+                instance: capture_snapshot(self, referent),
+            });
+        }
+    }
+
+    fn record_removed(&mut self, referent: Ref) {
+        if self.journal.is_some() {
+            let parent = self.instances[&referent].parent;
+
+            self.record(DomChange::Removed {
+                parent,
+                index: self.child_index(referent, parent),
+                instance: capture_snapshot(self, referent),
+            });
+        }
+    }
+
     fn inner_insert(&mut self, referent: Ref, instance: Instance) {
         self.instances.insert(referent, instance);
 
@@ -330,6 +853,14 @@ impl WeakDom {
                 self.unique_ids.insert(*unique_id);
             };
         }
+
+        if let Some(indexes) = &mut self.indexes {
+            indexes.insert(instance);
+        }
+
+        if let Some(refs) = &mut self.refs {
+            refs.insert(instance);
+        }
     }
 
     fn inner_remove(&mut self, referent: Ref) -> Instance {
@@ -342,20 +873,173 @@ impl WeakDom {
             self.unique_ids.remove(unique_id);
         }
 
+        if let Some(indexes) = &mut self.indexes {
+            indexes.remove(referent);
+        }
+
+        if let Some(refs) = &mut self.refs {
+            refs.remove(referent);
+        }
+
         instance
     }
 }
 
+fn is_ref(value: Option<&Variant>) -> bool {
+    matches!(value, Some(Variant::Ref(_)))
+}
+
 impl Default for WeakDom {
     fn default() -> WeakDom {
         WeakDom {
             instances: HashMap::new(),
             root_ref: Ref::none(),
             unique_ids: HashSet::new(),
+            journal: None,
+            indexes: None,
+            refs: None,
+            metadata: BTreeMap::new(),
         }
     }
 }
 
+/// A breadth-first iterator over the descendants of an instance in a
+/// [`WeakDom`], created by [`WeakDom::descendants`].
+#[derive(Debug)]
+pub struct Descendants<'a> {
+    dom: &'a WeakDom,
+    queue: VecDeque<Ref>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Instance;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let referent = self.queue.pop_front()?;
+        let instance = self.dom.get_by_ref(referent)?;
+        self.queue.extend(instance.children.iter().copied());
+
+        Some(instance)
+    }
+}
+
+/// A depth-first iterator over the descendants of an instance in a
+/// [`WeakDom`], created by [`WeakDom::descendants_dfs`].
+#[derive(Debug)]
+pub struct DescendantsDfs<'a> {
+    dom: &'a WeakDom,
+    stack: Vec<Ref>,
+}
+
+impl<'a> Iterator for DescendantsDfs<'a> {
+    type Item = &'a Instance;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let referent = self.stack.pop()?;
+        let instance = self.dom.get_by_ref(referent)?;
+
+        // Children are pushed in reverse so that the first child is the next
+        // one to be popped off of the stack.
+        self.stack.extend(instance.children.iter().rev().copied());
+
+        Some(instance)
+    }
+}
+
+/// An iterator over the ancestors of an instance in a [`WeakDom`], created by
+/// [`WeakDom::ancestors`].
+#[derive(Debug)]
+pub struct Ancestors<'a> {
+    dom: &'a WeakDom,
+    next: Ref,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a Instance;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let instance = self.dom.get_by_ref(self.next)?;
+        self.next = instance.parent;
+
+        Some(instance)
+    }
+}
+
+/// A walker that visits the descendants of an instance in a [`WeakDom`]
+/// mutably, created by [`WeakDom::descendants_mut`] or
+/// [`WeakDom::descendants_dfs_mut`].
+///
+/// Because each call to `next` borrows the `WeakDom` separately, this type
+/// does not implement `Iterator`:
+///
+/// ```
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut dom = WeakDom::new(
+///     InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part")),
+/// );
+///
+/// let mut walker = dom.descendants_mut(dom.root_ref());
+/// while let Some(instance) = walker.next(&mut dom) {
+///     instance.properties.insert("Anchored".to_owned(), true.into());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DescendantsMut {
+    queue: VecDeque<Ref>,
+    depth_first: bool,
+}
+
+impl DescendantsMut {
+    /// Returns the next instance in the walk, or `None` if every descendant has
+    /// been visited.
+    ///
+    /// Instances that were removed from `dom` since the walker was created are
+    /// skipped, along with their descendants.
+    pub fn next<'a>(&mut self, dom: &'a mut WeakDom) -> Option<&'a mut Instance> {
+        let referent = loop {
+            let referent = self.queue.pop_front()?;
+
+            if dom.instances.contains_key(&referent) {
+                break referent;
+            }
+        };
+
+        let instance = dom.instances.get_mut(&referent).unwrap();
+
+        if self.depth_first {
+            for &child in instance.children.iter().rev() {
+                self.queue.push_front(child);
+            }
+        } else {
+            self.queue.extend(instance.children.iter().copied());
+        }
+
+        Some(instance)
+    }
+}
+
+/// A walker that visits the ancestors of an instance in a [`WeakDom`] mutably,
+/// created by [`WeakDom::ancestors_mut`].
+///
+/// Like [`DescendantsMut`], this type borrows the `WeakDom` only for the
+/// duration of each call to `next`.
+#[derive(Debug)]
+pub struct AncestorsMut {
+    next: Ref,
+}
+
+impl AncestorsMut {
+    /// Returns the next ancestor in the walk, or `None` if the root of the tree
+    /// has been reached.
+    pub fn next<'a>(&mut self, dom: &'a mut WeakDom) -> Option<&'a mut Instance> {
+        let instance = dom.instances.get_mut(&self.next)?;
+        self.next = instance.parent;
+
+        Some(instance)
+    }
+}
+
 #[derive(Debug, Default)]
 struct CloneContext {
     queue: VecDeque<(Ref, Ref)>,
@@ -400,6 +1084,8 @@ impl CloneContext {
                     }
                 }
             }
+
+            dest.reindex(*new_ref);
         }
     }
 
@@ -604,6 +1290,278 @@ mod test {
         insta::assert_yaml_snapshot!(viewer.view(&other_dom));
     }
 
+    fn traversal_dom() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_name("Root")
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("A")
+                        .with_child(InstanceBuilder::new("Part").with_name("A1"))
+                        .with_child(InstanceBuilder::new("Part").with_name("A2")),
+                )
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("B")
+                        .with_child(InstanceBuilder::new("Part").with_name("B1")),
+                ),
+        )
+    }
+
+    #[test]
+    fn descendants() {
+        let dom = traversal_dom();
+
+        let names: Vec<&str> = dom
+            .descendants(dom.root_ref())
+            .map(|instance| instance.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["A", "B", "A1", "A2", "B1"]);
+    }
+
+    #[test]
+    fn descendants_dfs() {
+        let dom = traversal_dom();
+
+        let names: Vec<&str> = dom
+            .descendants_dfs(dom.root_ref())
+            .map(|instance| instance.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["A", "A1", "A2", "B", "B1"]);
+    }
+
+    #[test]
+    fn descendants_of_missing_instance() {
+        let dom = traversal_dom();
+
+        assert_eq!(dom.descendants(Ref::new()).count(), 0);
+        assert_eq!(dom.descendants_dfs(Ref::none()).count(), 0);
+        assert_eq!(dom.ancestors(Ref::new()).count(), 0);
+    }
+
+    #[test]
+    fn ancestors() {
+        let dom = traversal_dom();
+        let a = dom.root().children()[0];
+        let a2 = dom.get_by_ref(a).unwrap().children()[1];
+
+        let names: Vec<&str> = dom
+            .ancestors(a2)
+            .map(|instance| instance.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["A", "Root"]);
+        assert_eq!(dom.ancestors(dom.root_ref()).count(), 0);
+    }
+
+    #[test]
+    fn descendants_mut() {
+        let mut dom = traversal_dom();
+
+        let mut visited = Vec::new();
+        let mut walker = dom.descendants_dfs_mut(dom.root_ref());
+        while let Some(instance) = walker.next(&mut dom) {
+            visited.push(instance.name.clone());
+            instance
+                .properties
+                .insert("Visited".to_owned(), Variant::Bool(true));
+        }
+
+        assert_eq!(visited, ["A", "A1", "A2", "B", "B1"]);
+        assert!(dom
+            .descendants(dom.root_ref())
+            .all(|instance| instance.properties.get("Visited") == Some(&Variant::Bool(true))));
+        assert!(!dom.root().properties.contains_key("Visited"));
+
+        let b1 = dom
+            .descendants(dom.root_ref())
+            .find(|instance| instance.name == "B1")
+            .unwrap()
+            .referent();
+
+        let mut walker = dom.ancestors_mut(b1);
+        while let Some(instance) = walker.next(&mut dom) {
+            instance.name.push_str(" (ancestor)");
+        }
+
+        assert_eq!(dom.root().name, "Root (ancestor)");
+    }
+
+    #[test]
+    fn descendants_mut_skips_destroyed() {
+        let mut dom = traversal_dom();
+        let a = dom.root().children()[0];
+
+        let mut visited = Vec::new();
+        let mut walker = dom.descendants_mut(dom.root_ref());
+        while let Some(instance) = walker.next(&mut dom) {
+            visited.push(instance.name.clone());
+
+            if instance.name == "B" {
+                dom.destroy(a);
+            }
+        }
+
+        assert_eq!(visited, ["A", "B", "B1"]);
+    }
+
+    fn place_dom() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_name("Place1")
+                .with_child(
+                    InstanceBuilder::new("Workspace").with_child(
+                        InstanceBuilder::new("Model")
+                            .with_name("Map")
+                            .with_child(InstanceBuilder::new("Part").with_name("Floor"))
+                            .with_child(InstanceBuilder::new("SpawnLocation").with_name("Spawn"))
+                            .with_child(InstanceBuilder::new("Folder").with_name("Version.2")),
+                    ),
+                ),
+        )
+    }
+
+    #[test]
+    fn find_first_child() {
+        let dom = place_dom();
+        let workspace = dom.find_first_child(dom.root_ref(), "Workspace").unwrap();
+        let map = dom.find_first_child(workspace, "Map").unwrap();
+
+        assert_eq!(dom.get_by_ref(map).unwrap().class, "Model");
+        assert_eq!(dom.find_first_child(workspace, "Spawn"), None);
+        assert_eq!(dom.find_first_child(Ref::new(), "Map"), None);
+
+        let spawn = dom.find_first_child_of_class(map, "SpawnLocation").unwrap();
+        assert_eq!(dom.get_by_ref(spawn).unwrap().name, "Spawn");
+        assert_eq!(dom.find_first_child_of_class(map, "BasePart"), None);
+
+        assert_eq!(
+            dom.find_first_descendant(dom.root_ref(), "Spawn"),
+            Some(spawn)
+        );
+        assert_eq!(dom.find_first_descendant(map, "Map"), None);
+    }
+
+    #[cfg(feature = "reflection")]
+    #[test]
+    fn descendants_of_class() {
+        let database = rbx_reflection_database::get();
+        let dom = place_dom();
+
+        let parts: Vec<_> = dom
+            .descendants_of_class(dom.root_ref(), "BasePart", database)
+            .map(|instance| instance.name.as_str())
+            .collect();
+        assert_eq!(parts, ["Floor", "Spawn"]);
+
+        let models: Vec<_> = dom
+            .descendants_of_class(dom.root_ref(), "Model", database)
+            .map(|instance| instance.name.as_str())
+            .collect();
+        assert_eq!(models, ["Workspace", "Map"]);
+
+        let instances = dom.descendants_of_class(dom.root_ref(), "Instance", database);
+        assert_eq!(instances.count(), dom.descendants(dom.root_ref()).count());
+    }
+
+    #[test]
+    fn get_full_name() {
+        let dom = place_dom();
+        let spawn = dom.find_first_descendant(dom.root_ref(), "Spawn").unwrap();
+        let version = dom
+            .find_first_descendant(dom.root_ref(), "Version.2")
+            .unwrap();
+
+        assert_eq!(dom.get_full_name(spawn), "Workspace.Map.Spawn");
+        assert_eq!(dom.get_full_name(version), "Workspace.Map.Version.2");
+        assert_eq!(dom.get_full_name(dom.root_ref()), "Place1");
+        assert_eq!(dom.get_path(version), r"Workspace.Map.Version\.2");
+
+        // Trees that aren't rooted in a DataModel include their root.
+        let dom = traversal_dom();
+        let a1 = dom.find_first_descendant(dom.root_ref(), "A1").unwrap();
+        assert_eq!(dom.get_full_name(a1), "Root.A.A1");
+        assert_eq!(dom.get_path(a1), "A.A1");
+        assert_eq!(dom.get_path(dom.root_ref()), "");
+    }
+
+    #[test]
+    fn find_by_path() {
+        let dom = place_dom();
+        let spawn = dom.find_first_descendant(dom.root_ref(), "Spawn").unwrap();
+        let version = dom
+            .find_first_descendant(dom.root_ref(), "Version.2")
+            .unwrap();
+
+        assert_eq!(dom.find_by_path("Workspace.Map.Spawn"), Some(spawn));
+        assert_eq!(dom.find_by_path("game.Workspace.Map.Spawn"), Some(spawn));
+        assert_eq!(dom.find_by_path("Workspace/Map/Spawn"), Some(spawn));
+        assert_eq!(dom.find_by_path(r"Workspace.Map.Version\.2"), Some(version));
+        assert_eq!(dom.find_by_path("Workspace.Map.Version.2"), None);
+        assert_eq!(dom.find_by_path(""), Some(dom.root_ref()));
+        assert_eq!(dom.find_by_path("game"), Some(dom.root_ref()));
+
+        for referent in [spawn, version] {
+            assert_eq!(dom.find_by_path(&dom.get_path(referent)), Some(referent));
+        }
+
+        let map = dom.find_by_path("Workspace.Map").unwrap();
+        assert_eq!(dom.find_by_path_from(map, "Spawn"), Some(spawn));
+        assert_eq!(dom.find_by_path_from(map, ""), Some(map));
+        assert_eq!(dom.find_by_path_from(map, "game.Workspace"), None);
+    }
+
+    #[test]
+    fn path_round_trip() {
+        for dom in [place_dom(), traversal_dom()] {
+            let root = dom.root_ref();
+            let referents =
+                std::iter::once(root).chain(dom.descendants(root).map(Instance::referent));
+
+            for referent in referents {
+                assert_eq!(dom.find_by_path(&dom.get_path(referent)), Some(referent));
+            }
+        }
+    }
+
+    #[test]
+    fn path_ambiguous_names() {
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part").with_name("Same"))
+                .with_child(
+                    InstanceBuilder::new("Part")
+                        .with_name("Same")
+                        .with_child(InstanceBuilder::new("Attachment").with_name("Inner")),
+                )
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("")
+                        .with_child(InstanceBuilder::new("Part").with_name("Inner")),
+                ),
+        );
+        let root = dom.root_ref();
+        let children = dom.root().children();
+
+        // Siblings with the same name have the same path, which finds the
+        // first of them.
+        let attachment = dom.get_by_ref(children[1]).unwrap().children()[0];
+        assert_eq!(dom.get_path(children[1]), "Same");
+        assert_eq!(dom.find_by_path("Same"), Some(children[0]));
+        assert_eq!(dom.get_path(attachment), "Same.Inner");
+        assert_eq!(dom.find_by_path("Same.Inner"), None);
+
+        // A child of the root with an empty name has the same path as the
+        // root, but its descendants can still be found.
+        let inner = dom.get_by_ref(children[2]).unwrap().children()[0];
+        assert_eq!(dom.get_path(children[2]), "");
+        assert_eq!(dom.find_by_path(""), Some(root));
+        assert_eq!(dom.get_path(inner), ".Inner");
+        assert_eq!(dom.find_by_path(".Inner"), Some(inner));
+    }
+
     #[test]
     fn large_depth_tree() {
         // We've had issues with stack overflows when creating WeakDoms with
@@ -0,0 +1,296 @@
+//! Optional indexes that allow a [`WeakDom`] to look up instances by class,
+//! name, or tag without scanning the whole tree.
+
+use std::collections::{HashMap, HashSet};
+
+use rbx_types::{Ref, Variant};
+
+use crate::{Instance, WeakDom};
+
+/// Lookup tables from class names, names, and tags to the instances that have
+/// them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Indexes {
+    by_class: HashMap<String, HashSet<Ref>>,
+    by_name: HashMap<String, HashSet<Ref>>,
+    by_tag: HashMap<String, HashSet<Ref>>,
+
+    /// The keys each instance was indexed under. Instances can be modified
+    /// directly through `WeakDom::get_by_ref_mut`, so we can't rely on the
+    /// instance itself to know which entries to remove.
+    keys: HashMap<Ref, IndexKeys>,
+
+    /// Returned from lookups with no matching instances.
+    empty: HashSet<Ref>,
+}
+
+#[derive(Debug, Clone)]
+struct IndexKeys {
+    class: String,
+    name: String,
+    tags: Vec<String>,
+}
+
+impl Indexes {
+    pub(crate) fn insert(&mut self, instance: &Instance) {
+        let referent = instance.referent();
+        self.remove(referent);
+
+        let tags: Vec<String> = match instance.properties.get("Tags") {
+            Some(Variant::Tags(tags)) => tags.iter().map(str::to_owned).collect(),
+            _ => Vec::new(),
+        };
+
+        add(&mut self.by_class, &instance.class, referent);
+        add(&mut self.by_name, &instance.name, referent);
+        for tag in &tags {
+            add(&mut self.by_tag, tag, referent);
+        }
+
+        self.keys.insert(
+            referent,
+            IndexKeys {
+                class: instance.class.clone(),
+                name: instance.name.clone(),
+                tags,
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, referent: Ref) {
+        if let Some(keys) = self.keys.remove(&referent) {
+            remove(&mut self.by_class, &keys.class, referent);
+            remove(&mut self.by_name, &keys.name, referent);
+            for tag in &keys.tags {
+                remove(&mut self.by_tag, tag, referent);
+            }
+        }
+    }
+}
+
+fn add(index: &mut HashMap<String, HashSet<Ref>>, key: &str, referent: Ref) {
+    match index.get_mut(key) {
+        Some(referents) => {
+            referents.insert(referent);
+        }
+        None => {
+            index.insert(key.to_owned(), std::iter::once(referent).collect());
+        }
+    }
+}
+
+fn remove(index: &mut HashMap<String, HashSet<Ref>>, key: &str, referent: Ref) {
+    if let Some(referents) = index.get_mut(key) {
+        referents.remove(&referent);
+
+        if referents.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+impl WeakDom {
+    /// Starts maintaining indexes of every instance in the `WeakDom` by class,
+    /// name, and tag, which makes [`WeakDom::get_by_class`],
+    /// [`WeakDom::get_by_name`], and [`WeakDom::get_by_tag`] available. Does
+    /// nothing if indexes are already enabled.
+    ///
+    /// Indexes are kept up to date by methods on `WeakDom`, like
+    /// [`WeakDom::insert`] and [`WeakDom::set_name`]. If an instance is
+    /// modified directly through [`WeakDom::get_by_ref_mut`], call
+    /// [`WeakDom::reindex`] afterwards.
+    pub fn enable_indexes(&mut self) {
+        if self.indexes.is_some() {
+            return;
+        }
+
+        let mut indexes = Indexes::default();
+        for instance in self.instances.values() {
+            indexes.insert(instance);
+        }
+
+        self.indexes = Some(indexes);
+    }
+
+    /// Stops maintaining indexes and frees the memory used by them.
+    pub fn disable_indexes(&mut self) {
+        self.indexes = None;
+    }
+
+    /// Returns whether indexes are enabled for this `WeakDom`.
+    pub fn has_indexes(&self) -> bool {
+        self.indexes.is_some()
+    }
+
+    /// Updates the indexes and ref tracking for the instance with the given
+    /// referent. This must be called after changing the name, class, `Tags`
+    /// property, or `Ref` properties of an instance through
+    /// [`WeakDom::get_by_ref_mut`].
+    ///
+    /// Does nothing if neither indexes nor ref tracking are enabled.
+    pub fn reindex(&mut self, referent: Ref) {
+        let instance = self.instances.get(&referent);
+
+        if let Some(indexes) = &mut self.indexes {
+            match instance {
+                Some(instance) => indexes.insert(instance),
+                None => indexes.remove(referent),
+            }
+        }
+
+        if let Some(refs) = &mut self.refs {
+            match instance {
+                Some(instance) => refs.insert(instance),
+                None => refs.remove(referent),
+            }
+        }
+    }
+
+    /// Returns the referents of every instance whose class is exactly `class`.
+    ///
+    /// ## Panics
+    /// Panics if indexes are not enabled. See [`WeakDom::enable_indexes`].
+    pub fn get_by_class(&self, class: &str) -> &HashSet<Ref> {
+        let indexes = self.expect_indexes();
+        indexes.by_class.get(class).unwrap_or(&indexes.empty)
+    }
+
+    /// Returns the referents of every instance named `name`.
+    ///
+    /// ## Panics
+    /// Panics if indexes are not enabled. See [`WeakDom::enable_indexes`].
+    pub fn get_by_name(&self, name: &str) -> &HashSet<Ref> {
+        let indexes = self.expect_indexes();
+        indexes.by_name.get(name).unwrap_or(&indexes.empty)
+    }
+
+    /// Returns the referents of every instance whose `Tags` property contains
+    /// `tag`.
+    ///
+    /// ## Panics
+    /// Panics if indexes are not enabled. See [`WeakDom::enable_indexes`].
+    pub fn get_by_tag(&self, tag: &str) -> &HashSet<Ref> {
+        let indexes = self.expect_indexes();
+        indexes.by_tag.get(tag).unwrap_or(&indexes.empty)
+    }
+
+    fn expect_indexes(&self) -> &Indexes {
+        self.indexes
+            .as_ref()
+            .unwrap_or_else(|| panic!("indexes are not enabled for this WeakDom"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::Tags;
+
+    use crate::InstanceBuilder;
+
+    fn tags(tags: &[&str]) -> Tags {
+        let mut value = Tags::new();
+        for tag in tags {
+            value.push(tag);
+        }
+        value
+    }
+
+    fn dom() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Main")
+                        .with_property("Tags", tags(&["Enemy", "Boss"])),
+                )
+                .with_child(
+                    InstanceBuilder::new("Script").with_child(
+                        InstanceBuilder::new("Part")
+                            .with_name("Main")
+                            .with_property("Tags", tags(&["Enemy"])),
+                    ),
+                ),
+        )
+    }
+
+    fn set(referents: &[Ref]) -> HashSet<Ref> {
+        referents.iter().copied().collect()
+    }
+
+    #[test]
+    fn lookups() {
+        let mut dom = dom();
+        dom.enable_indexes();
+
+        let main = dom.root().children()[0];
+        let script = dom.root().children()[1];
+        let part = dom.get_by_ref(script).unwrap().children()[0];
+
+        assert_eq!(dom.get_by_class("Script"), &set(&[main, script]));
+        assert_eq!(dom.get_by_class("Folder"), &set(&[dom.root_ref()]));
+        assert_eq!(dom.get_by_name("Main"), &set(&[main, part]));
+        assert_eq!(dom.get_by_tag("Enemy"), &set(&[main, part]));
+        assert_eq!(dom.get_by_tag("Boss"), &set(&[main]));
+        assert!(dom.get_by_tag("Friend").is_empty());
+    }
+
+    #[test]
+    fn maintained_by_mutations() {
+        let mut dom = dom();
+        dom.enable_indexes();
+
+        let main = dom.root().children()[0];
+        let script = dom.root().children()[1];
+        let part = dom.get_by_ref(script).unwrap().children()[0];
+
+        dom.destroy(script);
+        assert_eq!(dom.get_by_class("Script"), &set(&[main]));
+        assert!(dom.get_by_class("Part").is_empty());
+        assert_eq!(dom.get_by_tag("Enemy"), &set(&[main]));
+        assert!(!dom.get_by_name("Main").contains(&part));
+
+        dom.set_name(main, "Renamed");
+        dom.set_class(main, "LocalScript");
+        dom.remove_property(main, "Tags");
+        assert!(dom.get_by_name("Main").is_empty());
+        assert_eq!(dom.get_by_name("Renamed"), &set(&[main]));
+        assert_eq!(dom.get_by_class("LocalScript"), &set(&[main]));
+        assert!(dom.get_by_tag("Enemy").is_empty());
+
+        let root = dom.root_ref();
+        let added = dom.insert(
+            root,
+            InstanceBuilder::new("Part").with_property("Tags", tags(&["Enemy"])),
+        );
+        assert_eq!(dom.get_by_tag("Enemy"), &set(&[added]));
+
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        other.enable_indexes();
+        let other_root = other.root_ref();
+        dom.transfer(added, &mut other, other_root);
+        assert!(dom.get_by_tag("Enemy").is_empty());
+        assert_eq!(other.get_by_tag("Enemy"), &set(&[added]));
+    }
+
+    #[test]
+    fn reindex() {
+        let mut dom = dom();
+        dom.enable_indexes();
+
+        let main = dom.root().children()[0];
+        dom.get_by_ref_mut(main).unwrap().name = "Renamed".to_owned();
+        assert!(dom.get_by_name("Main").contains(&main));
+
+        dom.reindex(main);
+        assert!(!dom.get_by_name("Main").contains(&main));
+        assert_eq!(dom.get_by_name("Renamed"), &set(&[main]));
+    }
+
+    #[test]
+    #[should_panic(expected = "indexes are not enabled")]
+    fn lookup_without_indexes() {
+        dom().get_by_class("Script");
+    }
+}
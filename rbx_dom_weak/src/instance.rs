@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+#[cfg(feature = "reflection")]
+use rbx_reflection::ReflectionDatabase;
 use rbx_types::{Ref, Variant};
 
 /**
@@ -175,7 +177,7 @@ impl InstanceBuilder {
 ///
 /// Operations that could affect other instances contained in the
 /// [`WeakDom`][crate::WeakDom] cannot be performed on an `Instance` correctly.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Instance {
     pub(crate) referent: Ref,
     pub(crate) children: Vec<Ref>,
@@ -213,4 +215,62 @@ impl Instance {
     pub fn parent(&self) -> Ref {
         self.parent
     }
+
+    /// Returns the effective value of the property with the given name, using
+    /// the given reflection database to fill in anything that isn't present in
+    /// [`Instance::properties`].
+    ///
+    /// If the property is an alias, it is first resolved to its canonical
+    /// name. The instance's own value is preferred, falling back to the
+    /// default value defined by the instance's class or nearest superclass.
+    /// Returns `None` if neither is available.
+    #[cfg(feature = "reflection")]
+    pub fn get_property_or_default<'a>(
+        &'a self,
+        name: &str,
+        database: &'a ReflectionDatabase<'_>,
+    ) -> Option<&'a Variant> {
+        let canonical = database.canonical_property_name(&self.class, name);
+
+        self.properties
+            .get(canonical)
+            .or_else(|| self.properties.get(name))
+            .or_else(|| database.find_default_property(&self.class, canonical))
+    }
+}
+
+#[cfg(all(test, feature = "reflection"))]
+mod test {
+    use super::*;
+
+    use crate::WeakDom;
+
+    #[test]
+    fn get_property_or_default() {
+        let database = rbx_reflection_database::get();
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Part")
+                .with_property("Anchored", true)
+                .with_property("Custom", 5.0f32),
+        );
+        let part = dom.root();
+
+        assert_eq!(
+            part.get_property_or_default("Anchored", database),
+            Some(&Variant::Bool(true))
+        );
+        assert_eq!(
+            part.get_property_or_default("Locked", database),
+            Some(&Variant::Bool(false))
+        );
+        assert_eq!(
+            part.get_property_or_default("size", database),
+            database.find_default_property("Part", "Size")
+        );
+        assert_eq!(
+            part.get_property_or_default("Custom", database),
+            Some(&Variant::Float32(5.0))
+        );
+        assert_eq!(part.get_property_or_default("Nonexistent", database), None);
+    }
 }
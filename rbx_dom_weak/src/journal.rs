@@ -0,0 +1,562 @@
+//! An optional journal of changes made to a [`WeakDom`], supporting undo, redo,
+//! and syncing changes to an external consumer.
+
+use rbx_types::{Ref, Variant};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diff::{snapshot_to_builder, InstanceSnapshot},
+    WeakDom,
+};
+
+/// A single reversible change made to a [`WeakDom`] while its journal was
+/// enabled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DomChange {
+    /// A subtree was inserted into the DOM.
+    #[serde(rename_all = "camelCase")]
+    Added {
+        /// The referent of the new parent of the subtree. This is the none
+        /// Ref for instances inserted without a parent.
+        parent: Ref,
+
+        /// The position of the subtree in its parent's list of children.
+        index: usize,
+
+        /// The inserted subtree.
+        instance: InstanceSnapshot,
+    },
+
+    /// A subtree was removed from the DOM.
+    #[serde(rename_all = "camelCase")]
+    Removed {
+        /// The referent of the parent the subtree was removed from.
+        parent: Ref,
+
+        /// The position the subtree had in its parent's list of children.
+        index: usize,
+
+        /// The removed subtree.
+        instance: InstanceSnapshot,
+    },
+
+    /// An instance was moved to a new parent.
+    #[serde(rename_all = "camelCase")]
+    Moved {
+        /// The referent of the moved instance.
+        referent: Ref,
+
+        /// The referent of the instance's previous parent.
+        old_parent: Ref,
+
+        /// The position the instance had in its previous parent's list of
+        /// children.
+        old_index: usize,
+
+        /// The referent of the instance's new parent.
+        new_parent: Ref,
+
+        /// The position of the instance in its new parent's list of children.
+        new_index: usize,
+    },
+
+    /// A property was set or removed.
+    #[serde(rename_all = "camelCase")]
+    Property {
+        /// The referent of the changed instance.
+        referent: Ref,
+
+        /// The name of the property.
+        name: String,
+
+        /// The previous value of the property, if it was set.
+        old: Option<Variant>,
+
+        /// The new value of the property, if it is set.
+        new: Option<Variant>,
+    },
+
+    /// An instance was renamed.
+    #[serde(rename_all = "camelCase")]
+    Name {
+        /// The referent of the renamed instance.
+        referent: Ref,
+
+        /// The instance's previous name.
+        old: String,
+
+        /// The instance's new name.
+        new: String,
+    },
+
+    /// An instance's class was changed.
+    #[serde(rename_all = "camelCase")]
+    Class {
+        /// The referent of the changed instance.
+        referent: Ref,
+
+        /// The instance's previous class.
+        old: String,
+
+        /// The instance's new class.
+        new: String,
+    },
+}
+
+impl DomChange {
+    /// Returns the change that reverses this one.
+    pub fn inverse(&self) -> DomChange {
+        match self.clone() {
+            DomChange::Added {
+                parent,
+                index,
+                instance,
+            } => DomChange::Removed {
+                parent,
+                index,
+                instance,
+            },
+            DomChange::Removed {
+                parent,
+                index,
+                instance,
+            } => DomChange::Added {
+                parent,
+                index,
+                instance,
+            },
+            DomChange::Moved {
+                referent,
+                old_parent,
+                old_index,
+                new_parent,
+                new_index,
+            } => DomChange::Moved {
+                referent,
+                old_parent: new_parent,
+                old_index: new_index,
+                new_parent: old_parent,
+                new_index: old_index,
+            },
+            DomChange::Property {
+                referent,
+                name,
+                old,
+                new,
+            } => DomChange::Property {
+                referent,
+                name,
+                old: new,
+                new: old,
+            },
+            DomChange::Name { referent, old, new } => DomChange::Name {
+                referent,
+                old: new,
+                new: old,
+            },
+            DomChange::Class { referent, old, new } => DomChange::Class {
+                referent,
+                old: new,
+                new: old,
+            },
+        }
+    }
+}
+
+/// The state of a `WeakDom`'s journal.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Journal {
+    /// Groups of changes that can be undone, most recent last.
+    undo: Vec<Vec<DomChange>>,
+
+    /// Groups of changes that were undone and can be redone, most recently
+    /// undone last.
+    redo: Vec<Vec<DomChange>>,
+
+    /// Every change applied to the DOM since the last call to
+    /// `WeakDom::take_changes`, including undos and redos.
+    pending: Vec<DomChange>,
+
+    /// The changes made in the current transaction, if there is one.
+    transaction: Option<Vec<DomChange>>,
+}
+
+impl Journal {
+    fn record(&mut self, change: DomChange) {
+        self.pending.push(change.clone());
+        self.redo.clear();
+
+        match &mut self.transaction {
+            Some(transaction) => transaction.push(change),
+            None => self.undo.push(vec![change]),
+        }
+    }
+}
+
+impl WeakDom {
+    /// Starts recording changes made to the `WeakDom` through its methods,
+    /// which allows them to be undone and redone, or retrieved with
+    /// [`WeakDom::take_changes`]. Does nothing if the journal is already
+    /// enabled.
+    ///
+    /// Changes made directly to an [`Instance`][crate::Instance] obtained from
+    /// [`WeakDom::get_by_ref_mut`] are not recorded. Use methods like
+    /// [`WeakDom::set_property`] and [`WeakDom::set_name`] instead.
+    pub fn enable_journal(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal::default());
+        }
+    }
+
+    /// Stops recording changes made to the `WeakDom` and discards any history
+    /// and pending changes.
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Returns whether changes made to the `WeakDom` are being recorded.
+    pub fn is_journal_enabled(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Runs the given function, grouping every change it makes into a single
+    /// step that is undone and redone as a unit. Transactions can be nested, in
+    /// which case the inner transaction becomes part of the outer one.
+    pub fn transaction<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut WeakDom) -> R,
+    {
+        let outer = match &mut self.journal {
+            Some(journal) => journal.transaction.replace(Vec::new()),
+            None => return f(self),
+        };
+
+        let result = f(self);
+
+        if let Some(journal) = &mut self.journal {
+            let changes = journal.transaction.take().unwrap_or_default();
+
+            match outer {
+                Some(mut outer) => {
+                    outer.extend(changes);
+                    journal.transaction = Some(outer);
+                }
+                None if !changes.is_empty() => journal.undo.push(changes),
+                None => {}
+            }
+        }
+
+        result
+    }
+
+    /// Returns whether there are any changes that can be undone.
+    pub fn can_undo(&self) -> bool {
+        self.journal
+            .as_ref()
+            .is_some_and(|journal| !journal.undo.is_empty())
+    }
+
+    /// Returns whether there are any changes that can be redone.
+    pub fn can_redo(&self) -> bool {
+        self.journal
+            .as_ref()
+            .is_some_and(|journal| !journal.redo.is_empty())
+    }
+
+    /// Reverts the most recent step recorded in the journal. Returns `false` if
+    /// there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let changes = match self.journal.as_mut().and_then(|journal| journal.undo.pop()) {
+            Some(changes) => changes,
+            None => return false,
+        };
+
+        for change in changes.iter().rev() {
+            self.replay(change.inverse());
+        }
+
+        self.journal.as_mut().unwrap().redo.push(changes);
+        true
+    }
+
+    /// Reapplies the most recently undone step. Returns `false` if there was
+    /// nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let changes = match self.journal.as_mut().and_then(|journal| journal.redo.pop()) {
+            Some(changes) => changes,
+            None => return false,
+        };
+
+        for change in &changes {
+            self.replay(change.clone());
+        }
+
+        self.journal.as_mut().unwrap().undo.push(changes);
+        true
+    }
+
+    /// Returns every change made to the `WeakDom` since the last time this
+    /// method was called, including changes made by undoing and redoing. This
+    /// is useful for keeping an external copy of the DOM in sync.
+    ///
+    /// Returns an empty list if the journal is not enabled.
+    pub fn take_changes(&mut self) -> Vec<DomChange> {
+        self.journal
+            .as_mut()
+            .map(|journal| std::mem::take(&mut journal.pending))
+            .unwrap_or_default()
+    }
+
+    /// Records a change in the journal, if it's enabled.
+    pub(crate) fn record(&mut self, change: DomChange) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(change);
+        }
+    }
+
+    /// Applies a change without adding it to the undo history, then adds it to
+    /// the list of pending changes.
+    fn replay(&mut self, change: DomChange) {
+        let journal = self.journal.take();
+
+        match &change {
+            DomChange::Added {
+                parent,
+                index,
+                instance,
+            } => {
+                let builder = snapshot_to_builder(instance);
+                let referent = self.insert(*parent, builder);
+                self.set_child_index(referent, *index);
+            }
+            DomChange::Removed { instance, .. } => self.destroy(instance.referent),
+            DomChange::Moved {
+                referent,
+                new_parent,
+                new_index,
+                ..
+            } => {
+                self.transfer_within(*referent, *new_parent);
+                self.set_child_index(*referent, *new_index);
+            }
+            DomChange::Property {
+                referent,
+                name,
+                new,
+                ..
+            } => match new {
+                Some(value) => {
+                    self.set_property(*referent, name.as_str(), value.clone());
+                }
+                None => {
+                    self.remove_property(*referent, name);
+                }
+            },
+            DomChange::Name { referent, new, .. } => {
+                self.set_name(*referent, new.as_str());
+            }
+            DomChange::Class { referent, new, .. } => {
+                self.set_class(*referent, new.as_str());
+            }
+        }
+
+        self.journal = journal;
+
+        if let Some(journal) = &mut self.journal {
+            journal.pending.push(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{diff, InstanceBuilder};
+
+    fn dom() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part").with_name("A"))
+                .with_child(InstanceBuilder::new("Part").with_name("B"))
+                .with_child(InstanceBuilder::new("Model").with_name("C")),
+        )
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut dom = dom();
+        let a = dom.find_by_path("A").unwrap();
+        dom.set_name(a, "Renamed");
+
+        assert!(!dom.is_journal_enabled());
+        assert!(!dom.can_undo());
+        assert!(!dom.undo());
+        assert!(dom.take_changes().is_empty());
+    }
+
+    #[test]
+    fn undo_redo() {
+        let original = dom();
+        let mut dom = original.clone();
+        dom.enable_journal();
+
+        let a = dom.find_by_path("A").unwrap();
+        let b = dom.find_by_path("B").unwrap();
+        let c = dom.find_by_path("C").unwrap();
+
+        dom.set_property(a, "Anchored", true);
+        dom.set_name(b, "Renamed");
+        dom.set_class(c, "Folder");
+        dom.transfer_within(a, c);
+        let inserted = dom.insert(
+            c,
+            InstanceBuilder::new("Model").with_child(InstanceBuilder::new("Part")),
+        );
+        dom.destroy(b);
+
+        let edited = dom.clone();
+
+        while dom.undo() {}
+        assert!(diff(&original, &dom).is_empty());
+        assert_eq!(dom.root().children(), original.root().children());
+        assert!(dom.get_by_ref(inserted).is_none());
+        assert!(dom.can_redo());
+
+        while dom.redo() {}
+        assert!(diff(&edited, &dom).is_empty());
+        assert_eq!(dom.get_by_ref(inserted).unwrap().children().len(), 1);
+    }
+
+    #[test]
+    fn destroy_restores_position() {
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let children = dom.root().children().to_vec();
+        dom.destroy(children[1]);
+        dom.undo();
+
+        assert_eq!(dom.root().children(), children.as_slice());
+    }
+
+    #[test]
+    fn transactions() {
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let a = dom.find_by_path("A").unwrap();
+        let b = dom.find_by_path("B").unwrap();
+
+        dom.transaction(|dom| {
+            dom.set_name(a, "One");
+            dom.transaction(|dom| dom.set_name(b, "Two"));
+        });
+        dom.set_name(a, "Three");
+
+        dom.undo();
+        assert_eq!(dom.get_by_ref(a).unwrap().name, "One");
+        assert_eq!(dom.get_by_ref(b).unwrap().name, "Two");
+
+        dom.undo();
+        assert_eq!(dom.get_by_ref(a).unwrap().name, "A");
+        assert_eq!(dom.get_by_ref(b).unwrap().name, "B");
+        assert!(!dom.can_undo());
+    }
+
+    #[test]
+    fn new_changes_clear_redo() {
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let a = dom.find_by_path("A").unwrap();
+        dom.set_name(a, "One");
+        dom.undo();
+        assert!(dom.can_redo());
+
+        dom.set_name(a, "Two");
+        assert!(!dom.can_redo());
+    }
+
+    #[test]
+    fn take_changes() {
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let a = dom.find_by_path("A").unwrap();
+        dom.set_property(a, "Anchored", true);
+        dom.remove_property(a, "Anchored");
+        dom.undo();
+
+        let change = DomChange::Property {
+            referent: a,
+            name: "Anchored".to_owned(),
+            old: Some(Variant::Bool(true)),
+            new: None,
+        };
+        assert_eq!(
+            dom.take_changes(),
+            [
+                DomChange::Property {
+                    referent: a,
+                    name: "Anchored".to_owned(),
+                    old: None,
+                    new: Some(Variant::Bool(true)),
+                },
+                change.clone(),
+                change.inverse(),
+            ]
+        );
+        assert!(dom.take_changes().is_empty());
+    }
+
+    #[test]
+    fn clones_are_recorded_once() {
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let c = dom.find_by_path("C").unwrap();
+        let a = dom.find_by_path("A").unwrap();
+        dom.transfer_within(a, c);
+        dom.take_changes();
+
+        let clone = dom.clone_within(c);
+        let changes = dom.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(
+            &changes[0],
+            DomChange::Added { parent, instance, .. }
+                if parent.is_none() && instance.referent == clone && instance.children.len() == 1
+        ));
+
+        dom.undo();
+        assert!(dom.get_by_ref(clone).is_none());
+    }
+
+    #[test]
+    fn large_depth_tree() {
+        // Journaled inserts and removals capture a snapshot of the whole
+        // subtree, which used to overflow the stack for deep trees. See
+        // WeakDom's test of the same name.
+        const N: usize = i16::MAX as usize;
+
+        let mut base = InstanceBuilder::new("Folder");
+        for _ in 0..N {
+            base = InstanceBuilder::new("Folder").with_child(base);
+        }
+
+        let mut dom = dom();
+        dom.enable_journal();
+
+        let root = dom.root_ref();
+        let deep = dom.insert(root, base);
+        dom.destroy(deep);
+        assert_eq!(dom.take_changes().len(), 2);
+
+        assert!(dom.undo());
+        assert_eq!(dom.descendants(deep).count(), N);
+        assert!(dom.redo());
+        assert!(dom.get_by_ref(deep).is_none());
+    }
+}
@@ -41,14 +41,33 @@
 
 #![deny(missing_docs)]
 
+mod compare;
+mod diff;
 mod dom;
+mod index;
 mod instance;
+mod journal;
+mod merge;
+mod path;
+mod refs;
+#[cfg(feature = "reflection")]
+mod validate;
 mod viewer;
 
 pub use rbx_types as types;
 
 pub use crate::{
-    dom::WeakDom,
+    compare::{subtree_eq, subtree_hash},
+    diff::{
+        apply_patch, diff, AddedInstance, ChangedInstance, DomPatch, InstanceSnapshot,
+        MovedInstance, PatchError, PropertyChange, RemovedInstance,
+    },
+    dom::{Ancestors, AncestorsMut, Descendants, DescendantsDfs, DescendantsMut, WeakDom},
     instance::{Instance, InstanceBuilder},
+    journal::DomChange,
+    merge::{merge, MergeConflict, MergeResult, MergeSide},
     viewer::{DomViewer, ViewedInstance},
 };
+
+#[cfg(feature = "reflection")]
+pub use crate::validate::{validate, ValidationIssue, ValidationReport};
@@ -0,0 +1,686 @@
+//! Three-way merging of `WeakDom` objects.
+//!
+//! Both sides of a merge are diffed against their common base, and the two
+//! resulting patches are combined into one. Whenever the two sides disagree,
+//! the change from "ours" is kept and the disagreement is reported as a
+//! [`MergeConflict`].
+
+use std::collections::{HashMap, HashSet};
+
+use rbx_types::{Ref, Variant};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diff::{
+        apply_patch, diff, AddedInstance, DomPatch, InstanceSnapshot, MovedInstance, PatchError,
+    },
+    WeakDom,
+};
+
+/// Performs a three-way merge of two `WeakDom` objects that were both derived
+/// from `base`.
+///
+/// Changes made on only one side are always kept. When both sides make
+/// conflicting changes, the change from `ours` wins and a [`MergeConflict`]
+/// is recorded.
+///
+/// Referents in the merged DOM and in any conflicts refer to instances in
+/// `base`, or to instances in `ours` or `theirs` for instances that were added
+/// by that side. If both sides added the same instance, it is only added once.
+///
+/// Returns an error if the combined changes can't be applied to `base`.
+pub fn merge(base: &WeakDom, ours: &WeakDom, theirs: &WeakDom) -> Result<MergeResult, PatchError> {
+    let ours_patch = diff(base, ours);
+    let theirs_patch = diff(base, theirs);
+
+    let mut merger = Merger::new(base, &ours_patch, &theirs_patch);
+    let patch = merger.merge();
+
+    let mut dom = base.clone();
+    apply_patch(&mut dom, &patch)?;
+
+    Ok(MergeResult {
+        dom,
+        conflicts: merger.conflicts,
+    })
+}
+
+/// The outcome of a three-way [`merge`].
+#[derive(Debug)]
+pub struct MergeResult {
+    /// The merged DOM.
+    pub dom: WeakDom,
+
+    /// Any conflicts between the two sides. These were resolved in favor of
+    /// "ours".
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// One side of a three-way merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeSide {
+    /// The side whose changes win conflicts.
+    Ours,
+
+    /// The side whose changes lose conflicts.
+    Theirs,
+}
+
+/// A disagreement between the two sides of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum MergeConflict {
+    /// Both sides changed the same property of an instance to different
+    /// values.
+    #[serde(rename_all = "camelCase")]
+    Property {
+        /// The referent of the instance that was changed.
+        referent: Ref,
+
+        /// The name of the property.
+        name: String,
+
+        /// The value that "ours" changed the property to.
+        ours: Option<Variant>,
+
+        /// The value that "theirs" changed the property to.
+        theirs: Option<Variant>,
+    },
+
+    /// Both sides moved an instance, but to different parents, or the move
+    /// made by "theirs" would have made the instance a descendant of itself.
+    #[serde(rename_all = "camelCase")]
+    Parent {
+        /// The referent of the instance that was moved.
+        referent: Ref,
+
+        /// The parent of the instance according to "ours".
+        ours: Ref,
+
+        /// The parent that "theirs" moved the instance to.
+        theirs: Ref,
+    },
+
+    /// One side removed an instance, while the other side changed it or
+    /// something inside of it.
+    #[serde(rename_all = "camelCase")]
+    Removed {
+        /// The referent of the root of the removed subtree.
+        referent: Ref,
+
+        /// The side that removed the instance.
+        removed_by: MergeSide,
+    },
+
+    /// Both sides added instances with the same referent, but not the same
+    /// subtree in the same place. The subtree added by "theirs" was left out.
+    #[serde(rename_all = "camelCase")]
+    Added {
+        /// The referent of the root of the subtree added by "theirs".
+        referent: Ref,
+    },
+}
+
+struct Merger<'a> {
+    base: &'a WeakDom,
+    ours: &'a DomPatch,
+    theirs: &'a DomPatch,
+
+    /// The parent of every instance added by either side. If both sides
+    /// added the same referent, the parent from "ours" is used.
+    added_parents: HashMap<Ref, Ref>,
+
+    /// Instances added by "theirs" that were left out of the merge because
+    /// they conflicted with instances added by "ours".
+    dropped_additions: HashSet<Ref>,
+
+    conflicts: Vec<MergeConflict>,
+}
+
+impl<'a> Merger<'a> {
+    fn new(base: &'a WeakDom, ours: &'a DomPatch, theirs: &'a DomPatch) -> Self {
+        let mut added_parents = HashMap::new();
+
+        for added in ours.added.iter().chain(&theirs.added) {
+            collect_parents(added.parent, &added.instance, &mut added_parents);
+        }
+
+        Merger {
+            base,
+            ours,
+            theirs,
+            added_parents,
+            dropped_additions: HashSet::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn merge(&mut self) -> DomPatch {
+        let ours_removed = self.removed_instances(self.ours);
+
+        // If "theirs" touched anything that "ours" removed, we drop those
+        // changes. If "ours" touched anything that "theirs" removed, we keep
+        // that subtree around instead.
+        let mut kept_removals = HashSet::new();
+
+        for removed in &self.ours.removed {
+            if self.touches_subtree(self.theirs, removed.referent) {
+                self.conflicts.push(MergeConflict::Removed {
+                    referent: removed.referent,
+                    removed_by: MergeSide::Ours,
+                });
+            }
+        }
+
+        for removed in &self.theirs.removed {
+            if self.touches_subtree(self.ours, removed.referent) {
+                self.conflicts.push(MergeConflict::Removed {
+                    referent: removed.referent,
+                    removed_by: MergeSide::Theirs,
+                });
+                kept_removals.insert(removed.referent);
+            }
+        }
+
+        let mut patch = self.ours.clone();
+
+        self.merge_additions(&mut patch, &ours_removed);
+
+        patch.removed.extend(
+            self.theirs
+                .removed
+                .iter()
+                .filter(|removed| {
+                    !kept_removals.contains(&removed.referent)
+                        && !ours_removed.contains(&removed.referent)
+                })
+                .cloned(),
+        );
+
+        self.merge_moves(&mut patch, &ours_removed);
+        self.merge_changes(&mut patch, &ours_removed);
+
+        patch
+    }
+
+    fn merge_additions(&mut self, patch: &mut DomPatch, ours_removed: &HashSet<Ref>) {
+        let ours_added: HashMap<Ref, &AddedInstance> = self
+            .ours
+            .added
+            .iter()
+            .map(|added| (added.instance.referent, added))
+            .collect();
+
+        let mut ours_subtrees = HashSet::new();
+        for added in &self.ours.added {
+            ours_subtrees.extend(subtree_referents(&added.instance));
+        }
+
+        for added in &self.theirs.added {
+            if ours_removed.contains(&added.parent) {
+                continue;
+            }
+
+            let referents = subtree_referents(&added.instance);
+
+            if !referents
+                .iter()
+                .any(|referent| ours_subtrees.contains(referent))
+            {
+                patch.added.push(added.clone());
+                continue;
+            }
+
+            // Both sides may have made the same addition, like when one side
+            // started out as a copy of the other.
+            let same = match ours_added.get(&added.instance.referent) {
+                Some(ours) => ours.parent == added.parent && ours.instance == added.instance,
+                None => false,
+            };
+
+            if !same {
+                self.conflicts.push(MergeConflict::Added {
+                    referent: added.instance.referent,
+                });
+                self.dropped_additions.extend(
+                    referents
+                        .into_iter()
+                        .filter(|referent| !ours_subtrees.contains(referent)),
+                );
+            }
+        }
+    }
+
+    fn merge_moves(&mut self, patch: &mut DomPatch, ours_removed: &HashSet<Ref>) {
+        let ours_moves: HashMap<Ref, Ref> = self
+            .ours
+            .moved
+            .iter()
+            .map(|moved| (moved.referent, moved.new_parent))
+            .collect();
+
+        let mut new_parents = ours_moves.clone();
+
+        for moved in &self.theirs.moved {
+            if ours_removed.contains(&moved.referent)
+                || ours_removed.contains(&self.anchor(moved.new_parent))
+                || self.dropped_additions.contains(&moved.new_parent)
+            {
+                continue;
+            }
+
+            if let Some(&ours_parent) = ours_moves.get(&moved.referent) {
+                if ours_parent != moved.new_parent {
+                    self.conflicts.push(MergeConflict::Parent {
+                        referent: moved.referent,
+                        ours: ours_parent,
+                        theirs: moved.new_parent,
+                    });
+                }
+
+                continue;
+            }
+
+            if self.would_cycle(moved, &new_parents) {
+                self.conflicts.push(MergeConflict::Parent {
+                    referent: moved.referent,
+                    ours: moved.old_parent,
+                    theirs: moved.new_parent,
+                });
+
+                continue;
+            }
+
+            new_parents.insert(moved.referent, moved.new_parent);
+            patch.moved.push(moved.clone());
+        }
+    }
+
+    fn merge_changes(&mut self, patch: &mut DomPatch, ours_removed: &HashSet<Ref>) {
+        let mut changed_index: HashMap<Ref, usize> = patch
+            .changed
+            .iter()
+            .enumerate()
+            .map(|(index, changed)| (changed.referent, index))
+            .collect();
+
+        for changed in &self.theirs.changed {
+            if ours_removed.contains(&changed.referent) {
+                continue;
+            }
+
+            let index = match changed_index.get(&changed.referent) {
+                Some(&index) => index,
+                None => {
+                    changed_index.insert(changed.referent, patch.changed.len());
+                    patch.changed.push(changed.clone());
+                    continue;
+                }
+            };
+
+            let ours_changes = &mut patch.changed[index].changes;
+
+            for change in &changed.changes {
+                match ours_changes.iter().find(|ours| ours.name == change.name) {
+                    Some(ours) => {
+                        if ours.new != change.new {
+                            self.conflicts.push(MergeConflict::Property {
+                                referent: changed.referent,
+                                name: change.name.clone(),
+                                ours: ours.new.clone(),
+                                theirs: change.new.clone(),
+                            });
+                        }
+                    }
+                    None => ours_changes.push(change.clone()),
+                }
+            }
+
+            ours_changes.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+
+    /// Returns the referents of every instance in `base` that is removed by
+    /// the given patch.
+    fn removed_instances(&self, patch: &DomPatch) -> HashSet<Ref> {
+        patch
+            .removed
+            .iter()
+            .flat_map(|removed| self.subtree(removed.referent))
+            .collect()
+    }
+
+    fn subtree(&self, referent: Ref) -> impl Iterator<Item = Ref> + '_ {
+        std::iter::once(referent).chain(
+            self.base
+                .descendants(referent)
+                .map(|instance| instance.referent()),
+        )
+    }
+
+    /// Returns the instance in `base` that the given referent is attached to.
+    /// For instances in `base`, this is the instance itself. For added
+    /// instances, it is the instance in `base` that they were added under.
+    fn anchor(&self, mut referent: Ref) -> Ref {
+        while let Some(&parent) = self.added_parents.get(&referent) {
+            referent = parent;
+        }
+
+        referent
+    }
+
+    /// Returns whether the given patch changes anything inside of the subtree
+    /// rooted at `root` in `base`, other than removing more of it.
+    fn touches_subtree(&self, patch: &DomPatch, root: Ref) -> bool {
+        let inside = |referent: Ref| self.is_within(self.anchor(referent), root);
+
+        patch.added.iter().any(|added| inside(added.parent))
+            || patch
+                .moved
+                .iter()
+                .any(|moved| inside(moved.referent) || inside(moved.new_parent))
+            || patch.changed.iter().any(|changed| inside(changed.referent))
+    }
+
+    /// Returns whether `referent` is `root` or one of its descendants in
+    /// `base`.
+    fn is_within(&self, referent: Ref, root: Ref) -> bool {
+        referent == root
+            || self
+                .base
+                .ancestors(referent)
+                .any(|ancestor| ancestor.referent() == root)
+    }
+
+    /// Returns whether applying the given move on top of the moves we've
+    /// already accepted would make an instance a descendant of itself.
+    fn would_cycle(&self, moved: &MovedInstance, new_parents: &HashMap<Ref, Ref>) -> bool {
+        let mut visited = HashSet::new();
+        let mut current = moved.new_parent;
+
+        while current.is_some() {
+            if current == moved.referent || !visited.insert(current) {
+                return true;
+            }
+
+            current = match new_parents.get(&current) {
+                Some(&parent) => parent,
+                None => match self.added_parents.get(&current) {
+                    Some(&parent) => parent,
+                    None => self
+                        .base
+                        .get_by_ref(current)
+                        .map(|instance| instance.parent())
+                        .unwrap_or_else(Ref::none),
+                },
+            };
+        }
+
+        false
+    }
+}
+
+fn collect_parents(parent: Ref, snapshot: &InstanceSnapshot, parents: &mut HashMap<Ref, Ref>) {
+    let mut stack = vec![(parent, snapshot)];
+
+    while let Some((parent, snapshot)) = stack.pop() {
+        parents.entry(snapshot.referent).or_insert(parent);
+        stack.extend(
+            snapshot
+                .children
+                .iter()
+                .map(|child| (snapshot.referent, child)),
+        );
+    }
+}
+
+/// Returns the referent of every instance in a snapshot.
+fn subtree_referents(snapshot: &InstanceSnapshot) -> Vec<Ref> {
+    let mut referents = Vec::new();
+    let mut stack = vec![snapshot];
+
+    while let Some(snapshot) = stack.pop() {
+        referents.push(snapshot.referent);
+        stack.extend(&snapshot.children);
+    }
+
+    referents
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    fn base() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("Workspace")
+                    .with_child(
+                        InstanceBuilder::new("Part")
+                            .with_name("Floor")
+                            .with_property("Anchored", true)
+                            .with_property("Transparency", 0.0f32),
+                    )
+                    .with_child(
+                        InstanceBuilder::new("Model")
+                            .with_name("House")
+                            .with_child(InstanceBuilder::new("Part").with_name("Door")),
+                    )
+                    .with_child(InstanceBuilder::new("Folder").with_name("Props")),
+            ),
+        )
+    }
+
+    fn set(dom: &mut WeakDom, path: &str, name: &str, value: impl Into<Variant>) {
+        let referent = dom.find_by_path(path).unwrap();
+        dom.get_by_ref_mut(referent)
+            .unwrap()
+            .properties
+            .insert(name.to_owned(), value.into());
+    }
+
+    #[test]
+    fn independent_changes() {
+        let base = base();
+
+        let mut ours = base.clone();
+        set(&mut ours, "Workspace.Floor", "Anchored", false);
+        let workspace = ours.find_by_path("Workspace").unwrap();
+        ours.insert(workspace, InstanceBuilder::new("Part").with_name("Ours"));
+
+        let mut theirs = base.clone();
+        set(&mut theirs, "Workspace.Floor", "Transparency", 0.5f32);
+        let door = theirs.find_by_path("Workspace.House.Door").unwrap();
+        let props = theirs.find_by_path("Workspace.Props").unwrap();
+        theirs.transfer_within(door, props);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+
+        let dom = &result.dom;
+        let floor = dom
+            .get_by_ref(dom.find_by_path("Workspace.Floor").unwrap())
+            .unwrap();
+        assert_eq!(floor.properties["Anchored"], Variant::Bool(false));
+        assert_eq!(floor.properties["Transparency"], Variant::Float32(0.5));
+        assert!(dom.find_by_path("Workspace.Ours").is_some());
+        assert!(dom.find_by_path("Workspace.Props.Door").is_some());
+        assert!(dom.find_by_path("Workspace.House.Door").is_none());
+    }
+
+    #[test]
+    fn property_conflict() {
+        let base = base();
+
+        let mut ours = base.clone();
+        set(&mut ours, "Workspace.Floor", "Transparency", 0.25f32);
+
+        let mut theirs = base.clone();
+        set(&mut theirs, "Workspace.Floor", "Transparency", 0.75f32);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(
+            result.conflicts,
+            [MergeConflict::Property {
+                referent: base.find_by_path("Workspace.Floor").unwrap(),
+                name: "Transparency".to_owned(),
+                ours: Some(Variant::Float32(0.25)),
+                theirs: Some(Variant::Float32(0.75)),
+            }]
+        );
+
+        let floor = result
+            .dom
+            .get_by_ref(base.find_by_path("Workspace.Floor").unwrap())
+            .unwrap();
+        assert_eq!(floor.properties["Transparency"], Variant::Float32(0.25));
+    }
+
+    #[test]
+    fn same_change_on_both_sides() {
+        let base = base();
+
+        let mut ours = base.clone();
+        set(&mut ours, "Workspace.Floor", "Anchored", false);
+        let theirs = ours.clone();
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn same_addition_on_both_sides() {
+        let base = base();
+
+        let mut ours = base.clone();
+        let workspace = ours.find_by_path("Workspace").unwrap();
+        let tree = ours.insert(
+            workspace,
+            InstanceBuilder::new("Model")
+                .with_name("Tree")
+                .with_child(InstanceBuilder::new("Part").with_name("Trunk")),
+        );
+        let theirs = ours.clone();
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+
+        let dom = &result.dom;
+        let workspace = dom
+            .get_by_ref(dom.find_by_path("Workspace").unwrap())
+            .unwrap();
+        assert_eq!(workspace.children().len(), 4);
+        assert_eq!(dom.find_by_path("Workspace.Tree").unwrap(), tree);
+        assert!(dom.find_by_path("Workspace.Tree.Trunk").is_some());
+    }
+
+    #[test]
+    fn addition_conflict() {
+        let base = base();
+
+        let mut ours = base.clone();
+        let workspace = ours.find_by_path("Workspace").unwrap();
+        let tree = ours.insert(workspace, InstanceBuilder::new("Model").with_name("Tree"));
+
+        let mut theirs = ours.clone();
+        theirs.insert(tree, InstanceBuilder::new("Part").with_name("Trunk"));
+        let props = theirs.find_by_path("Workspace.Props").unwrap();
+        let trunk = theirs.find_by_path("Workspace.Tree.Trunk").unwrap();
+        theirs.transfer_within(props, trunk);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts, [MergeConflict::Added { referent: tree }]);
+
+        // The move into the left-out Trunk is dropped along with it.
+        let dom = &result.dom;
+        let workspace = dom
+            .get_by_ref(dom.find_by_path("Workspace").unwrap())
+            .unwrap();
+        assert_eq!(workspace.children().len(), 4);
+        assert!(dom.find_by_path("Workspace.Tree.Trunk").is_none());
+        assert!(dom.find_by_path("Workspace.Props").is_some());
+    }
+
+    #[test]
+    fn removed_by_ours() {
+        let base = base();
+
+        let mut ours = base.clone();
+        let house = ours.find_by_path("Workspace.House").unwrap();
+        ours.destroy(house);
+
+        let mut theirs = base.clone();
+        set(&mut theirs, "Workspace.House.Door", "Locked", true);
+        let house = theirs.find_by_path("Workspace.House").unwrap();
+        theirs.insert(house, InstanceBuilder::new("Part").with_name("Window"));
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(
+            result.conflicts,
+            [MergeConflict::Removed {
+                referent: base.find_by_path("Workspace.House").unwrap(),
+                removed_by: MergeSide::Ours,
+            }]
+        );
+        assert!(result.dom.find_by_path("Workspace.House").is_none());
+    }
+
+    #[test]
+    fn removed_by_theirs() {
+        let base = base();
+
+        let mut ours = base.clone();
+        set(&mut ours, "Workspace.House.Door", "Locked", true);
+
+        let mut theirs = base.clone();
+        let house = theirs.find_by_path("Workspace.House").unwrap();
+        theirs.destroy(house);
+        let floor = theirs.find_by_path("Workspace.Floor").unwrap();
+        theirs.destroy(floor);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(
+            result.conflicts,
+            [MergeConflict::Removed {
+                referent: base.find_by_path("Workspace.House").unwrap(),
+                removed_by: MergeSide::Theirs,
+            }]
+        );
+
+        let dom = &result.dom;
+        let door = dom
+            .get_by_ref(dom.find_by_path("Workspace.House.Door").unwrap())
+            .unwrap();
+        assert_eq!(door.properties["Locked"], Variant::Bool(true));
+        assert!(dom.find_by_path("Workspace.Floor").is_none());
+    }
+
+    #[test]
+    fn move_conflicts() {
+        let base = base();
+
+        let mut ours = base.clone();
+        let house = ours.find_by_path("Workspace.House").unwrap();
+        let props = ours.find_by_path("Workspace.Props").unwrap();
+        ours.transfer_within(house, props);
+
+        let mut theirs = base.clone();
+        let props = theirs.find_by_path("Workspace.Props").unwrap();
+        let house = theirs.find_by_path("Workspace.House").unwrap();
+        theirs.transfer_within(props, house);
+
+        let result = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(
+            result.conflicts,
+            [MergeConflict::Parent {
+                referent: base.find_by_path("Workspace.Props").unwrap(),
+                ours: base.find_by_path("Workspace").unwrap(),
+                theirs: base.find_by_path("Workspace.House").unwrap(),
+            }]
+        );
+        assert!(result.dom.find_by_path("Workspace.Props.House").is_some());
+    }
+}
@@ -0,0 +1,89 @@
+//! Parsing and formatting of instance paths like `Workspace.Map.Spawn`.
+//!
+//! Path components are separated by either `.` or `/`. A backslash escapes the
+//! character following it, which allows names containing separators to be
+//! represented, like `ReplicatedStorage/Version\.2`.
+
+/// Splits an instance path into its components, removing any escapes.
+///
+/// An empty path has no components. A trailing backslash with nothing to
+/// escape is kept as-is.
+pub(crate) fn split_path(path: &str) -> Vec<String> {
+    let mut components = Vec::new();
+
+    if path.is_empty() {
+        return components;
+    }
+
+    let mut current = String::new();
+    let mut chars = path.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            '.' | '/' => components.push(std::mem::take(&mut current)),
+            _ => current.push(char),
+        }
+    }
+
+    components.push(current);
+    components
+}
+
+/// Appends `name` to `output`, escaping any characters that would otherwise be
+/// interpreted by [`split_path`].
+pub(crate) fn push_escaped(output: &mut String, name: &str) {
+    for char in name.chars() {
+        if matches!(char, '.' | '/' | '\\') {
+            output.push('\\');
+        }
+
+        output.push(char);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split() {
+        assert_eq!(split_path(""), Vec::<String>::new());
+        assert_eq!(split_path("Workspace"), ["Workspace"]);
+        assert_eq!(
+            split_path("game.Workspace.Map"),
+            ["game", "Workspace", "Map"]
+        );
+        assert_eq!(
+            split_path("Workspace/Map.Spawn"),
+            ["Workspace", "Map", "Spawn"]
+        );
+        assert_eq!(split_path("Workspace..Map"), ["Workspace", "", "Map"]);
+    }
+
+    #[test]
+    fn split_escapes() {
+        assert_eq!(split_path(r"Version\.2.Foo"), ["Version.2", "Foo"]);
+        assert_eq!(split_path(r"a\/b\\c"), [r"a/b\c"]);
+        assert_eq!(split_path(r"trailing\"), [r"trailing\"]);
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        let names = ["plain", "with.dot", "with/slash", r"with\backslash", ""];
+
+        let mut path = String::new();
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                path.push('.');
+            }
+
+            push_escaped(&mut path, name);
+        }
+
+        assert_eq!(split_path(&path), names);
+    }
+}
@@ -0,0 +1,393 @@
+//! Optional tracking of `Ref` properties, which allows a [`WeakDom`] to find
+//! every property that points at an instance and to clear those properties
+//! when the instance is destroyed.
+
+use std::collections::{HashMap, HashSet};
+
+use rbx_types::{Ref, Variant};
+
+use crate::{Instance, WeakDom};
+
+/// Lookup table from referents to the `Ref` properties that point at them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RefTracker {
+    /// The instance and property name of every `Ref` property pointing at
+    /// each referent. Referents that no longer exist are kept here until
+    /// nothing points at them.
+    referrers: HashMap<Ref, HashSet<(Ref, String)>>,
+
+    /// The referent of every instance with a `Ref` property of each name,
+    /// paired with the referent that property points at.
+    by_property: HashMap<String, HashSet<(Ref, Ref)>>,
+
+    /// The `Ref` properties each instance was tracked with, for the same
+    /// reason as `Indexes::keys`.
+    keys: HashMap<Ref, Vec<(String, Ref)>>,
+
+    /// Returned from lookups with no matching properties.
+    empty: HashSet<(Ref, String)>,
+    empty_pairs: HashSet<(Ref, Ref)>,
+}
+
+impl RefTracker {
+    pub(crate) fn insert(&mut self, instance: &Instance) {
+        let referent = instance.referent();
+        self.remove(referent);
+
+        let mut keys = Vec::new();
+
+        for (name, value) in &instance.properties {
+            if let Variant::Ref(target) = value {
+                if target.is_some() {
+                    self.referrers
+                        .entry(*target)
+                        .or_default()
+                        .insert((referent, name.clone()));
+                    self.by_property
+                        .entry(name.clone())
+                        .or_default()
+                        .insert((referent, *target));
+                    keys.push((name.clone(), *target));
+                }
+            }
+        }
+
+        if !keys.is_empty() {
+            self.keys.insert(referent, keys);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, referent: Ref) {
+        if let Some(keys) = self.keys.remove(&referent) {
+            for (name, target) in keys {
+                if let Some(pairs) = self.by_property.get_mut(&name) {
+                    pairs.remove(&(referent, target));
+
+                    if pairs.is_empty() {
+                        self.by_property.remove(&name);
+                    }
+                }
+
+                if let Some(referrers) = self.referrers.get_mut(&target) {
+                    referrers.remove(&(referent, name));
+
+                    if referrers.is_empty() {
+                        self.referrers.remove(&target);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn referrers(&self, target: Ref) -> &HashSet<(Ref, String)> {
+        self.referrers.get(&target).unwrap_or(&self.empty)
+    }
+}
+
+impl WeakDom {
+    /// Starts tracking which `Ref` properties point at each instance in the
+    /// `WeakDom`, which makes [`WeakDom::referrers`],
+    /// [`WeakDom::referrers_by_property`], [`WeakDom::refs_by_property`], and
+    /// [`WeakDom::dangling_refs`] available. Does nothing if tracking is
+    /// already enabled.
+    ///
+    /// While tracking is enabled, [`WeakDom::destroy`] sets every `Ref`
+    /// property that points at a destroyed instance to `Ref::none()`, so that
+    /// properties like `Weld.Part0` or `Model.PrimaryPart` are never left
+    /// pointing at an instance that doesn't exist.
+    ///
+    /// Tracking is kept up to date by methods on `WeakDom`, like
+    /// [`WeakDom::insert`], [`WeakDom::transfer`], and
+    /// [`WeakDom::set_property`]. If a property is
+    /// modified directly through [`WeakDom::get_by_ref_mut`], call
+    /// [`WeakDom::reindex`] afterwards.
+    pub fn enable_ref_tracking(&mut self) {
+        if self.refs.is_some() {
+            return;
+        }
+
+        let mut refs = RefTracker::default();
+        for instance in self.instances.values() {
+            refs.insert(instance);
+        }
+
+        self.refs = Some(refs);
+    }
+
+    /// Stops tracking `Ref` properties and frees the memory used to do so.
+    pub fn disable_ref_tracking(&mut self) {
+        self.refs = None;
+    }
+
+    /// Returns whether `Ref` properties are being tracked for this `WeakDom`.
+    pub fn has_ref_tracking(&self) -> bool {
+        self.refs.is_some()
+    }
+
+    /// Returns the referent and property name of every `Ref` property that
+    /// points at the instance with the given referent.
+    ///
+    /// ## Panics
+    /// Panics if tracking is not enabled. See [`WeakDom::enable_ref_tracking`].
+    pub fn referrers(&self, referent: Ref) -> &HashSet<(Ref, String)> {
+        self.expect_refs().referrers(referent)
+    }
+
+    /// Returns the referent of every instance whose `Ref` property named
+    /// `property` points at the instance with the given referent, like every
+    /// `Weld` whose `Part0` is a given part.
+    ///
+    /// ## Panics
+    /// Panics if tracking is not enabled. See [`WeakDom::enable_ref_tracking`].
+    pub fn referrers_by_property<'a>(
+        &'a self,
+        referent: Ref,
+        property: &'a str,
+    ) -> impl Iterator<Item = Ref> + 'a {
+        self.referrers(referent)
+            .iter()
+            .filter(move |(_, name)| name == property)
+            .map(|(referrer, _)| *referrer)
+    }
+
+    /// Returns every `Ref` property named `property` that is set to something
+    /// other than `Ref::none()`, as the referent of the instance the property
+    /// belongs to paired with the referent it points at.
+    ///
+    /// ## Panics
+    /// Panics if tracking is not enabled. See [`WeakDom::enable_ref_tracking`].
+    pub fn refs_by_property(&self, property: &str) -> &HashSet<(Ref, Ref)> {
+        let refs = self.expect_refs();
+        refs.by_property.get(property).unwrap_or(&refs.empty_pairs)
+    }
+
+    /// Returns every `Ref` property that points at an instance that isn't in
+    /// the `WeakDom`, as the referent of the instance the property belongs
+    /// to, the name of the property, and the referent it points at.
+    ///
+    /// Destroying an instance never leaves dangling refs behind while
+    /// tracking is enabled, but transferring an instance to another `WeakDom`
+    /// or setting a property to a referent that doesn't exist can.
+    ///
+    /// ## Panics
+    /// Panics if tracking is not enabled. See [`WeakDom::enable_ref_tracking`].
+    pub fn dangling_refs(&self) -> Vec<(Ref, String, Ref)> {
+        let refs = self.expect_refs();
+        let mut dangling = Vec::new();
+
+        for (target, referrers) in &refs.referrers {
+            if !self.instances.contains_key(target) {
+                for (referent, name) in referrers {
+                    dangling.push((*referent, name.clone(), *target));
+                }
+            }
+        }
+
+        dangling
+    }
+
+    /// Sets every `Ref` property pointing at one of the given referents to
+    /// `Ref::none()`. Does nothing if tracking is not enabled.
+    pub(crate) fn clear_refs_to(&mut self, targets: &[Ref]) {
+        let referrers: Vec<(Ref, String)> = match &self.refs {
+            Some(refs) => targets
+                .iter()
+                .flat_map(|&target| refs.referrers(target).iter().cloned())
+                .collect(),
+            None => return,
+        };
+
+        for (referent, name) in referrers {
+            self.set_property(referent, name, Ref::none());
+        }
+    }
+
+    fn expect_refs(&self) -> &RefTracker {
+        self.refs
+            .as_ref()
+            .unwrap_or_else(|| panic!("ref tracking is not enabled for this WeakDom"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    fn set(referrers: &[(Ref, &str)]) -> HashSet<(Ref, String)> {
+        referrers
+            .iter()
+            .map(|(referent, name)| (*referent, name.to_string()))
+            .collect()
+    }
+
+    fn pairs(pairs: &[(Ref, Ref)]) -> HashSet<(Ref, Ref)> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn referrers() {
+        let part = InstanceBuilder::new("Part");
+        let part_ref = part.referent();
+        let weld = InstanceBuilder::new("Weld")
+            .with_property("Part0", part_ref)
+            .with_property("Part1", part_ref);
+        let weld_ref = weld.referent();
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("Model").with_children([part, weld]));
+        dom.enable_ref_tracking();
+
+        let model = dom.root_ref();
+        assert_eq!(
+            dom.referrers(part_ref),
+            &set(&[(weld_ref, "Part0"), (weld_ref, "Part1")])
+        );
+
+        dom.set_property(model, "PrimaryPart", part_ref);
+        dom.set_property(weld_ref, "Part1", Ref::none());
+        assert_eq!(
+            dom.referrers(part_ref),
+            &set(&[(weld_ref, "Part0"), (model, "PrimaryPart")])
+        );
+
+        dom.remove_property(weld_ref, "Part0");
+        dom.get_by_ref_mut(model).unwrap().properties.clear();
+        dom.reindex(model);
+        assert!(dom.referrers(part_ref).is_empty());
+    }
+
+    #[test]
+    fn queries_by_property() {
+        let part = InstanceBuilder::new("Part");
+        let part_ref = part.referent();
+        let other_part = InstanceBuilder::new("Part");
+        let other_part_ref = other_part.referent();
+        let weld = InstanceBuilder::new("Weld")
+            .with_property("Part0", part_ref)
+            .with_property("Part1", other_part_ref);
+        let weld_ref = weld.referent();
+        let value = InstanceBuilder::new("ObjectValue").with_property("Value", part_ref);
+        let value_ref = value.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Model").with_children([part, other_part, weld, value]),
+        );
+        dom.enable_ref_tracking();
+
+        let part0: Vec<Ref> = dom.referrers_by_property(part_ref, "Part0").collect();
+        assert_eq!(part0, vec![weld_ref]);
+        assert_eq!(dom.referrers_by_property(part_ref, "Part1").count(), 0);
+        assert_eq!(
+            dom.refs_by_property("Part1"),
+            &pairs(&[(weld_ref, other_part_ref)])
+        );
+
+        dom.set_property(weld_ref, "Part1", part_ref);
+        assert_eq!(
+            dom.refs_by_property("Part1"),
+            &pairs(&[(weld_ref, part_ref)])
+        );
+
+        // Transferring an instance moves its refs to the other DOM.
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        other.enable_ref_tracking();
+        let other_root = other.root_ref();
+        dom.transfer(value_ref, &mut other, other_root);
+
+        assert!(dom.refs_by_property("Value").is_empty());
+        assert_eq!(
+            other.refs_by_property("Value"),
+            &pairs(&[(value_ref, part_ref)])
+        );
+        assert_eq!(other.referrers(part_ref), &set(&[(value_ref, "Value")]));
+    }
+
+    #[test]
+    fn destroy_clears_refs() {
+        let part = InstanceBuilder::new("Part");
+        let part_ref = part.referent();
+        let weld = InstanceBuilder::new("Weld").with_property("Part0", part_ref);
+        let weld_ref = weld.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", part_ref)
+                .with_children([InstanceBuilder::new("Folder").with_child(part), weld]),
+        );
+        dom.enable_ref_tracking();
+        dom.enable_journal();
+
+        let model = dom.root_ref();
+        let folder = dom.root().children()[0];
+        dom.destroy(folder);
+
+        let model_instance = dom.get_by_ref(model).unwrap();
+        assert_eq!(
+            model_instance.properties["PrimaryPart"],
+            Variant::Ref(Ref::none())
+        );
+        let weld_instance = dom.get_by_ref(weld_ref).unwrap();
+        assert_eq!(weld_instance.properties["Part0"], Variant::Ref(Ref::none()));
+        assert!(dom.referrers(part_ref).is_empty());
+        assert!(dom.dangling_refs().is_empty());
+
+        // Undoing the destroy also restores the refs that were cleared.
+        assert!(dom.undo());
+        assert_eq!(
+            dom.referrers(part_ref),
+            &set(&[(weld_ref, "Part0"), (model, "PrimaryPart")])
+        );
+    }
+
+    #[test]
+    fn clone_tracks_rewritten_refs() {
+        let part = InstanceBuilder::new("Part");
+        let part_ref = part.referent();
+        let model = InstanceBuilder::new("Model")
+            .with_property("PrimaryPart", part_ref)
+            .with_child(part);
+        let model_ref = model.referent();
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_child(model));
+        dom.enable_ref_tracking();
+
+        let cloned_model = dom.clone_within(model_ref);
+        let cloned_part = dom.get_by_ref(cloned_model).unwrap().children()[0];
+
+        assert_eq!(
+            dom.referrers(cloned_part),
+            &set(&[(cloned_model, "PrimaryPart")])
+        );
+        assert_eq!(dom.referrers(part_ref), &set(&[(model_ref, "PrimaryPart")]));
+    }
+
+    #[test]
+    fn dangling_refs() {
+        let part = InstanceBuilder::new("Part");
+        let part_ref = part.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", part_ref)
+                .with_child(part),
+        );
+        dom.enable_ref_tracking();
+
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        let other_root = other.root_ref();
+        dom.transfer(part_ref, &mut other, other_root);
+
+        assert_eq!(
+            dom.dangling_refs(),
+            vec![(dom.root_ref(), "PrimaryPart".to_owned(), part_ref)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ref tracking is not enabled")]
+    fn referrers_without_tracking() {
+        let dom = WeakDom::new(InstanceBuilder::new("Folder"));
+        dom.referrers(dom.root_ref());
+    }
+}
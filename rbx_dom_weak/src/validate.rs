@@ -0,0 +1,334 @@
+//! Checking a `WeakDom` against a reflection database before it's serialized.
+//!
+//! Serializers assume that instances and their properties match the reflection
+//! database. When they don't, serialization can fail partway through or
+//! produce a file that Roblox refuses to open. [`validate`] finds these
+//! problems up front.
+
+use std::convert::TryFrom;
+
+use rbx_reflection::{ClassTag, DataType, PropertyKind, PropertySerialization, ReflectionDatabase};
+use rbx_types::{Attributes, BrickColor, MaterialColors, Ref, Tags, Variant, VariantType};
+use serde::{Deserialize, Serialize};
+
+use crate::{Instance, WeakDom};
+
+/// Checks every instance in `dom` against `database`, returning every problem
+/// that was found.
+///
+/// The root instance of the DOM is not checked, since it's usually a
+/// placeholder like `DataModel` that is not serialized itself.
+///
+/// Properties that aren't in the database are not reported, since serializers
+/// write them as-is. Properties that don't serialize and `Opaque` values are
+/// not checked either.
+pub fn validate(dom: &WeakDom, database: &ReflectionDatabase) -> ValidationReport {
+    let mut issues = Vec::new();
+
+    for instance in dom.descendants(dom.root_ref()) {
+        validate_instance(dom, database, instance, &mut issues);
+    }
+
+    ValidationReport { issues }
+}
+
+/// The outcome of [`validate`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Every problem that was found, in the order the instances they belong to
+    /// were visited. Problems with properties of the same instance are ordered
+    /// by property name.
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns whether no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// A problem with an instance that would cause it to serialize incorrectly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The instance's class is not in the reflection database.
+    #[serde(rename_all = "camelCase")]
+    UnknownClass {
+        /// The referent of the instance.
+        referent: Ref,
+
+        /// The class of the instance.
+        class: String,
+    },
+
+    /// The instance's class can't be created by Roblox. Services are not
+    /// reported, since they're expected to appear in places.
+    #[serde(rename_all = "camelCase")]
+    NotCreatable {
+        /// The referent of the instance.
+        referent: Ref,
+
+        /// The class of the instance.
+        class: String,
+    },
+
+    /// A property has a value whose type is different from the type given by
+    /// the property's descriptor, and can't be converted to it the way
+    /// serializers convert values like `Float32` to `Float64`.
+    #[serde(rename_all = "camelCase")]
+    TypeMismatch {
+        /// The referent of the instance.
+        referent: Ref,
+
+        /// The name of the property, as it appears on the instance.
+        property: String,
+
+        /// The type given by the property's descriptor.
+        expected: VariantType,
+
+        /// The type of the property's value.
+        actual: VariantType,
+    },
+
+    /// A `Ref` property points to an instance that is not in the DOM.
+    #[serde(rename_all = "camelCase")]
+    DanglingRef {
+        /// The referent of the instance.
+        referent: Ref,
+
+        /// The name of the property.
+        property: String,
+
+        /// The referent the property points to.
+        target: Ref,
+    },
+
+    /// An enum property has a value that is not one of the enum's items.
+    #[serde(rename_all = "camelCase")]
+    InvalidEnum {
+        /// The referent of the instance.
+        referent: Ref,
+
+        /// The name of the property.
+        property: String,
+
+        /// The name of the enum the property belongs to.
+        enum_name: String,
+
+        /// The value of the property.
+        value: u32,
+    },
+}
+
+fn validate_instance(
+    dom: &WeakDom,
+    database: &ReflectionDatabase,
+    instance: &Instance,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let referent = instance.referent();
+
+    match database.classes.get(instance.class.as_str()) {
+        Some(class) => {
+            if class.tags.contains(&ClassTag::NotCreatable)
+                && !class.tags.contains(&ClassTag::Service)
+            {
+                issues.push(ValidationIssue::NotCreatable {
+                    referent,
+                    class: instance.class.clone(),
+                });
+            }
+        }
+        None => {
+            issues.push(ValidationIssue::UnknownClass {
+                referent,
+                class: instance.class.clone(),
+            });
+        }
+    }
+
+    let mut properties: Vec<_> = instance.properties.iter().collect();
+    properties.sort_unstable_by_key(|(name, _)| name.as_str());
+
+    for (name, value) in properties {
+        if let Variant::Ref(target) = value {
+            if target.is_some() && dom.get_by_ref(*target).is_none() {
+                issues.push(ValidationIssue::DanglingRef {
+                    referent,
+                    property: name.clone(),
+                    target: *target,
+                });
+            }
+        }
+
+        let canonical_name = database.canonical_property_name(&instance.class, name);
+        let descriptor = match database.find_property(&instance.class, canonical_name) {
+            Some(descriptor) => descriptor,
+            None => continue,
+        };
+
+        if let PropertyKind::Canonical {
+            serialization: PropertySerialization::DoesNotSerialize,
+        } = descriptor.kind
+        {
+            continue;
+        }
+
+        let expected = match &descriptor.data_type {
+            DataType::Value(ty) => *ty,
+            DataType::Enum(_) => VariantType::Enum,
+            _ => continue,
+        };
+
+        if value.ty() != VariantType::Opaque && !converts_to(value, expected) {
+            issues.push(ValidationIssue::TypeMismatch {
+                referent,
+                property: name.clone(),
+                expected,
+                actual: value.ty(),
+            });
+            continue;
+        }
+
+        if let (DataType::Enum(enum_name), Variant::Enum(value)) = (&descriptor.data_type, value) {
+            let is_item = match database.enums.get(enum_name) {
+                Some(descriptor) => descriptor
+                    .items
+                    .values()
+                    .any(|&item| item == value.to_u32()),
+
+                // Enums that aren't in the database can't be checked.
+                None => true,
+            };
+
+            if !is_item {
+                issues.push(ValidationIssue::InvalidEnum {
+                    referent,
+                    property: name.clone(),
+                    enum_name: enum_name.to_string(),
+                    value: value.to_u32(),
+                });
+            }
+        }
+    }
+}
+
+/// Returns whether a value either has the given type or is one of the values
+/// that serializers convert to it, like older files that store 64-bit numbers
+/// as 32-bit ones.
+fn converts_to(value: &Variant, ty: VariantType) -> bool {
+    match (value, ty) {
+        _ if value.ty() == ty => true,
+        (Variant::Int32(_), VariantType::Int64) => true,
+        (Variant::Float32(_), VariantType::Float64) => true,
+        (Variant::Int32(number), VariantType::BrickColor) => u16::try_from(*number)
+            .ok()
+            .and_then(BrickColor::from_number)
+            .is_some(),
+        (Variant::Color3(_), VariantType::Color3uint8) => true,
+        (Variant::BinaryString(value), VariantType::Tags) => Tags::decode(value.as_ref()).is_ok(),
+        (Variant::BinaryString(value), VariantType::Attributes) => {
+            Attributes::from_reader(AsRef::<[u8]>::as_ref(value)).is_ok()
+        }
+        (Variant::BinaryString(value), VariantType::MaterialColors) => {
+            MaterialColors::decode(value.as_ref()).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::Enum;
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn valid_dom() {
+        let database = rbx_reflection_database::get();
+        let dom = WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("Workspace").with_child(
+                    InstanceBuilder::new("Part")
+                        .with_property("Anchored", true)
+                        .with_property("Material", Enum::from_u32(256)),
+                ),
+            ),
+        );
+
+        let report = validate(&dom, database);
+        assert!(report.is_valid(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn convertible_types() {
+        let database = rbx_reflection_database::get();
+
+        // NumberValue.Value is a Float64, but older files store it as a
+        // Float32, which serializers convert.
+        let dom = WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(InstanceBuilder::new("NumberValue").with_property("Value", 0.5f32)),
+        );
+
+        let report = validate(&dom, database);
+        assert!(report.is_valid(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn issues() {
+        let database = rbx_reflection_database::get();
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+
+        let part = dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("Part")
+                .with_property("Anchored", 1i32)
+                .with_property("Material", Enum::from_u32(12345))
+                .with_property("Name2", true),
+        );
+        let target = Ref::new();
+        let object_value = dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("ObjectValue").with_property("Value", target),
+        );
+        let unknown = dom.insert(dom.root_ref(), InstanceBuilder::new("NotARealClass"));
+        let base_part = dom.insert(dom.root_ref(), InstanceBuilder::new("BasePart"));
+
+        let report = validate(&dom, database);
+        assert_eq!(
+            report.issues,
+            vec![
+                ValidationIssue::TypeMismatch {
+                    referent: part,
+                    property: "Anchored".to_owned(),
+                    expected: VariantType::Bool,
+                    actual: VariantType::Int32,
+                },
+                ValidationIssue::InvalidEnum {
+                    referent: part,
+                    property: "Material".to_owned(),
+                    enum_name: "Material".to_owned(),
+                    value: 12345,
+                },
+                ValidationIssue::DanglingRef {
+                    referent: object_value,
+                    property: "Value".to_owned(),
+                    target,
+                },
+                ValidationIssue::UnknownClass {
+                    referent: unknown,
+                    class: "NotARealClass".to_owned(),
+                },
+                ValidationIssue::NotCreatable {
+                    referent: base_part,
+                    class: "BasePart".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -5,6 +5,7 @@
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
+    iter,
 };
 
 use rbx_types::{Variant, VariantType};
@@ -39,6 +40,88 @@ impl<'a> ReflectionDatabase<'a> {
             enums: HashMap::new(),
         }
     }
+
+    /// Returns the descriptor for the class with the given name followed by
+    /// the descriptors of all of its superclasses, ending with the root of its
+    /// hierarchy (usually `Instance`).
+    ///
+    /// Returns `None` if the class, or any of its superclasses, is not present
+    /// in the database.
+    pub fn superclasses(&self, class_name: &str) -> Option<Vec<&ClassDescriptor<'a>>> {
+        let list: Vec<_> = self.walk_superclasses(class_name).collect();
+
+        match list.last() {
+            Some(root) if root.superclass.is_none() => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the class named `class_name` is `base_name` or inherits
+    /// from it, like Roblox's `Instance:IsA`.
+    ///
+    /// Classes that are not present in the database are only considered to be
+    /// themselves.
+    pub fn is_a(&self, class_name: &str, base_name: &str) -> bool {
+        class_name == base_name
+            || self
+                .walk_superclasses(class_name)
+                .any(|descriptor| descriptor.superclass.as_deref() == Some(base_name))
+    }
+
+    /// Finds the descriptor for the property with the given name on the given
+    /// class, checking each of the class's superclasses in turn.
+    ///
+    /// The returned descriptor may be an alias of another property. Returns
+    /// `None` if the class is not present in the database or the property is
+    /// not defined on it or any of its superclasses.
+    pub fn find_property(
+        &self,
+        class_name: &str,
+        property_name: &str,
+    ) -> Option<&PropertyDescriptor<'a>> {
+        self.walk_superclasses(class_name)
+            .find_map(|descriptor| descriptor.properties.get(property_name))
+    }
+
+    /// Returns the canonical name of the property with the given name on the
+    /// given class. If the property is an alias, this is the name of the
+    /// property it is an alias for. Otherwise, the name is returned unchanged.
+    pub fn canonical_property_name<'b>(
+        &'b self,
+        class_name: &str,
+        property_name: &'b str,
+    ) -> &'b str {
+        match self.find_property(class_name, property_name) {
+            Some(PropertyDescriptor {
+                kind: PropertyKind::Alias { alias_for },
+                ..
+            }) => alias_for,
+            _ => property_name,
+        }
+    }
+
+    /// Finds the default value of the property with the given name on the
+    /// given class, checking each of the class's superclasses in turn.
+    ///
+    /// Defaults are keyed by canonical property names, so aliases should be
+    /// resolved with [`ReflectionDatabase::canonical_property_name`] first.
+    pub fn find_default_property(&self, class_name: &str, property_name: &str) -> Option<&Variant> {
+        self.walk_superclasses(class_name)
+            .find_map(|descriptor| descriptor.default_properties.get(property_name))
+    }
+
+    /// Iterates over the descriptor for the class with the given name and then
+    /// the descriptors of its superclasses, stopping early if a class is not
+    /// present in the database.
+    fn walk_superclasses<'b>(
+        &'b self,
+        class_name: &str,
+    ) -> impl Iterator<Item = &'b ClassDescriptor<'a>> + 'b {
+        iter::successors(self.classes.get(class_name), move |descriptor| {
+            let superclass = descriptor.superclass.as_deref()?;
+            self.classes.get(superclass)
+        })
+    }
 }
 
 /// Describes a class of Instance, its properties, and its relation to other
@@ -206,3 +289,117 @@ impl<'a> EnumDescriptor<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hierarchy() -> ReflectionDatabase<'static> {
+        let mut database = ReflectionDatabase::new();
+
+        for (name, superclass) in [
+            ("Instance", None),
+            ("PVInstance", Some("Instance")),
+            ("BasePart", Some("PVInstance")),
+            ("Part", Some("BasePart")),
+            ("Orphan", Some("Missing")),
+        ] {
+            let mut class = ClassDescriptor::new(name);
+            class.superclass = superclass.map(Into::into);
+            database.classes.insert(name.into(), class);
+        }
+
+        let base_part = database.classes.get_mut("BasePart").unwrap();
+        base_part.properties.insert(
+            "Anchored".into(),
+            PropertyDescriptor::new("Anchored", DataType::Value(VariantType::Bool)),
+        );
+        let mut alias = PropertyDescriptor::new("anchored", DataType::Value(VariantType::Bool));
+        alias.kind = PropertyKind::Alias {
+            alias_for: "Anchored".into(),
+        };
+        base_part.properties.insert("anchored".into(), alias);
+        base_part
+            .default_properties
+            .insert("Anchored".into(), Variant::Bool(false));
+
+        database
+            .classes
+            .get_mut("Part")
+            .unwrap()
+            .default_properties
+            .insert("Anchored".into(), Variant::Bool(true));
+
+        database
+    }
+
+    #[test]
+    fn superclasses() {
+        let database = hierarchy();
+
+        let names: Vec<_> = database
+            .superclasses("Part")
+            .unwrap()
+            .iter()
+            .map(|class| class.name.as_ref())
+            .collect();
+        assert_eq!(names, ["Part", "BasePart", "PVInstance", "Instance"]);
+
+        assert!(database.superclasses("Nonexistent").is_none());
+        assert!(database.superclasses("Orphan").is_none());
+    }
+
+    #[test]
+    fn is_a() {
+        let database = hierarchy();
+
+        assert!(database.is_a("Part", "Part"));
+        assert!(database.is_a("Part", "BasePart"));
+        assert!(database.is_a("Part", "Instance"));
+        assert!(!database.is_a("BasePart", "Part"));
+        assert!(!database.is_a("Instance", "BasePart"));
+
+        assert!(database.is_a("Orphan", "Missing"));
+        assert!(database.is_a("Nonexistent", "Nonexistent"));
+        assert!(!database.is_a("Nonexistent", "Instance"));
+    }
+
+    #[test]
+    fn find_property() {
+        let database = hierarchy();
+
+        let property = database.find_property("Part", "anchored").unwrap();
+        assert_eq!(property.name, "anchored");
+        assert!(database.find_property("Instance", "Anchored").is_none());
+        assert!(database.find_property("Nonexistent", "Anchored").is_none());
+
+        assert_eq!(
+            database.canonical_property_name("Part", "anchored"),
+            "Anchored"
+        );
+        assert_eq!(
+            database.canonical_property_name("Part", "Anchored"),
+            "Anchored"
+        );
+        assert_eq!(
+            database.canonical_property_name("Part", "Unknown"),
+            "Unknown"
+        );
+    }
+
+    #[test]
+    fn find_default_property() {
+        let database = hierarchy();
+
+        assert_eq!(
+            database.find_default_property("Part", "Anchored"),
+            Some(&Variant::Bool(true))
+        );
+        assert_eq!(
+            database.find_default_property("BasePart", "Anchored"),
+            Some(&Variant::Bool(false))
+        );
+        assert_eq!(database.find_default_property("Instance", "Anchored"), None);
+        assert_eq!(database.find_default_property("Orphan", "Anchored"), None);
+    }
+}
@@ -13,6 +13,7 @@ mod faces;
 mod font;
 mod lister;
 mod material_colors;
+mod opaque;
 mod physical_properties;
 mod referent;
 mod security_capabilities;
@@ -31,6 +32,7 @@ pub use error::*;
 pub use faces::*;
 pub use font::*;
 pub use material_colors::*;
+pub use opaque::*;
 pub use physical_properties::*;
 pub use referent::*;
 pub use security_capabilities::*;
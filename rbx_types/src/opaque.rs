@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+/// A property value of a type that rbx-dom does not understand, preserved
+/// exactly as it appeared in the file it was read from.
+///
+/// Opaque values can only be written back to the same format they were read
+/// from. They allow files from newer versions of Roblox to pass through tools
+/// built on rbx-dom without losing data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct Opaque {
+    format: OpaqueFormat,
+    data: Arc<[u8]>,
+}
+
+impl Opaque {
+    /// Creates an `Opaque` from an XML property with the given tag name, like
+    /// `ProtectedString2`. `data` holds the contents of the tag as XML.
+    pub fn xml<S: Into<String>, D: Into<Arc<[u8]>>>(tag: S, data: D) -> Self {
+        Self {
+            format: OpaqueFormat::Xml { tag: tag.into() },
+            data: data.into(),
+        }
+    }
+
+    /// Creates an `Opaque` from a binary `PROP` chunk with the given type ID.
+    ///
+    /// The values of every instance in a `PROP` chunk are stored together, and
+    /// without knowing the type, they can't be split apart. Instead, `data`
+    /// holds the values for the entire chunk and `index` is the position of
+    /// this instance within the chunk.
+    pub fn binary<D: Into<Arc<[u8]>>>(type_id: u8, index: u32, data: D) -> Self {
+        Self {
+            format: OpaqueFormat::Binary { type_id, index },
+            data: data.into(),
+        }
+    }
+
+    /// Returns the format this value was read from.
+    pub fn format(&self) -> &OpaqueFormat {
+        &self.format
+    }
+
+    /// Returns the raw data of this value. See [`Opaque::xml`] and
+    /// [`Opaque::binary`] for what the data contains.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns whether both values share the same underlying data, which is
+    /// the case for binary values read from the same `PROP` chunk.
+    pub fn shares_data(&self, other: &Opaque) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+/// Describes where an [`Opaque`] value came from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum OpaqueFormat {
+    /// The value was read from an XML model or place.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Xml {
+        /// The name of the tag the value was stored in.
+        tag: String,
+    },
+
+    /// The value was read from a binary model or place.
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Binary {
+        /// The type ID of the `PROP` chunk the value was stored in.
+        type_id: u8,
+
+        /// The position of the instance within the `PROP` chunk.
+        index: u32,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shares_data() {
+        let data: Arc<[u8]> = Arc::from(vec![1, 2, 3]);
+        let first = Opaque::binary(0x40, 0, data.clone());
+        let second = Opaque::binary(0x40, 1, data);
+        let copy = Opaque::binary(0x40, 0, vec![1, 2, 3]);
+
+        assert!(first.shares_data(&second));
+        assert!(!first.shares_data(&copy));
+        assert_eq!(first, copy);
+        assert_eq!(first.data(), [1, 2, 3]);
+    }
+}
@@ -1,8 +1,8 @@
 use crate::{
     Attributes, Axes, BinaryString, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
-    Content, Enum, Faces, Font, MaterialColors, NumberRange, NumberSequence, PhysicalProperties,
-    Ray, Rect, Ref, Region3, Region3int16, SecurityCapabilities, SharedString, Tags, UDim, UDim2,
-    UniqueId, Vector2, Vector2int16, Vector3, Vector3int16,
+    Content, Enum, Faces, Font, MaterialColors, NumberRange, NumberSequence, Opaque,
+    PhysicalProperties, Ray, Rect, Ref, Region3, Region3int16, SecurityCapabilities, SharedString,
+    Tags, UDim, UDim2, UniqueId, Vector2, Vector2int16, Vector3, Vector3int16,
 };
 
 /// Reduces boilerplate from listing different values of Variant by wrapping
@@ -133,6 +133,7 @@ make_variant! {
     UniqueId(UniqueId),
     MaterialColors(MaterialColors),
     SecurityCapabilities(SecurityCapabilities),
+    Opaque(Opaque),
 }
 
 impl From<&'_ str> for Variant {
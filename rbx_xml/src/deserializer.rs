@@ -8,7 +8,9 @@ use rbx_dom_weak::{
     types::{Ref, SharedString, Variant, VariantType},
     InstanceBuilder, WeakDom,
 };
-use rbx_reflection::{DataType, PropertyKind, PropertySerialization, ReflectionDatabase};
+use rbx_reflection::{
+    DataType, PropertyDescriptor, PropertyKind, PropertySerialization, ReflectionDatabase,
+};
 
 use crate::{
     conversion::ConvertVariant,
@@ -20,6 +22,23 @@ use crate::{
 use crate::deserializer_core::{XmlEventReader, XmlReadEvent};
 
 pub fn decode_internal<R: Read>(source: R, options: DecodeOptions) -> Result<WeakDom, DecodeError> {
+    let (tree, _) = decode(source, options, false)?;
+
+    Ok(tree)
+}
+
+pub fn decode_lenient_internal<R: Read>(
+    source: R,
+    options: DecodeOptions,
+) -> Result<(WeakDom, Vec<DecodeError>), DecodeError> {
+    decode(source, options, true)
+}
+
+fn decode<R: Read>(
+    source: R,
+    options: DecodeOptions,
+    lenient: bool,
+) -> Result<(WeakDom, Vec<DecodeError>), DecodeError> {
     let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
 
     let root_id = tree.root_ref();
@@ -27,11 +46,21 @@ pub fn decode_internal<R: Read>(source: R, options: DecodeOptions) -> Result<Wea
     let mut iterator = XmlEventReader::from_source(source);
     let mut state = ParseState::new(&mut tree, options);
 
+    if lenient {
+        state.warnings = Some(Vec::new());
+    }
+
     deserialize_root(&mut iterator, &mut state, root_id)?;
     apply_referent_rewrites(&mut state);
     apply_shared_string_rewrites(&mut state);
 
-    Ok(tree)
+    if state.options.prunes() {
+        prune_filtered(&mut state);
+    }
+
+    let warnings = state.warnings.take().unwrap_or_default();
+
+    Ok((tree, warnings))
 }
 
 /// Describes the strategy that rbx_xml should use when deserializing
@@ -72,6 +101,9 @@ pub enum DecodePropertyBehavior {
 pub struct DecodeOptions<'db> {
     property_behavior: DecodePropertyBehavior,
     database: &'db ReflectionDatabase<'db>,
+    classes: Option<HashSet<String>>,
+    properties: Option<HashSet<String>>,
+    prune: bool,
 }
 
 impl<'db> DecodeOptions<'db> {
@@ -81,6 +113,9 @@ impl<'db> DecodeOptions<'db> {
         DecodeOptions {
             property_behavior: DecodePropertyBehavior::IgnoreUnknown,
             database: rbx_reflection_database::get(),
+            classes: None,
+            properties: None,
+            prune: false,
         }
     }
 
@@ -101,6 +136,69 @@ impl<'db> DecodeOptions<'db> {
         DecodeOptions { database, ..self }
     }
 
+    /// Only decode properties of instances that are one of the given classes
+    /// or inherit from one of them. Instances of other classes are still
+    /// created with their names unless `prune_filtered` is set.
+    pub fn only_classes<I, S>(self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        DecodeOptions {
+            classes: Some(classes.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Only decode properties with the given canonical names. Instance names
+    /// are always decoded.
+    pub fn only_properties<I, S>(self, properties: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        DecodeOptions {
+            properties: Some(properties.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Determines whether instances that aren't one of the classes given to
+    /// `only_classes` will be removed from the tree, unless one of their
+    /// descendants is. Has no effect if `only_classes` isn't used.
+    ///
+    /// `Ref` properties that point to pruned instances are left as-is.
+    #[inline]
+    pub fn prune_filtered(self, prune: bool) -> Self {
+        DecodeOptions { prune, ..self }
+    }
+
+    /// Returns whether instances of the given class should have their
+    /// properties decoded.
+    pub(crate) fn allows_class(&self, class_name: &str) -> bool {
+        match &self.classes {
+            Some(classes) => classes
+                .iter()
+                .any(|allowed| self.database.is_a(class_name, allowed)),
+            None => true,
+        }
+    }
+
+    /// Returns whether the property with the given canonical name should be
+    /// decoded.
+    pub(crate) fn allows_property(&self, property_name: &str) -> bool {
+        match &self.properties {
+            Some(properties) => property_name == "Name" || properties.contains(property_name),
+            None => true,
+        }
+    }
+
+    /// Returns whether instances that don't pass the class filter should be
+    /// removed from the tree.
+    pub(crate) fn prunes(&self) -> bool {
+        self.prune && self.classes.is_some()
+    }
+
     /// A utility function to determine whether or not we should reference the
     /// reflection database at all.
     pub(crate) fn use_reflection(&self) -> bool {
@@ -120,11 +218,6 @@ pub struct ParseState<'dom, 'db> {
 
     options: DecodeOptions<'db>,
 
-    /// Metadata deserialized from 'Meta' fields in the file.
-    /// Known fields are:
-    /// - ExplicitAutoJoints
-    metadata: HashMap<String, String>,
-
     /// A map referent strings to IDs. This map is filled up as instances are
     /// deserialized, and referred to when filling out Ref properties.
     ///
@@ -149,6 +242,10 @@ pub struct ParseState<'dom, 'db> {
     /// Contains all of the unknown types that have been found so far. Tracking
     /// them here helps ensure that we only output a warning once per type.
     unknown_type_names: HashSet<String>,
+
+    /// The errors that have been skipped over when decoding leniently, or
+    /// `None` if any error should stop decoding.
+    warnings: Option<Vec<DecodeError>>,
 }
 
 struct ReferentRewrite {
@@ -168,12 +265,26 @@ impl<'dom, 'db> ParseState<'dom, 'db> {
         ParseState {
             tree,
             options,
-            metadata: HashMap::new(),
             referents_to_ids: HashMap::new(),
             referent_rewrites: Vec::new(),
             known_shared_strings: HashMap::new(),
             shared_string_rewrites: Vec::new(),
             unknown_type_names: HashSet::new(),
+            warnings: None,
+        }
+    }
+
+    /// Called when part of the file can't be decoded. When decoding leniently,
+    /// the error is recorded so that decoding can continue past it. Otherwise,
+    /// it's returned.
+    fn recover(&mut self, error: DecodeError) -> Result<(), DecodeError> {
+        match &mut self.warnings {
+            Some(warnings) => {
+                log::warn!("Skipping part of XML file: {}", error);
+                warnings.push(error);
+                Ok(())
+            }
+            None => Err(error),
         }
     }
 
@@ -261,6 +372,36 @@ fn apply_shared_string_rewrites(state: &mut ParseState) {
     }
 }
 
+/// Removes every instance whose class doesn't pass the filter and that has no
+/// descendants that do.
+fn prune_filtered(state: &mut ParseState) {
+    let root_ref = state.tree.root_ref();
+    let order: Vec<Ref> = state
+        .tree
+        .descendants(root_ref)
+        .map(|instance| instance.referent())
+        .collect();
+
+    // Children always come after their parents in breadth-first order, so
+    // walking backwards lets us decide on every child before its parent.
+    let mut kept = HashSet::new();
+    for &referent in order.iter().rev() {
+        let instance = state.tree.get_by_ref(referent).unwrap();
+
+        if state.options.allows_class(&instance.class)
+            || instance.children().iter().any(|child| kept.contains(child))
+        {
+            kept.insert(referent);
+        }
+    }
+
+    for referent in order {
+        if !kept.contains(&referent) && state.tree.get_by_ref(referent).is_some() {
+            state.tree.destroy(referent);
+        }
+    }
+}
+
 fn deserialize_root<R: Read>(
     reader: &mut XmlEventReader<R>,
     state: &mut ParseState,
@@ -288,6 +429,20 @@ fn deserialize_root<R: Read>(
         return Err(reader.error(DecodeErrorKind::WrongDocVersion(doc_version)));
     }
 
+    // If the file can't be read any further, everything read so far is kept
+    // when decoding leniently.
+    if let Err(error) = deserialize_root_children(reader, state, parent_id) {
+        state.recover(error)?;
+    }
+
+    Ok(())
+}
+
+fn deserialize_root_children<R: Read>(
+    reader: &mut XmlEventReader<R>,
+    state: &mut ParseState,
+    parent_id: Ref,
+) -> Result<(), DecodeError> {
     loop {
         match reader.expect_peek()? {
             XmlReadEvent::StartElement { name, .. } => {
@@ -353,7 +508,7 @@ fn deserialize_metadata<R: Read>(
     let value = reader.read_characters()?;
     reader.expect_end_with_name("Meta")?;
 
-    state.metadata.insert(name, value);
+    state.tree.metadata_mut().insert(name, value);
     Ok(())
 }
 
@@ -486,19 +641,21 @@ fn deserialize_instance<R: Read>(
         }
     }
 
+    let name = match properties.remove("Name") {
+        Some(Variant::String(value)) => Some(value),
+        Some(value) => {
+            state.recover(reader.error(DecodeErrorKind::NameMustBeString(value.ty())))?;
+            None
+        }
+        None => None,
+    };
+
     let instance = state.tree.get_by_ref_mut(instance_id).unwrap();
 
-    instance.name = match properties.remove("Name") {
-        Some(value) => match value {
-            Variant::String(value) => value,
-            _ => return Err(reader.error(DecodeErrorKind::NameMustBeString(value.ty()))),
-        },
-
-        // TODO: Use reflection to get default name instead. This should only
-        // matter for ValueBase instances in files created by tools other than
-        // Roblox Studio.
-        None => instance.class.clone(),
-    };
+    // TODO: Use reflection to get default name instead. This should only
+    // matter for ValueBase instances in files created by tools other than
+    // Roblox Studio.
+    instance.name = name.unwrap_or_else(|| instance.class.clone());
 
     instance.properties = properties;
 
@@ -526,6 +683,8 @@ fn deserialize_properties<R: Read>(
         class_name
     );
 
+    let class_allowed = state.options.allows_class(&class_name);
+
     loop {
         let (xml_type_name, xml_property_name) = {
             match reader.expect_peek()? {
@@ -581,115 +740,202 @@ fn deserialize_properties<R: Read>(
             None
         };
 
-        if let Some(descriptor) = maybe_descriptor {
-            let value =
-                match read_value_xml(reader, state, &xml_type_name, instance_id, &descriptor.name)?
-                {
-                    Some(value) => value,
-                    None => continue,
-                };
+        if !should_decode(state, class_allowed, &xml_property_name, maybe_descriptor) {
+            log::trace!(
+                "Skipping property {}.{} because it was filtered out",
+                class_name,
+                xml_property_name
+            );
+            reader.eat_unknown_tag()?;
+            continue;
+        }
 
-            let xml_ty = value.ty();
-
-            // The property descriptor might specify a different type than the
-            // one we saw in the XML.
-            //
-            // This happens when property types are upgraded or if the
-            // serialized data type is different than the canonical one.
-            //
-            // For example:
-            // - Int/Float widening from 32-bit to 64-bit
-            // - BrickColor properties turning into Color3
-            let expected_type = match &descriptor.data_type {
-                DataType::Value(data_type) => *data_type,
-                DataType::Enum(_enum_name) => VariantType::Enum,
-                _ => unimplemented!(),
-            };
-            log::trace!("property's read type: {xml_ty:?}, canonical type: {expected_type:?}");
-
-            let value = match value.try_convert(expected_type) {
-                Ok(value) => value,
-
-                // The property descriptor disagreed, and there was no
-                // conversion available. This is always an error.
-                Err(message) => {
-                    return Err(
-                        reader.error(DecodeErrorKind::UnsupportedPropertyConversion {
-                            class_name: class_name.clone(),
-                            property_name: descriptor.name.to_string(),
-                            expected_type,
-                            actual_type: xml_ty,
-                            message,
-                        }),
-                    );
-                }
+        // Properties that can't be decoded are skipped over when decoding
+        // leniently, which means reading up to the end of their tag. If that
+        // fails too, the file can't be read any further.
+        let depth = reader.depth();
+        let result = deserialize_property(
+            reader,
+            state,
+            instance_id,
+            &xml_type_name,
+            xml_property_name,
+            maybe_descriptor,
+            props,
+        );
+
+        if let Err(error) = result {
+            if state.warnings.is_none() || reader.skip_to_depth(depth).is_err() {
+                return Err(error);
+            }
+
+            state.recover(error)?;
+        }
+    }
+}
+
+/// Reads the value of a single property and adds it to `props`, converting or
+/// migrating it according to its descriptor if it has one.
+fn deserialize_property<R: Read>(
+    reader: &mut XmlEventReader<R>,
+    state: &mut ParseState,
+    instance_id: Ref,
+    xml_type_name: &str,
+    xml_property_name: String,
+    maybe_descriptor: Option<&PropertyDescriptor>,
+    props: &mut HashMap<String, Variant>,
+) -> Result<(), DecodeError> {
+    if let Some(descriptor) = maybe_descriptor {
+        let value =
+            match read_value_xml(reader, state, xml_type_name, instance_id, &descriptor.name)? {
+                Some(value) => value,
+                None => return Ok(()),
             };
 
-            match &descriptor.kind {
-                PropertyKind::Canonical {
-                    serialization: PropertySerialization::Migrate(migration),
-                } => {
-                    let new_property_name = &migration.new_property_name;
-                    let old_property_name = &descriptor.name;
-
-                    if !props.contains_key(new_property_name) {
-                        log::trace!(
-                            "Attempting to migrate property {old_property_name} to {new_property_name}"
-                        );
-                        match migration.perform(&value) {
-                            Ok(migrated_value) => {
-                                props.insert(new_property_name.to_string(), migrated_value);
-                                log::trace!(
-                                    "Successfully migrated property {old_property_name} to {new_property_name}"
-                                );
-                            }
-                            Err(error) => {
-                                return Err(reader.error(DecodeErrorKind::MigrationError(error)));
-                            }
+        // Opaque values have no type that we could convert them to, so
+        // they're kept exactly as they were read.
+        if let Variant::Opaque(_) = value {
+            props.insert(descriptor.name.to_string(), value);
+            return Ok(());
+        }
+
+        let xml_ty = value.ty();
+
+        // The property descriptor might specify a different type than the
+        // one we saw in the XML.
+        //
+        // This happens when property types are upgraded or if the
+        // serialized data type is different than the canonical one.
+        //
+        // For example:
+        // - Int/Float widening from 32-bit to 64-bit
+        // - BrickColor properties turning into Color3
+        let expected_type = match &descriptor.data_type {
+            DataType::Value(data_type) => *data_type,
+            DataType::Enum(_enum_name) => VariantType::Enum,
+            _ => unimplemented!(),
+        };
+        log::trace!("property's read type: {xml_ty:?}, canonical type: {expected_type:?}");
+
+        let value = match value.try_convert(expected_type) {
+            Ok(value) => value,
+
+            // The property descriptor disagreed, and there was no
+            // conversion available. This is always an error.
+            Err(message) => {
+                return Err(
+                    reader.error(DecodeErrorKind::UnsupportedPropertyConversion {
+                        class_name: class_name(state, instance_id),
+                        property_name: descriptor.name.to_string(),
+                        expected_type,
+                        actual_type: xml_ty,
+                        message,
+                    }),
+                );
+            }
+        };
+
+        match &descriptor.kind {
+            PropertyKind::Canonical {
+                serialization: PropertySerialization::Migrate(migration),
+            } => {
+                let new_property_name = &migration.new_property_name;
+                let old_property_name = &descriptor.name;
+
+                if !props.contains_key(new_property_name) {
+                    log::trace!(
+                        "Attempting to migrate property {old_property_name} to {new_property_name}"
+                    );
+                    match migration.perform(&value) {
+                        Ok(migrated_value) => {
+                            props.insert(new_property_name.to_string(), migrated_value);
+                            log::trace!(
+                                "Successfully migrated property {old_property_name} to {new_property_name}"
+                            );
+                        }
+                        Err(error) => {
+                            return Err(reader.error(DecodeErrorKind::MigrationError(error)));
                         }
                     }
                 }
-                _ => {
-                    props.insert(descriptor.name.to_string(), value);
-                }
-            };
-        } else {
-            match state.options.property_behavior {
-                DecodePropertyBehavior::IgnoreUnknown => {
-                    // We don't care about this property, so we can read it and
-                    // throw it into the void.
-
-                    read_value_xml(
-                        reader,
-                        state,
-                        &xml_type_name,
-                        instance_id,
-                        &xml_property_name,
-                    )?;
-                }
-                DecodePropertyBehavior::ReadUnknown | DecodePropertyBehavior::NoReflection => {
-                    // We'll take this value as-is with no conversions on either
-                    // the name or value.
-
-                    let value = match read_value_xml(
-                        reader,
-                        state,
-                        &xml_type_name,
-                        instance_id,
-                        &xml_property_name,
-                    )? {
-                        Some(value) => value,
-                        None => continue,
-                    };
-                    props.insert(xml_property_name, value);
-                }
-                DecodePropertyBehavior::ErrorOnUnknown => {
-                    return Err(reader.error(DecodeErrorKind::UnknownProperty {
-                        class_name,
-                        property_name: xml_property_name,
-                    }));
-                }
+            }
+            _ => {
+                props.insert(descriptor.name.to_string(), value);
+            }
+        };
+    } else {
+        match state.options.property_behavior {
+            DecodePropertyBehavior::IgnoreUnknown => {
+                // We don't care about this property, so we can read it and
+                // throw it into the void.
+
+                read_value_xml(
+                    reader,
+                    state,
+                    xml_type_name,
+                    instance_id,
+                    &xml_property_name,
+                )?;
+            }
+            DecodePropertyBehavior::ReadUnknown | DecodePropertyBehavior::NoReflection => {
+                // We'll take this value as-is with no conversions on either
+                // the name or value.
+
+                let value = match read_value_xml(
+                    reader,
+                    state,
+                    xml_type_name,
+                    instance_id,
+                    &xml_property_name,
+                )? {
+                    Some(value) => value,
+                    None => return Ok(()),
+                };
+                props.insert(xml_property_name, value);
+            }
+            DecodePropertyBehavior::ErrorOnUnknown => {
+                return Err(reader.error(DecodeErrorKind::UnknownProperty {
+                    class_name: class_name(state, instance_id),
+                    property_name: xml_property_name,
+                }));
             }
         }
     }
+
+    Ok(())
+}
+
+/// Returns the class of the instance with the given ID, for use in errors.
+fn class_name(state: &ParseState, instance_id: Ref) -> String {
+    state.tree.get_by_ref(instance_id).unwrap().class.clone()
+}
+
+/// Determines whether a property should be decoded according to the filter in
+/// the decode options.
+fn should_decode(
+    state: &ParseState,
+    class_allowed: bool,
+    xml_property_name: &str,
+    descriptor: Option<&PropertyDescriptor>,
+) -> bool {
+    let canonical_name = descriptor.map_or(xml_property_name, |descriptor| &descriptor.name);
+
+    if canonical_name == "Name" {
+        return true;
+    }
+
+    if !class_allowed {
+        return false;
+    }
+
+    if state.options.allows_property(canonical_name) {
+        return true;
+    }
+
+    match descriptor.map(|descriptor| &descriptor.kind) {
+        Some(PropertyKind::Canonical {
+            serialization: PropertySerialization::Migrate(migration),
+        }) => state.options.allows_property(&migration.new_property_name),
+        _ => false,
+    }
 }
@@ -17,16 +17,34 @@ pub struct XmlEventReader<R: Read> {
     reader: xml::EventReader<R>,
     peeked: Option<Result<XmlReadEvent, xml::reader::Error>>,
     finished: bool,
+
+    /// The number of elements that have been started but not ended, not
+    /// counting a start tag that has only been peeked.
+    depth: usize,
 }
 
 impl<R: Read> Iterator for XmlEventReader<R> {
     type Item = XmlReadResult;
 
     fn next(&mut self) -> Option<XmlReadResult> {
-        if let Some(value) = self.peeked.take() {
-            return Some(value);
+        let event = match self.peeked.take() {
+            Some(value) => Some(value),
+            None => self.read_event(),
+        };
+
+        match &event {
+            Some(Ok(XmlReadEvent::StartElement { .. })) => self.depth += 1,
+            Some(Ok(XmlReadEvent::EndElement { .. })) => self.depth = self.depth.saturating_sub(1),
+            _ => {}
         }
 
+        event
+    }
+}
+
+impl<R: Read> XmlEventReader<R> {
+    /// Reads the next event from the underlying reader, skipping whitespace.
+    fn read_event(&mut self) -> Option<XmlReadResult> {
         if self.finished {
             return None;
         }
@@ -48,9 +66,7 @@ impl<R: Read> Iterator for XmlEventReader<R> {
             }
         }
     }
-}
 
-impl<R: Read> XmlEventReader<R> {
     /// Constructs a new `XmlEventReader` from a source that implements `Read`.
     pub fn from_source(source: R) -> XmlEventReader<R> {
         let reader = ParserConfig::new()
@@ -61,6 +77,7 @@ impl<R: Read> XmlEventReader<R> {
             reader,
             peeked: None,
             finished: false,
+            depth: 0,
         }
     }
 
@@ -70,7 +87,7 @@ impl<R: Read> XmlEventReader<R> {
             return self.peeked.as_ref();
         }
 
-        self.peeked = self.next();
+        self.peeked = self.read_event();
         self.peeked.as_ref()
     }
 
@@ -301,4 +318,20 @@ impl<R: Read> XmlEventReader<R> {
 
         Ok(())
     }
+
+    /// The number of elements that have been started but not ended.
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Consume events from the iterator until every element started after
+    /// `depth` was reached has ended. Used to skip the rest of a tag that
+    /// couldn't be decoded.
+    pub(crate) fn skip_to_depth(&mut self, depth: usize) -> Result<(), NewDecodeError> {
+        while self.depth > depth {
+            self.expect_next()?;
+        }
+
+        Ok(())
+    }
 }
@@ -227,6 +227,7 @@ pub(crate) enum EncodeErrorKind {
         property_name: String,
     },
     UnsupportedPropertyType(VariantType),
+    InvalidOpaqueValue(xml::reader::Error),
     UnsupportedPropertyConversion {
         class_name: String,
         property_name: String,
@@ -256,6 +257,9 @@ impl fmt::Display for EncodeErrorKind {
             UnsupportedPropertyType(ty) => {
                 write!(output, "Properties of type {:?} cannot be encoded yet", ty)
             }
+            InvalidOpaqueValue(err) => {
+                write!(output, "Opaque value does not contain valid XML: {}", err)
+            }
             UnsupportedPropertyConversion {
                 class_name,
                 property_name,
@@ -280,6 +284,7 @@ impl std::error::Error for EncodeErrorKind {
             Io(err) => Some(err),
             Xml(err) => Some(err),
             Type(err) => Some(err),
+            InvalidOpaqueValue(err) => Some(err),
 
             UnknownProperty { .. }
             | UnsupportedPropertyType(_)
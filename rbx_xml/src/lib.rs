@@ -127,12 +127,15 @@ use std::io::{Read, Write};
 
 use rbx_dom_weak::{types::Ref, WeakDom};
 
-use crate::{deserializer::decode_internal, serializer::encode_internal};
+use crate::{
+    deserializer::{decode_internal, decode_lenient_internal},
+    serializer::encode_internal,
+};
 
 pub use crate::{
     deserializer::{DecodeOptions, DecodePropertyBehavior},
     error::{DecodeError, EncodeError},
-    serializer::{EncodeOptions, EncodePropertyBehavior},
+    serializer::{EncodeOptions, EncodePropertyBehavior, EncodeReferentBehavior},
 };
 
 /// Decodes an XML-format model or place from something that implements the
@@ -158,6 +161,33 @@ pub fn from_str_default<S: AsRef<str>>(reader: S) -> Result<WeakDom, DecodeError
     decode_internal(reader.as_ref().as_bytes(), DecodeOptions::default())
 }
 
+/// Decodes an XML-format model or place from something that implements the
+/// `std::io::Read` trait, skipping over any parts of the file that can't be
+/// decoded instead of returning an error.
+///
+/// This is useful for salvaging damaged files. Properties whose values can't
+/// be read or converted are skipped, and if the file ends early or stops being
+/// valid XML, everything read up to that point is kept. The errors that were
+/// skipped over are returned in the order they were found.
+///
+/// An error is only returned if the file doesn't start with a `<roblox>` tag
+/// that rbx_xml understands.
+pub fn from_reader_lenient<R: Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<(WeakDom, Vec<DecodeError>), DecodeError> {
+    decode_lenient_internal(reader, options)
+}
+
+/// Decodes an XML-format model or place from a string, skipping over any parts
+/// of the file that can't be decoded. See [`from_reader_lenient`].
+pub fn from_str_lenient<S: AsRef<str>>(
+    reader: S,
+    options: DecodeOptions,
+) -> Result<(WeakDom, Vec<DecodeError>), DecodeError> {
+    decode_lenient_internal(reader.as_ref().as_bytes(), options)
+}
+
 /// Serializes a subset of the given tree to an XML format model or place,
 /// writing to something that implements the `std::io::Write` trait.
 pub fn to_writer<W: Write>(
@@ -179,4 +209,3 @@ pub fn to_writer_default<W: Write>(
 ) -> Result<(), EncodeError> {
     encode_internal(writer, tree, ids, EncodeOptions::default())
 }
-
@@ -3,7 +3,7 @@
 //! This module wraps the low-level XML writer functions (such as property and instance
 //! serialization) into a single convenience function, `to_string`.
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
 use std::error::Error;
 
@@ -35,10 +35,30 @@ pub enum EncodePropertyBehavior {
     NoReflection,
 }
 
+/// Describes the strategy that rbx_xml uses when generating the referents that
+/// identify instances within a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeReferentBehavior {
+    /// Numbers instances in the order they're written. Adding or removing an
+    /// instance changes the referent of every instance written after it.
+    Sequential,
+    /// Derives each instance's referent from its path in the tree. Referents
+    /// only change when an instance or one of its ancestors is renamed or
+    /// moved, or when a sibling with the same name is added before it.
+    Path,
+    /// Derives each instance's referent from its `UniqueId` property, which
+    /// stays the same when the instance is renamed or moved. Instances without
+    /// a `UniqueId` fall back to `Path`.
+    UniqueId,
+}
+
 /// Options for serializing a Roblox model or place.
 #[derive(Debug, Clone)]
 pub struct EncodeOptions<'db> {
     pub property_behavior: EncodePropertyBehavior,
+    /// How referents are generated for the instances being written.
+    pub referent_behavior: EncodeReferentBehavior,
     pub database: &'db ReflectionDatabase<'db>,
 }
 
@@ -48,6 +68,7 @@ impl<'db> EncodeOptions<'db> {
     pub fn new() -> Self {
         EncodeOptions {
             property_behavior: EncodePropertyBehavior::IgnoreUnknown,
+            referent_behavior: EncodeReferentBehavior::Sequential,
             database: rbx_reflection_database::get(),
         }
     }
@@ -58,6 +79,12 @@ impl<'db> EncodeOptions<'db> {
         EncodeOptions { property_behavior, ..self }
     }
 
+    /// Sets the referent behavior.
+    #[inline]
+    pub fn referent_behavior(self, referent_behavior: EncodeReferentBehavior) -> Self {
+        EncodeOptions { referent_behavior, ..self }
+    }
+
     /// Sets a custom reflection database.
     #[inline]
     pub fn reflection_database(self, database: &'db ReflectionDatabase<'db>) -> Self {
@@ -82,6 +109,9 @@ pub struct EmitState<'db> {
     pub referent_map: HashMap<Ref, u32>,
     /// The next referent value.
     pub next_referent: u32,
+    /// Referents generated ahead of time from the instances themselves, used
+    /// instead of `referent_map` when the referent behavior isn't sequential.
+    pub stable_referents: HashMap<Ref, String>,
     /// Map of shared strings to be emitted later.
     pub shared_strings_to_emit: BTreeMap<SharedStringHash, SharedString>,
 }
@@ -92,18 +122,23 @@ impl<'db> EmitState<'db> {
             options,
             referent_map: HashMap::new(),
             next_referent: 0,
+            stable_referents: HashMap::new(),
             shared_strings_to_emit: BTreeMap::new(),
         }
     }
 
-    pub fn map_id(&mut self, id: Ref) -> u32 {
+    pub fn map_id(&mut self, id: Ref) -> String {
+        if let Some(referent) = self.stable_referents.get(&id) {
+            return referent.clone();
+        }
+
         if let Some(&value) = self.referent_map.get(&id) {
-            value
+            value.to_string()
         } else {
             let referent = self.next_referent;
             self.referent_map.insert(id, referent);
             self.next_referent += 1;
-            referent
+            referent.to_string()
         }
     }
 
@@ -122,8 +157,15 @@ pub fn encode_internal<W: Write>(
     let mut writer = XmlEventWriter::from_output(output);
     let mut state = EmitState::new(options);
 
+    if state.options.referent_behavior != EncodeReferentBehavior::Sequential {
+        state.stable_referents =
+            ReferentGenerator::new(tree, state.options.referent_behavior).generate(ids);
+    }
+
     writer.write(XmlWriteEvent::start_element("roblox").attr("version", "4"))?;
 
+    serialize_metadata(&mut writer, tree)?;
+
     let mut property_buffer = Vec::new();
     for id in ids {
         serialize_instance(&mut writer, &mut state, tree, *id, &mut property_buffer)?;
@@ -136,6 +178,20 @@ pub fn encode_internal<W: Write>(
     Ok(())
 }
 
+/// Serializes the metadata of the tree as a series of `Meta` tags.
+fn serialize_metadata<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    tree: &WeakDom,
+) -> Result<(), NewEncodeError> {
+    for (name, value) in tree.metadata() {
+        writer.write(XmlWriteEvent::start_element("Meta").attr("name", name))?;
+        writer.write_string(value)?;
+        writer.end_element()?;
+    }
+
+    Ok(())
+}
+
 /// Serializes a single instance (and its children) into XML.
 fn serialize_instance<'dom, W: Write>(
     writer: &mut XmlEventWriter<W>,
@@ -150,7 +206,7 @@ fn serialize_instance<'dom, W: Write>(
     writer.write(
         XmlWriteEvent::start_element("Item")
             .attr("class", &instance.class)
-            .attr("referent", &mapped_id.to_string()),
+            .attr("referent", &mapped_id),
     )?;
 
     writer.write(XmlWriteEvent::start_element("Properties"))?;
@@ -185,6 +241,11 @@ fn serialize_instance<'dom, W: Write>(
 
             let mut serialized_name = serialized_descriptor.name.as_ref();
 
+            if let Variant::Opaque(_) = value {
+                write_value_xml(writer, state, serialized_name, value)?;
+                continue;
+            }
+
             let mut converted_value = match value.try_convert_ref(data_type) {
                 Ok(val) => val,
                 Err(message) => {
@@ -238,6 +299,157 @@ fn serialize_instance<'dom, W: Write>(
     Ok(())
 }
 
+/// Generates referents for instances that are derived from the instances
+/// themselves, so that they stay the same when unrelated parts of the tree
+/// change.
+struct ReferentGenerator<'dom> {
+    tree: &'dom WeakDom,
+    behavior: EncodeReferentBehavior,
+    /// The hash of each instance's path that has been computed so far.
+    path_hashes: HashMap<Ref, u128>,
+    /// How many earlier siblings of each instance have the same name as it.
+    duplicate_indices: HashMap<Ref, u32>,
+    referents: HashMap<Ref, String>,
+    used: HashSet<String>,
+}
+
+impl<'dom> ReferentGenerator<'dom> {
+    fn new(tree: &'dom WeakDom, behavior: EncodeReferentBehavior) -> Self {
+        ReferentGenerator {
+            tree,
+            behavior,
+            path_hashes: HashMap::new(),
+            duplicate_indices: HashMap::new(),
+            referents: HashMap::new(),
+            used: HashSet::new(),
+        }
+    }
+
+    /// Generates referents for the given instances, their descendants, and
+    /// any instances that they point to with `Ref` properties.
+    fn generate(mut self, ids: &[Ref]) -> HashMap<Ref, String> {
+        for &id in ids {
+            let instances = self
+                .tree
+                .get_by_ref(id)
+                .into_iter()
+                .chain(self.tree.descendants(id));
+
+            for instance in instances {
+                self.add(instance.referent());
+
+                for value in instance.properties.values() {
+                    if let Variant::Ref(target) = value {
+                        if self.tree.get_by_ref(*target).is_some() {
+                            self.add(*target);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.referents
+    }
+
+    fn add(&mut self, referent: Ref) {
+        if self.referents.contains_key(&referent) {
+            return;
+        }
+
+        let mut hash = self.path_hash(referent);
+        let mut candidate = None;
+
+        if self.behavior == EncodeReferentBehavior::UniqueId {
+            let instance = self.tree.get_by_ref(referent).unwrap();
+            if let Some(Variant::UniqueId(unique_id)) = instance.properties.get("UniqueId") {
+                if !unique_id.is_nil() {
+                    candidate = Some(format!("RBX{}", unique_id.to_string().to_uppercase()));
+                }
+            }
+        }
+
+        let mut generated = candidate.unwrap_or_else(|| format!("RBX{:032X}", hash));
+
+        // Collisions are unlikely, but would make the file unreadable.
+        while self.used.contains(&generated) {
+            hash = fnv1a(hash, &[0]);
+            generated = format!("RBX{:032X}", hash);
+        }
+
+        self.used.insert(generated.clone());
+        self.referents.insert(referent, generated);
+    }
+
+    /// Hashes the names of the given instance and each of its ancestors, along
+    /// with how many earlier siblings share each of those names.
+    fn path_hash(&mut self, referent: Ref) -> u128 {
+        let mut uncached = Vec::new();
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut current = referent;
+
+        // Walk up until we find an ancestor whose hash we already know, which
+        // also avoids recursing through very deep trees.
+        while let Some(instance) = self.tree.get_by_ref(current) {
+            if let Some(&cached) = self.path_hashes.get(&current) {
+                hash = cached;
+                break;
+            }
+
+            uncached.push(instance);
+            current = instance.parent();
+        }
+
+        for instance in uncached.into_iter().rev() {
+            let duplicate_index = self.duplicate_index(instance.referent());
+
+            hash = fnv1a(hash, &(instance.name.len() as u64).to_le_bytes());
+            hash = fnv1a(hash, instance.name.as_bytes());
+            hash = fnv1a(hash, &duplicate_index.to_le_bytes());
+            self.path_hashes.insert(instance.referent(), hash);
+        }
+
+        hash
+    }
+
+    fn duplicate_index(&mut self, referent: Ref) -> u32 {
+        if let Some(&index) = self.duplicate_indices.get(&referent) {
+            return index;
+        }
+
+        let parent = self.tree.get_by_ref(referent).unwrap().parent();
+        let siblings = match self.tree.get_by_ref(parent) {
+            Some(parent) => parent.children(),
+            None => return 0,
+        };
+
+        // Index every sibling at once so that instances with many children
+        // don't take quadratic time.
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        for &sibling in siblings {
+            let name = self.tree.get_by_ref(sibling).unwrap().name.as_str();
+            let count = counts.entry(name).or_insert(0);
+            self.duplicate_indices.insert(sibling, *count);
+            *count += 1;
+        }
+
+        self.duplicate_indices[&referent]
+    }
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+/// Hashes the given bytes with 128-bit FNV-1a, starting from `hash`. Unlike the
+/// hashers in the standard library, the output is the same on every run.
+fn fnv1a(mut hash: u128, bytes: &[u8]) -> u128 {
+    for &byte in bytes {
+        hash ^= u128::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
 /// Serializes shared strings into a SharedStrings XML block.
 fn serialize_shared_strings<W: Write>(
     writer: &mut XmlEventWriter<W>,
@@ -3,8 +3,8 @@
 use rbx_dom_weak::types::{
     Attributes, BinaryString, BrickColor, Color3, Color3uint8, ColorSequence,
     ColorSequenceKeypoint, Enum, Font, MaterialColors, NumberRange, NumberSequence,
-    NumberSequenceKeypoint, Rect, Tags, TerrainMaterials, UDim, UDim2, UniqueId, Variant, Vector2,
-    Vector3,
+    NumberSequenceKeypoint, OpaqueFormat, Rect, Tags, TerrainMaterials, UDim, UDim2, UniqueId,
+    Variant, Vector2, Vector3,
 };
 use rbx_dom_weak::{InstanceBuilder, WeakDom};
 
@@ -345,3 +345,303 @@ fn bad_migrated_property() {
     crate::to_writer_default(&mut encoded, &tree, &[tree.root_ref()]).unwrap();
     insta::assert_snapshot!(std::str::from_utf8(&encoded).unwrap());
 }
+
+#[test]
+fn filter_classes_and_properties() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="folder">
+                <Properties>
+                    <string name="Name">Map</string>
+                    <BinaryString name="Tags">SGVsbG8=</BinaryString>
+                </Properties>
+                <Item class="Script" referent="script">
+                    <Properties>
+                        <string name="Name">Spawner</string>
+                        <ProtectedString name="Source">print('spawn')</ProtectedString>
+                        <bool name="Disabled">true</bool>
+                    </Properties>
+                </Item>
+                <Item class="StringValue" referent="value">
+                    <Properties>
+                        <string name="Name">Version</string>
+                        <string name="Value">1.0</string>
+                    </Properties>
+                </Item>
+            </Item>
+        </roblox>
+    "#;
+
+    let options = crate::DecodeOptions::new()
+        .only_classes(["LuaSourceContainer"])
+        .only_properties(["Source"]);
+    let tree = crate::from_str(document, options).unwrap();
+
+    let folder = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(folder.name, "Map");
+    assert!(folder.properties.is_empty());
+
+    let script = tree.get_by_ref(folder.children()[0]).unwrap();
+    assert_eq!(script.name, "Spawner");
+    assert_eq!(script.properties.len(), 1);
+    assert_eq!(
+        script.properties.get("Source"),
+        Some(&Variant::String("print('spawn')".to_owned()))
+    );
+
+    let value = tree.get_by_ref(folder.children()[1]).unwrap();
+    assert_eq!(value.name, "Version");
+    assert!(value.properties.is_empty());
+}
+
+#[test]
+fn filter_prune() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="folder">
+                <Properties>
+                    <string name="Name">Map</string>
+                </Properties>
+                <Item class="Model" referent="model">
+                    <Properties>
+                        <string name="Name">Enemy</string>
+                    </Properties>
+                    <Item class="Script" referent="script">
+                        <Properties>
+                            <string name="Name">AI</string>
+                        </Properties>
+                    </Item>
+                    <Item class="Part" referent="part">
+                        <Properties>
+                            <string name="Name">Body</string>
+                        </Properties>
+                    </Item>
+                </Item>
+                <Item class="Part" referent="floor">
+                    <Properties>
+                        <string name="Name">Floor</string>
+                    </Properties>
+                </Item>
+            </Item>
+        </roblox>
+    "#;
+
+    let options = crate::DecodeOptions::new()
+        .only_classes(["Script"])
+        .prune_filtered(true);
+    let tree = crate::from_str(document, options).unwrap();
+
+    let names: Vec<&str> = tree
+        .descendants(tree.root_ref())
+        .map(|instance| instance.name.as_str())
+        .collect();
+
+    assert_eq!(names, ["Map", "Enemy", "AI"]);
+}
+
+#[test]
+fn metadata_round_trip() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Meta name="ExplicitAutoJoints">true</Meta>
+            <Item class="Folder" referent="folder">
+                <Properties>
+                    <string name="Name">Folder</string>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let tree = crate::from_str_default(document).unwrap();
+    assert_eq!(
+        tree.metadata()
+            .get("ExplicitAutoJoints")
+            .map(String::as_str),
+        Some("true")
+    );
+
+    let mut encoded = Vec::new();
+    crate::to_writer_default(&mut encoded, &tree, tree.root().children()).unwrap();
+
+    let round_tripped = crate::from_reader_default(encoded.as_slice()).unwrap();
+    assert_eq!(round_tripped.metadata(), tree.metadata());
+}
+
+#[test]
+fn unknown_type_round_trip() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Part" referent="part">
+                <Properties>
+                    <string name="Name">Part</string>
+                    <FutureBool name="Anchored"><value kind="new">1</value><empty></empty></FutureBool>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let tree = crate::from_str_default(document).unwrap();
+    let part = tree.get_by_ref(tree.root().children()[0]).unwrap();
+
+    let value = match part.properties.get("Anchored") {
+        Some(Variant::Opaque(value)) => value.clone(),
+        other => panic!("expected an opaque value, got {:?}", other),
+    };
+    assert_eq!(
+        value.format(),
+        &OpaqueFormat::Xml {
+            tag: "FutureBool".to_owned()
+        }
+    );
+    assert_eq!(
+        value.data(),
+        br#"<value kind="new">1</value><empty></empty>"#
+    );
+
+    let mut encoded = Vec::new();
+    crate::to_writer_default(&mut encoded, &tree, tree.root().children()).unwrap();
+
+    let round_tripped = crate::from_reader_default(encoded.as_slice()).unwrap();
+    let part = round_tripped
+        .get_by_ref(round_tripped.root().children()[0])
+        .unwrap();
+    assert_eq!(
+        part.properties.get("Anchored"),
+        Some(&Variant::Opaque(value))
+    );
+}
+
+#[test]
+fn lenient_decoding() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Part" referent="part">
+                <Properties>
+                    <string name="Name">Part</string>
+                    <Vector3 name="size"><X>wide</X><Y>1</Y><Z>1</Z></Vector3>
+                    <bool name="Anchored">true</bool>
+                </Properties>
+            </Item>
+            <Item class="Folder" referent="folder">
+                <Properties>
+                    <bool name="Name">true</bool>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    assert!(crate::from_str_default(document).is_err());
+
+    let (tree, warnings) = crate::from_str_lenient(document, crate::DecodeOptions::new()).unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].line(), 6);
+
+    let part = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(part.name, "Part");
+    assert_eq!(part.properties.get("Size"), None);
+    assert_eq!(part.properties.get("Anchored"), Some(&Variant::Bool(true)));
+
+    let folder = tree.get_by_ref(tree.root().children()[1]).unwrap();
+    assert_eq!(folder.name, "Folder");
+}
+
+#[test]
+fn lenient_truncated() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="first">
+                <Properties>
+                    <string name="Name">First</string>
+                </Properties>
+            </Item>
+            <Item class="Folder" referent="second">
+                <Properties>
+                    <string name="Name">Sec"#;
+
+    let (tree, warnings) = crate::from_str_lenient(document, crate::DecodeOptions::new()).unwrap();
+    assert_eq!(warnings.len(), 1);
+
+    let first = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(first.name, "First");
+
+    // Documents that aren't models at all are still rejected.
+    assert!(crate::from_str_lenient("<html></html>", crate::DecodeOptions::new()).is_err());
+}
+
+#[test]
+fn stable_referents() {
+    let _ = env_logger::try_init();
+
+    fn referents(tree: &WeakDom, behavior: crate::EncodeReferentBehavior) -> Vec<String> {
+        let mut encoded = Vec::new();
+        let options = crate::EncodeOptions::new().referent_behavior(behavior);
+        crate::to_writer(&mut encoded, tree, tree.root().children(), options).unwrap();
+
+        let round_tripped = crate::from_reader_default(encoded.as_slice()).unwrap();
+        let model = round_tripped.root().children()[0];
+        let primary_part = &round_tripped.get_by_ref(model).unwrap().properties["PrimaryPart"];
+        assert_eq!(
+            primary_part,
+            &Variant::Ref(round_tripped.get_by_ref(model).unwrap().children()[1])
+        );
+
+        let encoded = String::from_utf8(encoded).unwrap();
+        encoded
+            .split("referent=\"")
+            .skip(1)
+            .map(|rest| rest[..rest.find('"').unwrap()].to_owned())
+            .collect()
+    }
+
+    let unique_id = UniqueId::new(1, 2, 3);
+    let handle = InstanceBuilder::new("Part").with_name("Handle");
+    let handle_ref = handle.referent();
+    let mut tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", handle_ref)
+                .with_property("UniqueId", unique_id)
+                .with_child(InstanceBuilder::new("Part").with_name("Handle"))
+                .with_child(handle),
+        ),
+    );
+
+    let sequential = referents(&tree, crate::EncodeReferentBehavior::Sequential);
+    // The handle is numbered as soon as PrimaryPart refers to it.
+    assert_eq!(sequential, ["0", "2", "1"]);
+
+    let by_path = referents(&tree, crate::EncodeReferentBehavior::Path);
+    let by_unique_id = referents(&tree, crate::EncodeReferentBehavior::UniqueId);
+    assert_eq!(by_unique_id[0], "RBX00000000000000030000000200000001");
+    assert_eq!(by_unique_id[1..], by_path[1..]);
+
+    for referent in &by_path {
+        assert!(referent.starts_with("RBX") && referent.len() == 35);
+    }
+    assert_ne!(by_path[1], by_path[2]);
+
+    // Adding an instance after the others doesn't change their referents.
+    let model = tree.root().children()[0];
+    tree.insert(model, InstanceBuilder::new("Script"));
+    let after_insert = referents(&tree, crate::EncodeReferentBehavior::Path);
+    assert_eq!(after_insert[..3], by_path[..]);
+
+    // Renaming the model changes the referents derived from its path, but not
+    // the one derived from its UniqueId.
+    tree.set_name(model, "Sword");
+    let renamed = referents(&tree, crate::EncodeReferentBehavior::UniqueId);
+    assert_eq!(renamed[0], by_unique_id[0]);
+    assert_ne!(renamed[1], by_unique_id[1]);
+}
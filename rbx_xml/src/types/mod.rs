@@ -22,6 +22,7 @@ mod material_colors;
 mod number_range;
 mod number_sequence;
 mod numbers;
+mod opaque;
 mod optional_cframe;
 mod physical_properties;
 mod ray;
@@ -55,6 +56,7 @@ use crate::{
 use self::{
     attributes::write_attributes,
     material_colors::write_material_colors,
+    opaque::{read_opaque, write_opaque},
     referent::{read_ref, write_ref},
     shared_string::{read_shared_string, write_shared_string},
     tags::write_tags,
@@ -89,9 +91,8 @@ macro_rules! declare_rbx_types {
 
                 _ => {
                     state.unknown_type_visited(instance_id, property_name, xml_type_name);
-                    reader.eat_unknown_tag()?;
 
-                    Ok(None)
+                    Ok(Some(Variant::Opaque(read_opaque(reader, xml_type_name)?)))
                 },
             }
         }
@@ -117,6 +118,7 @@ macro_rules! declare_rbx_types {
                 Variant::Tags(value) => write_tags(writer, xml_property_name, value),
                 Variant::Attributes(value) => write_attributes(writer, xml_property_name, value),
                 Variant::MaterialColors(value) => write_material_colors(writer, xml_property_name, value),
+                Variant::Opaque(value) => write_opaque(writer, xml_property_name, value),
 
                 unknown => {
                     Err(writer.error(EncodeErrorKind::UnsupportedPropertyType(unknown.ty())))
@@ -0,0 +1,129 @@
+//! Opaque values hold properties whose XML type rbx_xml doesn't understand.
+//!
+//! The contents of the tag are kept as a string of XML so that they can be
+//! written back out without rbx_xml needing to know what they mean.
+
+use std::io::{Read, Write};
+
+use rbx_dom_weak::types::{Opaque, OpaqueFormat};
+use xml::{reader::ParserConfig, EmitterConfig};
+
+use crate::{
+    deserializer_core::{XmlEventReader, XmlReadEvent},
+    error::{DecodeError, EncodeError, EncodeErrorKind},
+    serializer_core::{XmlEventWriter, XmlWriteEvent},
+};
+
+/// The tag that the contents of opaque values are wrapped in while they're
+/// converted to and from XML strings, since the contents may not have a single
+/// root element.
+const WRAPPER_TAG: &str = "opaque";
+
+pub fn read_opaque<R: Read>(
+    reader: &mut XmlEventReader<R>,
+    xml_type_name: &str,
+) -> Result<Opaque, DecodeError> {
+    reader.expect_start_with_name(xml_type_name)?;
+
+    let mut buffer = Vec::new();
+    let mut writer = EmitterConfig::new()
+        .write_document_declaration(false)
+        .normalize_empty_elements(false)
+        .create_writer(&mut buffer);
+
+    writer
+        .write(XmlWriteEvent::start_element(WRAPPER_TAG))
+        .expect("rbx_xml bug: could not write opaque value");
+
+    let mut depth = 0;
+    loop {
+        let event = reader.expect_next()?;
+
+        match &event {
+            XmlReadEvent::StartElement { .. } => depth += 1,
+            XmlReadEvent::EndElement { .. } => {
+                if depth == 0 {
+                    break;
+                }
+
+                depth -= 1;
+            }
+            _ => {}
+        }
+
+        if let Some(event) = event.as_writer_event() {
+            writer
+                .write(event)
+                .expect("rbx_xml bug: could not write opaque value");
+        }
+    }
+
+    writer
+        .write(XmlWriteEvent::end_element())
+        .expect("rbx_xml bug: could not write opaque value");
+
+    let contents = std::str::from_utf8(&buffer).expect("rbx_xml bug: opaque value was not UTF-8");
+    let contents = contents
+        .strip_prefix(&format!("<{}>", WRAPPER_TAG))
+        .and_then(|contents| contents.strip_suffix(&format!("</{}>", WRAPPER_TAG)))
+        .expect("rbx_xml bug: opaque value was not wrapped");
+
+    Ok(Opaque::xml(xml_type_name, contents.as_bytes()))
+}
+
+pub fn write_opaque<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    xml_property_name: &str,
+    value: &Opaque,
+) -> Result<(), EncodeError> {
+    let tag = match value.format() {
+        OpaqueFormat::Xml { tag } => tag,
+        _ => {
+            log::warn!(
+                "Property {} holds an opaque value from another format and will not be written",
+                xml_property_name
+            );
+            return Ok(());
+        }
+    };
+
+    let mut wrapped = format!("<{}>", WRAPPER_TAG).into_bytes();
+    wrapped.extend_from_slice(value.data());
+    wrapped.extend_from_slice(format!("</{}>", WRAPPER_TAG).as_bytes());
+
+    writer.write(XmlWriteEvent::start_element(tag.as_str()).attr("name", xml_property_name))?;
+
+    let mut depth = 0;
+    for event in ParserConfig::new().create_reader(wrapped.as_slice()) {
+        let event = event.map_err(|err| writer.error(EncodeErrorKind::InvalidOpaqueValue(err)))?;
+
+        match &event {
+            XmlReadEvent::StartElement { .. } => {
+                depth += 1;
+
+                if depth == 1 {
+                    continue;
+                }
+            }
+            XmlReadEvent::EndElement { .. } => {
+                depth -= 1;
+
+                if depth == 0 {
+                    continue;
+                }
+            }
+            XmlReadEvent::Whitespace(_) => continue,
+            _ => {}
+        }
+
+        if depth > 0 {
+            if let Some(event) = event.as_writer_event() {
+                writer.write(event)?;
+            }
+        }
+    }
+
+    writer.write(XmlWriteEvent::end_element())?;
+
+    Ok(())
+}